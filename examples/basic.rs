@@ -8,13 +8,13 @@ async fn main() {
 
     // Simple GET route
     app.get("/", |_req: Request| async {
-        Ok(Response::text("Hello, Ruffus!".to_string()))
+        Ok::<_, ruffus::Error>(Response::text("Hello, Ruffus!".to_string()))
     });
 
     // Route with path parameter
     app.get("/hello/:name", |req: Request| async move {
         let name = req.param("name").unwrap_or("World");
-        Ok(Response::text(format!("Hello, {}!", name)))
+        Ok::<_, ruffus::Error>(Response::text(format!("Hello, {}!", name)))
     });
 
     // JSON response
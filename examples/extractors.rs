@@ -1,6 +1,6 @@
 //! Example demonstrating extractor patterns in Ruffus
 
-use ruffus::{App, FromRequest, Json, Path, Query, Response};
+use ruffus::{App, Json, Path, Query};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
@@ -27,27 +27,33 @@ struct User {
     email: String,
 }
 
+async fn update_user(Path(params): Path<UserPath>, Json(body): Json<CreateUser>) -> Json<User> {
+    let user = User {
+        id: params.id,
+        name: body.name,
+        email: body.email,
+    };
+
+    Json(user)
+}
+
 #[tokio::main]
 async fn main() {
     let mut app = App::new();
 
     // Using Path extractor for path parameters
-    app.get("/users/:id", |mut req: ruffus::Request| async move {
-        let Path(params): Path<UserPath> = Path::from_request(&mut req).await?;
-        
+    app.get("/users/:id", |Path(params): Path<UserPath>| async move {
         let user = User {
             id: params.id,
             name: "John Doe".to_string(),
             email: "john@example.com".to_string(),
         };
         
-        Response::json(&user)
+        Json(user)
     });
 
     // Using Query extractor for query parameters
-    app.get("/users", |mut req: ruffus::Request| async move {
-        let Query(params): Query<Pagination> = Query::from_request(&mut req).await?;
-        
+    app.get("/users", |Query(params): Query<Pagination>| async move {
         let users = vec![
             User {
                 id: 1,
@@ -64,27 +70,29 @@ async fn main() {
         // In a real app, you'd use page and limit to paginate
         println!("Fetching page {} with limit {}", params.page, params.limit);
         
-        Response::json(&users)
+        Json(users)
     });
 
     // Using Json extractor for request body
-    app.post("/users", |mut req: ruffus::Request| async move {
-        let Json(body): Json<CreateUser> = Json::from_request(&mut req).await?;
-        
+    app.post("/users", |Json(body): Json<CreateUser>| async move {
         let user = User {
             id: 123,
             name: body.name,
             email: body.email,
         };
         
-        Response::json(&user)
+        Json(user)
     });
 
+    // Combining extractors; the body extractor comes last
+    app.put("/users/:id", update_user);
+
     println!("Starting Ruffus server with extractors...");
     println!("Try:");
     println!("  GET  http://localhost:3000/users/42");
     println!("  GET  http://localhost:3000/users?page=1&limit=10");
     println!("  POST http://localhost:3000/users (with JSON body)");
+    println!("  PUT  http://localhost:3000/users/42 (with JSON body)");
     
     app.listen("127.0.0.1:3000").await.unwrap();
 }
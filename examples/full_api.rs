@@ -4,16 +4,18 @@
 //! - CRUD operations
 //! - Middleware (logging, auth, CORS)
 //! - Router organization
+//! - Shared state
 //! - Error handling
 //! - JSON request/response handling
 //! - Path and query parameters
 
 use async_trait::async_trait;
-use ruffus::{App, Middleware, Next, Request, Response, Result, Router};
+use ruffus::cors::Cors;
+use ruffus::{App, Middleware, Next, Request, Response, Result, Router, State};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use http::StatusCode;
 
 // ============================================================================
@@ -142,23 +144,6 @@ impl Middleware for Timer {
     }
 }
 
-/// CORS middleware
-struct Cors;
-
-#[async_trait]
-impl Middleware for Cors {
-    async fn handle(&self, req: Request, next: Next) -> Result<Response> {
-        let response = next.run(req).await?;
-        
-        let response = response
-            .header("Access-Control-Allow-Origin", "*")
-            .header("Access-Control-Allow-Methods", "GET, POST, PUT, DELETE, PATCH, OPTIONS")
-            .header("Access-Control-Allow-Headers", "Content-Type, Authorization");
-        
-        Ok(response)
-    }
-}
-
 /// Simple API key authentication
 struct ApiKeyAuth {
     api_key: String,
@@ -196,204 +181,184 @@ impl Middleware for ApiKeyAuth {
 // Route Handlers
 // ============================================================================
 
-fn create_task_router(db: Database) -> Router {
+fn create_task_router() -> Router {
     let mut router = Router::new("/tasks");
 
     // GET /tasks - List all tasks with optional filtering
-    let db_clone = db.clone();
-    router.get("", move |req: Request| {
-        let db = db_clone.clone();
-        async move {
-            let db = db.lock().unwrap();
-            
-            // Check for completed filter in query params
-            let completed_filter = req.query("completed");
-            
-            let tasks: Vec<Task> = db.values()
-                .filter(|task| {
-                    if let Some(filter) = completed_filter {
-                        if filter == "true" {
-                            task.completed
-                        } else if filter == "false" {
-                            !task.completed
-                        } else {
-                            true
-                        }
+    router.get("", |State(db): State<Database>, req: Request| async move {
+        let db = db.lock().unwrap();
+        
+        // Check for completed filter in query params
+        let completed_filter = req.query("completed");
+        
+        let tasks: Vec<Task> = db.values()
+            .filter(|task| {
+                if let Some(filter) = completed_filter {
+                    if filter == "true" {
+                        task.completed
+                    } else if filter == "false" {
+                        !task.completed
                     } else {
                         true
                     }
-                })
-                .cloned()
-                .collect();
-            
-            Response::json(&ApiResponse::success(tasks))
-        }
+                } else {
+                    true
+                }
+            })
+            .cloned()
+            .collect();
+        
+        Response::json(&ApiResponse::success(tasks))
     });
 
     // GET /tasks/:id - Get a specific task
-    let db_clone = db.clone();
-    router.get("/:id", move |req: Request| {
-        let db = db_clone.clone();
-        async move {
-            let id = req.param("id").unwrap_or("0");
-            let task_id: u32 = match id.parse() {
-                Ok(id) => id,
-                Err(_) => {
-                    return Response::json(&ApiResponse::<()>::error(
-                        "Invalid task ID".to_string()
-                    )).map(|r| {
-                        r.status(StatusCode::BAD_REQUEST)
-                    });
-                }
-            };
-            
-            let db = db.lock().unwrap();
-            
-            match db.get(&task_id) {
-                Some(task) => Response::json(&ApiResponse::success(task.clone())),
-                None => Response::json(&ApiResponse::<()>::error(
-                    "Task not found".to_string()
+    router.get("/:id", |State(db): State<Database>, req: Request| async move {
+        let id = req.param("id").unwrap_or("0");
+        let task_id: u32 = match id.parse() {
+            Ok(id) => id,
+            Err(_) => {
+                return Response::json(&ApiResponse::<()>::error(
+                    "Invalid task ID".to_string()
                 )).map(|r| {
-                    r.status(StatusCode::NOT_FOUND)
-                }),
+                    r.status(StatusCode::BAD_REQUEST)
+                });
             }
+        };
+        
+        let db = db.lock().unwrap();
+        
+        match db.get(&task_id) {
+            Some(task) => Response::json(&ApiResponse::success(task.clone())),
+            None => Response::json(&ApiResponse::<()>::error(
+                "Task not found".to_string()
+            )).map(|r| {
+                r.status(StatusCode::NOT_FOUND)
+            }),
         }
     });
 
     // POST /tasks - Create a new task
-    let db_clone = db.clone();
-    router.post("", move |mut req: Request| {
-        let db = db_clone.clone();
-        async move {
-            let body: CreateTaskRequest = match req.json().await {
-                Ok(body) => body,
-                Err(e) => {
-                    return Response::json(&ApiResponse::<()>::error(
-                        format!("Invalid JSON: {}", e)
-                    )).map(|r| {
-                        r.status(StatusCode::BAD_REQUEST)
-                    });
-                }
-            };
-            
-            // Validate
-            if body.title.trim().is_empty() {
+    router.post("", |State(db): State<Database>, mut req: Request| async move {
+        let body: CreateTaskRequest = match req.json().await {
+            Ok(body) => body,
+            Err(e) => {
                 return Response::json(&ApiResponse::<()>::error(
-                    "Title cannot be empty".to_string()
+                    format!("Invalid JSON: {}", e)
                 )).map(|r| {
                     r.status(StatusCode::BAD_REQUEST)
                 });
             }
-            
-            let mut db = db.lock().unwrap();
-            
-            // Generate new ID
-            let new_id = db.keys().max().unwrap_or(&0) + 1;
-            
-            let task = Task {
-                id: new_id,
-                title: body.title,
-                description: body.description,
-                completed: false,
-                created_at: chrono::Utc::now().to_rfc3339(),
-            };
-            
-            db.insert(new_id, task.clone());
-            
-            Response::json(&ApiResponse::success(task)).map(|r| {
-                r.status(StatusCode::CREATED)
-            })
+        };
+        
+        // Validate
+        if body.title.trim().is_empty() {
+            return Response::json(&ApiResponse::<()>::error(
+                "Title cannot be empty".to_string()
+            )).map(|r| {
+                r.status(StatusCode::BAD_REQUEST)
+            });
         }
+        
+        let mut db = db.lock().unwrap();
+        
+        // Generate new ID
+        let new_id = db.keys().max().unwrap_or(&0) + 1;
+        
+        let task = Task {
+            id: new_id,
+            title: body.title,
+            description: body.description,
+            completed: false,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        
+        db.insert(new_id, task.clone());
+        
+        Response::json(&ApiResponse::success(task)).map(|r| {
+            r.status(StatusCode::CREATED)
+        })
     });
 
     // PUT /tasks/:id - Update a task
-    let db_clone = db.clone();
-    router.put("/:id", move |mut req: Request| {
-        let db = db_clone.clone();
-        async move {
-            let id = req.param("id").unwrap_or("0");
-            let task_id: u32 = match id.parse() {
-                Ok(id) => id,
-                Err(_) => {
-                    return Response::json(&ApiResponse::<()>::error(
-                        "Invalid task ID".to_string()
-                    )).map(|r| {
-                        r.status(StatusCode::BAD_REQUEST)
-                    });
+    router.put("/:id", |State(db): State<Database>, mut req: Request| async move {
+        let id = req.param("id").unwrap_or("0");
+        let task_id: u32 = match id.parse() {
+            Ok(id) => id,
+            Err(_) => {
+                return Response::json(&ApiResponse::<()>::error(
+                    "Invalid task ID".to_string()
+                )).map(|r| {
+                    r.status(StatusCode::BAD_REQUEST)
+                });
+            }
+        };
+        
+        let body: UpdateTaskRequest = match req.json().await {
+            Ok(body) => body,
+            Err(e) => {
+                return Response::json(&ApiResponse::<()>::error(
+                    format!("Invalid JSON: {}", e)
+                )).map(|r| {
+                    r.status(StatusCode::BAD_REQUEST)
+                });
+            }
+        };
+        
+        let mut db = db.lock().unwrap();
+        
+        match db.get_mut(&task_id) {
+            Some(task) => {
+                if let Some(title) = body.title {
+                    if !title.trim().is_empty() {
+                        task.title = title;
+                    }
                 }
-            };
-            
-            let body: UpdateTaskRequest = match req.json().await {
-                Ok(body) => body,
-                Err(e) => {
-                    return Response::json(&ApiResponse::<()>::error(
-                        format!("Invalid JSON: {}", e)
-                    )).map(|r| {
-                        r.status(StatusCode::BAD_REQUEST)
-                    });
+                if let Some(description) = body.description {
+                    task.description = description;
                 }
-            };
-            
-            let mut db = db.lock().unwrap();
-            
-            match db.get_mut(&task_id) {
-                Some(task) => {
-                    if let Some(title) = body.title {
-                        if !title.trim().is_empty() {
-                            task.title = title;
-                        }
-                    }
-                    if let Some(description) = body.description {
-                        task.description = description;
-                    }
-                    if let Some(completed) = body.completed {
-                        task.completed = completed;
-                    }
-                    
-                    Response::json(&ApiResponse::success(task.clone()))
+                if let Some(completed) = body.completed {
+                    task.completed = completed;
                 }
-                None => Response::json(&ApiResponse::<()>::error(
-                    "Task not found".to_string()
-                )).map(|r| {
-                    r.status(StatusCode::NOT_FOUND)
-                }),
+                
+                Response::json(&ApiResponse::success(task.clone()))
             }
+            None => Response::json(&ApiResponse::<()>::error(
+                "Task not found".to_string()
+            )).map(|r| {
+                r.status(StatusCode::NOT_FOUND)
+            }),
         }
     });
 
     // DELETE /tasks/:id - Delete a task
-    let db_clone = db.clone();
-    router.delete("/:id", move |req: Request| {
-        let db = db_clone.clone();
-        async move {
-            let id = req.param("id").unwrap_or("0");
-            let task_id: u32 = match id.parse() {
-                Ok(id) => id,
-                Err(_) => {
-                    return Response::json(&ApiResponse::<()>::error(
-                        "Invalid task ID".to_string()
-                    )).map(|r| {
-                        r.status(StatusCode::BAD_REQUEST)
-                    });
-                }
-            };
-            
-            let mut db = db.lock().unwrap();
-            
-            match db.remove(&task_id) {
-                Some(_) => {
-                    use serde_json::json;
-                    Response::json(&json!({
-                        "success": true,
-                        "message": format!("Task {} deleted successfully", task_id)
-                    }))
-                }
-                None => Response::json(&ApiResponse::<()>::error(
-                    "Task not found".to_string()
+    router.delete("/:id", |State(db): State<Database>, req: Request| async move {
+        let id = req.param("id").unwrap_or("0");
+        let task_id: u32 = match id.parse() {
+            Ok(id) => id,
+            Err(_) => {
+                return Response::json(&ApiResponse::<()>::error(
+                    "Invalid task ID".to_string()
                 )).map(|r| {
-                    r.status(StatusCode::NOT_FOUND)
-                }),
+                    r.status(StatusCode::BAD_REQUEST)
+                });
             }
+        };
+        
+        let mut db = db.lock().unwrap();
+        
+        match db.remove(&task_id) {
+            Some(_) => {
+                use serde_json::json;
+                Response::json(&json!({
+                    "success": true,
+                    "message": format!("Task {} deleted successfully", task_id)
+                }))
+            }
+            None => Response::json(&ApiResponse::<()>::error(
+                "Task not found".to_string()
+            )).map(|r| {
+                r.status(StatusCode::NOT_FOUND)
+            }),
         }
     });
 
@@ -406,15 +371,18 @@ fn create_task_router(db: Database) -> Router {
 
 #[tokio::main]
 async fn main() {
-    let mut app = App::new();
-    
-    // Initialize database
-    let db = create_database();
+    // Initialize database; handlers get it through the State extractor
+    let mut app = App::with_state(create_database());
 
     // Add global middleware
     app.use_middleware(Arc::new(Logger));
     app.use_middleware(Arc::new(Timer));
-    app.use_middleware(Arc::new(Cors));
+    app.use_middleware(Arc::new(
+        Cors::new()
+            .allow_any_origin()
+            .allow_headers(["Content-Type", "Authorization"])
+            .max_age(Duration::from_secs(3600)),
+    ));
 
     // Root endpoint
     app.get("/", |_req: Request| async {
@@ -452,33 +420,29 @@ async fn main() {
     let mut api_router = Router::new("/api");
     
     // Mount task router
-    api_router.mount("", create_task_router(db.clone()));
+    api_router.mount("", create_task_router());
     
     // Admin endpoints with authentication
     let mut admin_router = Router::new("/admin");
     admin_router.use_middleware(Arc::new(ApiKeyAuth::new("secret-admin-key".to_string())));
     
-    let db_clone = db.clone();
-    admin_router.get("/stats", move |_req: Request| {
-        let db = db_clone.clone();
-        async move {
-            let db = db.lock().unwrap();
-            let total = db.len();
-            let completed = db.values().filter(|t| t.completed).count();
-            let pending = total - completed;
-            
-            use serde_json::json;
-            Response::json(&json!({
-                "total_tasks": total,
-                "completed_tasks": completed,
-                "pending_tasks": pending,
-                "completion_rate": if total > 0 { 
-                    (completed as f64 / total as f64) * 100.0 
-                } else { 
-                    0.0 
-                }
-            }))
-        }
+    admin_router.get("/stats", |State(db): State<Database>| async move {
+        let db = db.lock().unwrap();
+        let total = db.len();
+        let completed = db.values().filter(|t| t.completed).count();
+        let pending = total - completed;
+        
+        use serde_json::json;
+        Response::json(&json!({
+            "total_tasks": total,
+            "completed_tasks": completed,
+            "pending_tasks": pending,
+            "completion_rate": if total > 0 { 
+                (completed as f64 / total as f64) * 100.0 
+            } else { 
+                0.0 
+            }
+        }))
     });
     
     api_router.mount("", admin_router);
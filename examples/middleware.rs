@@ -1,6 +1,7 @@
 //! Example demonstrating middleware usage in Ruffus
 
 use async_trait::async_trait;
+use ruffus::cors::Cors;
 use ruffus::{App, Middleware, Next, Request, Response, Result, Router};
 use std::sync::Arc;
 use std::time::Instant;
@@ -86,27 +87,6 @@ impl Middleware for Auth {
     }
 }
 
-/// CORS middleware that adds CORS headers to responses
-struct Cors;
-
-#[async_trait]
-impl Middleware for Cors {
-    async fn handle(&self, req: Request, next: Next) -> Result<Response> {
-        // Process the request
-        let response = next.run(req).await?;
-        
-        // Add CORS headers to the response
-        let response = response
-            .header("Access-Control-Allow-Origin", "*")
-            .header("Access-Control-Allow-Methods", "GET, POST, PUT, DELETE, OPTIONS")
-            .header("Access-Control-Allow-Headers", "Content-Type, Authorization");
-        
-        println!("[CORS] Added CORS headers");
-        
-        Ok(response)
-    }
-}
-
 /// Request ID middleware that adds a unique ID to each request
 struct RequestId;
 
@@ -140,11 +120,15 @@ async fn main() {
     app.use_middleware(Arc::new(Logger));
     app.use_middleware(Arc::new(Timer));
     app.use_middleware(Arc::new(RequestId));
-    app.use_middleware(Arc::new(Cors));
+    app.use_middleware(Arc::new(
+        Cors::new()
+            .allow_origins(["http://localhost:8080"])
+            .allow_headers(["Content-Type", "Authorization"]),
+    ));
 
     // Public route (no authentication required)
     app.get("/", |_req: Request| async {
-        Ok(Response::text("Welcome to Ruffus! This is a public endpoint.".to_string()))
+        Ok::<_, ruffus::Error>(Response::text("Welcome to Ruffus! This is a public endpoint.".to_string()))
     });
 
     // Public API info route
@@ -190,7 +174,7 @@ async fn main() {
     println!("  1. Logger    - Logs all requests");
     println!("  2. Timer     - Measures request duration");
     println!("  3. RequestId - Adds unique request ID");
-    println!("  4. CORS      - Adds CORS headers");
+    println!("  4. CORS      - Answers preflights and adds CORS headers");
     println!("  5. Auth      - Protects /protected and /admin routes");
     println!("\nTry these endpoints:");
     println!("  GET http://localhost:3000/");
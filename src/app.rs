@@ -13,14 +13,16 @@
 //!     let mut app = App::new();
 //!     
 //!     app.get("/", |_req: Request| async {
-//!         Ok(Response::text("Hello, World!".to_string()))
+//!         Response::text("Hello, World!".to_string())
 //!     });
 //!     
 //!     app.listen("127.0.0.1:3000").await.unwrap();
 //! }
 //! ```
 
-use crate::{Error, Method, Middleware, Request, Response, Result, Router};
+use crate::router::RouteMatch;
+use crate::request::{Extensions, DEFAULT_BODY_LIMIT};
+use crate::{Error, Handler, Listener, Method, Middleware, Request, Response, Result, Router, Server};
 use std::future::Future;
 use std::sync::Arc;
 
@@ -44,7 +46,7 @@ use std::sync::Arc;
 ///     let mut app = App::new();
 ///     
 ///     app.get("/hello", |_req: Request| async {
-///         Ok(Response::text("Hello!".to_string()))
+///         Response::text("Hello!".to_string())
 ///     });
 ///     
 ///     app.listen("127.0.0.1:3000").await.unwrap();
@@ -53,6 +55,8 @@ use std::sync::Arc;
 pub struct App {
     router: Router,
     middleware: Vec<Arc<dyn Middleware>>,
+    state: Extensions,
+    body_limit: usize,
 }
 
 impl App {
@@ -69,15 +73,69 @@ impl App {
         Self {
             router: Router::new(""),
             middleware: Vec::new(),
+            state: Extensions::new(),
+            body_limit: DEFAULT_BODY_LIMIT,
         }
     }
 
+    /// Creates a new Application instance with shared state.
+    ///
+    /// The state is cloned into the extensions of every request before any
+    /// middleware runs, so handlers can extract it with [`State<S>`](crate::State)
+    /// and middleware can read it with `req.extensions().get::<S>()`. Wrap
+    /// mutable data in an `Arc<Mutex<...>>` or similar so clones share it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ruffus::{App, Response, State};
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// type Database = Arc<Mutex<HashMap<u32, String>>>;
+    ///
+    /// let mut app = App::with_state(Database::default());
+    ///
+    /// app.get("/count", |State(db): State<Database>| async move {
+    ///     Response::text(db.lock().unwrap().len().to_string())
+    /// });
+    /// ```
+    pub fn with_state<S>(state: S) -> Self
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let mut app = Self::new();
+        app.state.insert(state);
+        app
+    }
+
+    /// Sets the largest request body in bytes that handlers will buffer.
+    ///
+    /// [`Request::body`], [`Request::json`] and the [`Json`](crate::Json)
+    /// extractor answer larger bodies with `413 Payload Too Large`. Streamed
+    /// bodies taken with [`Request::take_body`] aren't limited. Middleware can
+    /// change the limit per request with [`Request::set_body_limit`].
+    /// Defaults to 2 MiB.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruffus::App;
+    ///
+    /// let mut app = App::new();
+    /// app.body_limit(10 * 1024 * 1024);
+    /// ```
+    pub fn body_limit(&mut self, limit: usize) -> &mut Self {
+        self.body_limit = limit;
+        self
+    }
+
     /// Registers a GET route with the specified path and handler.
     ///
     /// # Arguments
     ///
     /// * `path` - The route pattern (e.g., "/users/:id")
-    /// * `handler` - An async function that handles the request
+    /// * `handler` - An async function taking a `Request` or up to 12 extractors
     ///
     /// # Examples
     ///
@@ -85,13 +143,28 @@ impl App {
     /// # use ruffus::{App, Request, Response};
     /// # let mut app = App::new();
     /// app.get("/users", |_req: Request| async {
-    ///     Ok(Response::text("List of users".to_string()))
+    ///     Response::text("List of users".to_string())
     /// });
     /// ```
-    pub fn get<F, Fut>(&mut self, path: &str, handler: F) -> &mut Self
+    ///
+    /// Handlers may take extractors instead of the raw request:
+    ///
+    /// ```no_run
+    /// # use ruffus::{App, Path, Query, Response};
+    /// # use serde::Deserialize;
+    /// # let mut app = App::new();
+    /// #[derive(Deserialize)]
+    /// struct Page {
+    ///     page: u32,
+    /// }
+    ///
+    /// app.get("/users/:id/posts", |Path(id): Path<u64>, Query(q): Query<Page>| async move {
+    ///     Response::text(format!("Posts of user {}, page {}", id, q.page))
+    /// });
+    /// ```
+    pub fn get<H, T>(&mut self, path: &str, handler: H) -> &mut Self
     where
-        F: Fn(Request) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Response>> + Send + 'static,
+        H: Handler<T>,
     {
         self.router.get(path, handler);
         self
@@ -102,7 +175,7 @@ impl App {
     /// # Arguments
     ///
     /// * `path` - The route pattern (e.g., "/users")
-    /// * `handler` - An async function that handles the request
+    /// * `handler` - An async function taking a `Request` or up to 12 extractors
     ///
     /// # Examples
     ///
@@ -111,13 +184,12 @@ impl App {
     /// # let mut app = App::new();
     /// app.post("/users", |mut req: Request| async move {
     ///     // Handle user creation
-    ///     Ok(Response::text("User created".to_string()))
+    ///     Response::text("User created".to_string())
     /// });
     /// ```
-    pub fn post<F, Fut>(&mut self, path: &str, handler: F) -> &mut Self
+    pub fn post<H, T>(&mut self, path: &str, handler: H) -> &mut Self
     where
-        F: Fn(Request) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Response>> + Send + 'static,
+        H: Handler<T>,
     {
         self.router.post(path, handler);
         self
@@ -128,11 +200,10 @@ impl App {
     /// # Arguments
     ///
     /// * `path` - The route pattern (e.g., "/users/:id")
-    /// * `handler` - An async function that handles the request
-    pub fn put<F, Fut>(&mut self, path: &str, handler: F) -> &mut Self
+    /// * `handler` - An async function taking a `Request` or up to 12 extractors
+    pub fn put<H, T>(&mut self, path: &str, handler: H) -> &mut Self
     where
-        F: Fn(Request) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Response>> + Send + 'static,
+        H: Handler<T>,
     {
         self.router.put(path, handler);
         self
@@ -143,11 +214,10 @@ impl App {
     /// # Arguments
     ///
     /// * `path` - The route pattern (e.g., "/users/:id")
-    /// * `handler` - An async function that handles the request
-    pub fn delete<F, Fut>(&mut self, path: &str, handler: F) -> &mut Self
+    /// * `handler` - An async function taking a `Request` or up to 12 extractors
+    pub fn delete<H, T>(&mut self, path: &str, handler: H) -> &mut Self
     where
-        F: Fn(Request) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Response>> + Send + 'static,
+        H: Handler<T>,
     {
         self.router.delete(path, handler);
         self
@@ -158,11 +228,10 @@ impl App {
     /// # Arguments
     ///
     /// * `path` - The route pattern (e.g., "/users/:id")
-    /// * `handler` - An async function that handles the request
-    pub fn patch<F, Fut>(&mut self, path: &str, handler: F) -> &mut Self
+    /// * `handler` - An async function taking a `Request` or up to 12 extractors
+    pub fn patch<H, T>(&mut self, path: &str, handler: H) -> &mut Self
     where
-        F: Fn(Request) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Response>> + Send + 'static,
+        H: Handler<T>,
     {
         self.router.patch(path, handler);
         self
@@ -217,7 +286,7 @@ impl App {
     /// # let mut app = App::new();
     /// let mut api = Router::new("/v1");
     /// api.get("/users", |_req: Request| async {
-    ///     Ok(Response::text("Users".to_string()))
+    ///     Response::text("Users".to_string())
     /// });
     ///
     /// app.mount("/api", api);
@@ -228,57 +297,105 @@ impl App {
         self
     }
 
+    /// Serves the files below `dir` at `prefix`.
+    ///
+    /// A shorthand for registering a [`ServeDir`](crate::fs::ServeDir) on
+    /// `{prefix}/*path`; use `ServeDir` directly to enable precompressed
+    /// files or a fallback file.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use ruffus::App;
+    /// # let mut app = App::new();
+    /// app.static_files("/assets", "public");
+    /// // GET /assets/css/site.css serves public/css/site.css
+    /// ```
+    pub fn static_files(&mut self, prefix: &str, dir: impl Into<std::path::PathBuf>) -> &mut Self {
+        let pattern = format!("{}/*path", prefix.trim_end_matches('/'));
+        self.router.get(&pattern, crate::fs::ServeDir::new(dir));
+        self
+    }
+
     /// Handles an incoming request through the middleware pipeline and routing.
     ///
     /// This method:
-    /// 1. Finds a matching route for the request
+    /// 1. Adds the shared state to the request extensions and applies the
+    ///    body limit, then finds a matching route
     /// 2. Extracts path parameters
-    /// 3. Executes the middleware stack
+    /// 3. Executes the global middleware followed by the route's own middleware
     /// 4. Invokes the route handler
     ///
     /// Returns a 404 error if no route matches, or a 405 error if the path exists
-    /// but the HTTP method doesn't match.
+    /// but the HTTP method doesn't match. The global middleware runs in that
+    /// case too, with the error as the result of `next.run`, so it can answer
+    /// requests no route handles, such as CORS preflights. So does the
+    /// middleware of a mounted router whose prefix covers the path.
     pub async fn handle_request(&self, mut req: Request) -> Result<Response> {
-        use crate::middleware::{Next};
-        
+        use crate::middleware::{BoxedHandler, Next};
+
+        // Make the shared state visible to middleware and handlers
+        req.extensions_mut().extend(self.state.clone());
+        req.set_body_limit(Some(self.body_limit));
+
         let method = Method::from(req.method().clone());
         let path = req.uri().path().to_string();
 
-        // Try to find a matching route
-        if let Some((route, params)) = self.router.find_route(&method, &path) {
-            // Set path parameters in the request
-            for (key, value) in params {
-                req.set_param(key, value);
-            }
+        // Resolve the route and the allowed methods in one lookup
+        let (stack, handler): (Vec<Arc<dyn Middleware>>, BoxedHandler) = match self.router.resolve(&method, &path) {
+            RouteMatch::Found(route, params) => {
+                // Set path parameters in the request
+                for (key, value) in params {
+                    req.set_param(key, value);
+                }
 
-            // Execute middleware stack with the route handler
-            if self.middleware.is_empty() {
-                // No middleware, execute handler directly
-                route.handle(req).await
-            } else {
-                // Create a handler that will execute the route
-                // We need to clone the handler function from the route
+                // Global middleware runs first, then middleware scoped to the route
+                let stack = self
+                    .middleware
+                    .iter()
+                    .chain(self.router.middleware())
+                    .chain(route.middleware())
+                    .cloned()
+                    .collect();
                 let handler_fn = route.handler_fn();
-                let handler = Arc::new(move |req: Request| {
-                    handler_fn(req)
-                });
-                
-                // Execute middleware stack with the handler
-                let next = Next::new(self.middleware.clone(), Some(handler));
-                next.run(req).await
+                (stack, Arc::new(move |req: Request| handler_fn(req)))
             }
-        } else {
-            // Check if path exists with different method
-            if self.router.path_exists(&path) {
-                let allowed = self.router.allowed_methods(&path);
+            // Global middleware, and that of the router the path falls under,
+            // still runs when no route matches, so it can answer requests such
+            // as CORS preflights or decorate the error
+            RouteMatch::MethodNotAllowed(allowed) => {
                 let allowed_http: Vec<http::Method> = allowed.into_iter().map(|m| m.into()).collect();
-                Err(Error::MethodNotAllowed(allowed_http))
-            } else {
-                Err(Error::RouteNotFound)
+                let handler: BoxedHandler = Arc::new(move |_req: Request| {
+                    let allowed = allowed_http.clone();
+                    Box::pin(async move { Err(Error::MethodNotAllowed(allowed)) })
+                });
+                (self.unmatched_middleware(&path), handler)
+            }
+            RouteMatch::NotFound => {
+                let handler: BoxedHandler = Arc::new(|_req: Request| Box::pin(async { Err(Error::RouteNotFound) }));
+                (self.unmatched_middleware(&path), handler)
             }
+        };
+
+        // Execute middleware stack with the final handler
+        if stack.is_empty() {
+            handler(req).await
+        } else {
+            Next::new(stack, Some(handler)).run(req).await
         }
     }
 
+    /// The middleware that runs for a request no route matches: the global
+    /// middleware, then that of the mounted router the path falls under.
+    fn unmatched_middleware(&self, path: &str) -> Vec<Arc<dyn Middleware>> {
+        self.middleware
+            .iter()
+            .chain(self.router.middleware())
+            .chain(self.router.scope_middleware(path))
+            .cloned()
+            .collect()
+    }
+
     /// Get the internal router (for testing)
     pub fn router(&self) -> &Router {
         &self.router
@@ -321,74 +438,107 @@ impl App {
     /// # }
     /// ```
     pub async fn listen(self, addr: &str) -> Result<()> {
-        use hyper::server::conn::http1;
-        use hyper::service::service_fn;
-        use hyper_util::rt::TokioIo;
-        use tokio::net::TcpListener;
-
-        // Parse the address
-        let addr = addr.parse::<std::net::SocketAddr>()
-            .map_err(|e| Error::InternalServerError(format!("Invalid address: {}", e)))?;
-
-        // Bind to the address
-        let listener = TcpListener::bind(addr)
-            .await
-            .map_err(|e| Error::InternalServerError(format!("Failed to bind: {}", e)))?;
-
-        println!("Ruffus server listening on http://{}", addr);
-
-        // Wrap self in Arc for sharing across connections
-        let app = Arc::new(self);
-
-        // Accept connections in a loop
-        loop {
-            let (stream, _) = listener.accept()
-                .await
-                .map_err(|e| Error::InternalServerError(format!("Failed to accept connection: {}", e)))?;
-
-            let io = TokioIo::new(stream);
-            let app_clone = app.clone();
+        Server::new(self).listen(addr).await
+    }
 
-            // Spawn a task to handle this connection
-            tokio::spawn(async move {
-                // Create a service function that handles requests
-                let service = service_fn(move |hyper_req: hyper::Request<hyper::body::Incoming>| {
-                    let app = app_clone.clone();
-                    async move {
-                        // Convert hyper request to our Request type
-                        let req = match Request::from_hyper(hyper_req).await {
-                            Ok(req) => req,
-                            Err(e) => {
-                                // Return error response
-                                let response: hyper::Response<http_body_util::Full<bytes::Bytes>> = 
-                                    e.into_response().into();
-                                return Ok::<_, hyper::Error>(response);
-                            }
-                        };
+    /// Starts the HTTP server and shuts it down gracefully when `signal` completes.
+    ///
+    /// On shutdown the server stops accepting connections, lets in-flight
+    /// requests finish, closes idle keep-alive connections, and returns once
+    /// every connection is closed. Use [`Server`] to bound the wait with a
+    /// drain timeout.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`listen`](App::listen).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use ruffus::App;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let app = App::new();
+    /// app.listen_with_shutdown("127.0.0.1:3000", async {
+    ///     tokio::signal::ctrl_c().await.ok();
+    /// })
+    /// .await
+    /// .unwrap();
+    /// # }
+    /// ```
+    pub async fn listen_with_shutdown<F>(self, addr: &str, signal: F) -> Result<()>
+    where
+        F: Future<Output = ()> + Send,
+    {
+        Server::new(self).listen_with_shutdown(addr, signal).await
+    }
 
-                        // Handle the request through our pipeline
-                        let response = match app.handle_request(req).await {
-                            Ok(resp) => resp,
-                            Err(e) => e.into_response(),
-                        };
+    /// Serves the application from an already bound listener.
+    ///
+    /// Accepts a Tokio `TcpListener`, a `UnixListener`, or any other
+    /// [`Listener`]. Binding the listener yourself lets you bind to port 0
+    /// and read the port that was picked, or serve from a socket inherited
+    /// from systemd (see [`listen_fds`](crate::listener::listen_fds)).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the listener fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use ruffus::App;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    /// let port = listener.local_addr().unwrap().port();
+    ///
+    /// App::new().serve(listener).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn serve<L: Listener>(self, listener: L) -> Result<()> {
+        Server::new(self).serve(listener).await
+    }
 
-                        // Convert our Response to hyper Response
-                        let hyper_response: hyper::Response<http_body_util::Full<bytes::Bytes>> = 
-                            response.into();
-                        
-                        Ok::<_, hyper::Error>(hyper_response)
-                    }
-                });
+    /// Serves the application from an already bound listener and shuts it
+    /// down gracefully when `signal` completes.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`serve`](App::serve).
+    pub async fn serve_with_shutdown<L, F>(self, listener: L, signal: F) -> Result<()>
+    where
+        L: Listener,
+        F: Future<Output = ()> + Send,
+    {
+        Server::new(self).serve_with_shutdown(listener, signal).await
+    }
 
-                // Serve the connection
-                if let Err(err) = http1::Builder::new()
-                    .serve_connection(io, service)
-                    .await
-                {
-                    eprintln!("Error serving connection: {:?}", err);
-                }
-            });
-        }
+    /// Starts an HTTPS server using the given TLS configuration.
+    ///
+    /// HTTP/2 and HTTP/1.1 are negotiated with each client through ALPN.
+    /// Use [`Server::listen_tls_with_shutdown`] for graceful shutdown.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`listen`](App::listen), or an error if the
+    /// TLS configuration is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use ruffus::{App, TlsConfig};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let app = App::new();
+    /// let tls = TlsConfig::from_pem_files("cert.pem", "key.pem").unwrap();
+    /// app.listen_tls("0.0.0.0:443", tls).await.unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+    pub async fn listen_tls(self, addr: &str, tls: crate::TlsConfig) -> Result<()> {
+        Server::new(self).listen_tls(addr, tls).await
     }
 }
 
@@ -0,0 +1,474 @@
+//! Request and response bodies
+//!
+//! A [`Body`] is read lazily from the connection: nothing is buffered until
+//! a handler asks for it. [`Request::body`](crate::Request::body),
+//! [`Request::json`](crate::Request::json) and the [`Json`](crate::Json)
+//! extractor buffer the whole body, up to the request's body limit. Handlers
+//! that process large uploads can take the body with
+//! [`Request::take_body`](crate::Request::take_body), or the `Body` extractor,
+//! and read it as a [`Stream`] of chunks or through [`AsyncRead`].
+//!
+//! Responses use the same type, so a handler can stream data to the client
+//! with [`Response::stream`](crate::Response::stream) or
+//! [`Response::reader`](crate::Response::reader) instead of building the
+//! whole body in memory.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use futures_util::StreamExt;
+//! use ruffus::{App, Body, Response};
+//!
+//! let mut app = App::new();
+//!
+//! app.post("/upload", |mut body: Body| async move {
+//!     let mut size = 0;
+//!     while let Some(chunk) = body.next().await {
+//!         size += chunk.map_err(|e| ruffus::Error::BadRequest(e.to_string()))?.len();
+//!     }
+//!     Ok::<_, ruffus::Error>(Response::text(format!("Received {} bytes", size)))
+//! });
+//! ```
+
+use async_trait::async_trait;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::future::Future;
+use std::time::Duration;
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures_core::Stream;
+use http::StatusCode;
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::BodyExt;
+use hyper::body::{Body as HttpBody, Frame, SizeHint};
+use sync_wrapper::SyncWrapper;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::extractors::FromRequest;
+use crate::{Error, Request, Result};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// How much `Body::from_reader` reads at a time
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// The body of a request or response, produced lazily as a stream of chunks.
+///
+/// A body yields `io::Result<Bytes>` chunks as a [`Stream`] and can also be
+/// read through [`AsyncRead`]. Reading a request body fails with
+/// [`io::ErrorKind::TimedOut`] once the server's
+/// [`request_body_timeout`](crate::Server::request_body_timeout) expires.
+/// It also implements `http_body::Body`, which is how the server sends it.
+///
+/// Bodies can be built from `Bytes`, `Vec<u8>`, `String` or `&'static str`,
+/// from a stream with [`Body::from_stream`], or from an [`AsyncRead`] with
+/// [`Body::from_reader`].
+pub struct Body {
+    inner: Inner,
+    /// Part of a chunk not yet handed out through `AsyncRead`
+    leftover: Bytes,
+}
+
+enum Inner {
+    Full(Bytes),
+    Stream(SyncWrapper<UnsyncBoxBody<Bytes, BoxError>>),
+}
+
+impl Body {
+    /// Creates an empty body.
+    pub fn empty() -> Self {
+        Self::from(Bytes::new())
+    }
+
+    /// Creates a body from a stream of chunks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bytes::Bytes;
+    /// use ruffus::Body;
+    ///
+    /// let chunks = vec![Ok::<_, std::io::Error>(Bytes::from("hello ")), Ok(Bytes::from("world"))];
+    /// let body = Body::from_stream(futures_util::stream::iter(chunks));
+    /// ```
+    pub fn from_stream<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+        E: Into<BoxError> + 'static,
+    {
+        use futures_util::TryStreamExt;
+
+        let frames = stream.map_ok(Frame::data).map_err(Into::into);
+        Self::from_http_body(http_body_util::StreamBody::new(frames))
+    }
+
+    /// Creates a body that reads from `reader` until it reaches the end.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async fn example() -> std::io::Result<()> {
+    /// use ruffus::Body;
+    ///
+    /// let file = tokio::fs::File::open("export.csv").await?;
+    /// let body = Body::from_reader(file);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_reader<R>(reader: R) -> Self
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        use tokio::io::AsyncReadExt;
+
+        // The reader is dropped after the first error or the end of input
+        let state = Some((Box::pin(reader), vec![0; READ_CHUNK_SIZE]));
+        Self::from_stream(futures_util::stream::unfold(state, |state| async move {
+            let (mut reader, mut buf) = state?;
+            match reader.read(&mut buf).await {
+                Ok(0) => None,
+                Ok(n) => Some((Ok(Bytes::copy_from_slice(&buf[..n])), Some((reader, buf)))),
+                Err(e) => Some((Err(e), None)),
+            }
+        }))
+    }
+
+    /// Wraps any `http_body::Body`, such as hyper's incoming body.
+    pub(crate) fn from_http_body<B>(body: B) -> Self
+    where
+        B: HttpBody + Send + 'static,
+        B::Data: Send,
+        B::Error: Into<BoxError>,
+    {
+        let body = body
+            .map_frame(|frame| frame.map_data(|mut data| data.copy_to_bytes(data.remaining())))
+            .map_err(Into::into);
+        Self {
+            inner: Inner::Stream(SyncWrapper::new(UnsyncBoxBody::new(body))),
+            leftover: Bytes::new(),
+        }
+    }
+
+    /// Fails reads that happen once `timeout` has passed.
+    pub(crate) fn with_timeout(self, timeout: Duration) -> Self {
+        match self.inner {
+            Inner::Full(_) => self,
+            Inner::Stream(body) => Self {
+                inner: Inner::Stream(SyncWrapper::new(UnsyncBoxBody::new(Timeout {
+                    body: body.into_inner(),
+                    sleep: Box::pin(tokio::time::sleep(timeout)),
+                }))),
+                leftover: self.leftover,
+            },
+        }
+    }
+
+    /// Fails reads once more than `limit` bytes have been read, so that
+    /// [`to_bytes`](Self::to_bytes) returns `413 Payload Too Large`.
+    ///
+    /// Unlike the limit passed to `to_bytes`, this one also bounds readers
+    /// that stream the body.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use ruffus::Body;
+    ///
+    /// let body = Body::from("Hello, World!").with_limit(5);
+    /// let error = body.to_bytes(None).await.unwrap_err();
+    /// assert_eq!(error.status_code(), http::StatusCode::PAYLOAD_TOO_LARGE);
+    /// # }
+    /// ```
+    pub fn with_limit(self, limit: usize) -> Self {
+        Self::from_http_body(Limit {
+            body: self,
+            remaining: limit,
+            limit,
+        })
+    }
+
+    /// Returns the body if it is held in memory, or `None` if it is streamed.
+    pub fn as_bytes(&self) -> Option<&Bytes> {
+        match &self.inner {
+            Inner::Full(bytes) if self.leftover.is_empty() => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Reads the whole body into memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns `413 Payload Too Large` if the body is larger than `limit`
+    /// bytes, `408 Request Timeout` if the request body timeout expires, and
+    /// `400 Bad Request` if reading from the connection fails.
+    pub async fn to_bytes(self, limit: Option<usize>) -> Result<Bytes> {
+        let too_large = |limit| Error::Custom {
+            status: StatusCode::PAYLOAD_TOO_LARGE,
+            message: format!("Request body is larger than {} bytes", limit),
+        };
+
+        let mut body = match self.inner {
+            Inner::Full(bytes) => {
+                let mut full = BytesMut::from(self.leftover);
+                full.extend_from_slice(&bytes);
+                let full = full.freeze();
+                return match limit {
+                    Some(limit) if full.len() > limit => Err(too_large(limit)),
+                    _ => Ok(full),
+                };
+            }
+            Inner::Stream(_) => self,
+        };
+
+        let mut buffer = BytesMut::new();
+        while let Some(chunk) = futures_util::StreamExt::next(&mut body).await {
+            let chunk = chunk.map_err(|e| match (limit_exceeded(&e), e.kind()) {
+                (Some(limit), _) => too_large(limit),
+                (None, io::ErrorKind::TimedOut) => Error::Custom {
+                    status: StatusCode::REQUEST_TIMEOUT,
+                    message: "Timed out reading the request body".to_string(),
+                },
+                (None, _) => Error::BadRequest(format!("Failed to read request body: {}", e)),
+            })?;
+            if let Some(limit) = limit {
+                if buffer.len() + chunk.len() > limit {
+                    return Err(too_large(limit));
+                }
+            }
+            buffer.extend_from_slice(&chunk);
+        }
+        Ok(buffer.freeze())
+    }
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.inner {
+            Inner::Full(bytes) => f.debug_tuple("Body").field(bytes).finish(),
+            Inner::Stream(_) => f.write_str("Body(<stream>)"),
+        }
+    }
+}
+
+impl From<Bytes> for Body {
+    fn from(bytes: Bytes) -> Self {
+        Self {
+            inner: Inner::Full(bytes),
+            leftover: Bytes::new(),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::from(Bytes::from(bytes))
+    }
+}
+
+impl From<String> for Body {
+    fn from(text: String) -> Self {
+        Self::from(Bytes::from(text))
+    }
+}
+
+impl From<&'static str> for Body {
+    fn from(text: &'static str) -> Self {
+        Self::from(Bytes::from_static(text.as_bytes()))
+    }
+}
+
+impl HttpBody for Body {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<Frame<Bytes>, BoxError>>> {
+        let this = self.get_mut();
+        if !this.leftover.is_empty() {
+            return Poll::Ready(Some(Ok(Frame::data(std::mem::take(&mut this.leftover)))));
+        }
+
+        match &mut this.inner {
+            Inner::Full(bytes) if bytes.is_empty() => Poll::Ready(None),
+            Inner::Full(bytes) => Poll::Ready(Some(Ok(Frame::data(std::mem::take(bytes))))),
+            Inner::Stream(body) => Pin::new(body.get_mut()).poll_frame(cx),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.leftover.is_empty()
+            && match &self.inner {
+                Inner::Full(bytes) => bytes.is_empty(),
+                Inner::Stream(_) => false,
+            }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match &self.inner {
+            Inner::Full(bytes) => SizeHint::with_exact((self.leftover.len() + bytes.len()) as u64),
+            // The stream can't be inspected through `&self`
+            Inner::Stream(_) => SizeHint::default(),
+        }
+    }
+}
+
+impl Stream for Body {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match ready!(self.as_mut().poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) if data.is_empty() => continue,
+                    Ok(data) => return Poll::Ready(Some(Ok(data))),
+                    // Trailers are not exposed
+                    Err(_) => continue,
+                },
+                Some(Err(e)) => {
+                    // Errors of bodies built from readers are already I/O errors
+                    let e = match e.downcast::<io::Error>() {
+                        Ok(e) => *e,
+                        Err(e) if e.is::<TimedOut>() => io::Error::new(io::ErrorKind::TimedOut, e),
+                        Err(e) => io::Error::other(e),
+                    };
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl AsyncRead for Body {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        if self.leftover.is_empty() {
+            match ready!(self.as_mut().poll_next(cx)) {
+                Some(Ok(chunk)) => self.leftover = chunk,
+                Some(Err(e)) => return Poll::Ready(Err(e)),
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+
+        let len = buf.remaining().min(self.leftover.len());
+        buf.put_slice(&self.leftover.split_to(len));
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Takes the body of the request, leaving it empty
+#[async_trait]
+impl FromRequest for Body {
+    async fn from_request(req: &mut Request) -> Result<Self> {
+        Ok(req.take_body())
+    }
+}
+
+/// Error yielded by a body whose read timeout has expired
+#[derive(Debug)]
+struct TimedOut;
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("timed out reading the request body")
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Body failing with `TimedOut` once its deadline passes
+struct Timeout {
+    body: UnsyncBoxBody<Bytes, BoxError>,
+    sleep: Pin<Box<tokio::time::Sleep>>,
+}
+
+impl HttpBody for Timeout {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<Frame<Bytes>, BoxError>>> {
+        if self.sleep.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(Some(Err(Box::new(TimedOut))));
+        }
+        Pin::new(&mut self.body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+/// Error yielded by a body longer than its limit
+#[derive(Debug)]
+struct LimitExceeded(usize);
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "body is larger than {} bytes", self.0)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Returns the limit of a body built with [`Body::with_limit`] if `e` is the
+/// error it yields once the limit is exceeded.
+pub(crate) fn limit_exceeded(e: &io::Error) -> Option<usize> {
+    e.get_ref()
+        .and_then(|inner| inner.downcast_ref::<LimitExceeded>())
+        .map(|exceeded| exceeded.0)
+}
+
+/// Body failing with `LimitExceeded` once more than `limit` bytes are read
+struct Limit {
+    body: Body,
+    remaining: usize,
+    limit: usize,
+}
+
+impl HttpBody for Limit {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<Frame<Bytes>, BoxError>>> {
+        let frame = ready!(Pin::new(&mut self.body).poll_frame(cx));
+        if let Some(Ok(frame)) = &frame {
+            let len = frame.data_ref().map_or(0, Bytes::len);
+            if len > self.remaining {
+                return Poll::Ready(Some(Err(Box::new(LimitExceeded(self.limit)))));
+            }
+            self.remaining -= len;
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        HttpBody::size_hint(&self.body)
+    }
+}
@@ -0,0 +1,521 @@
+//! Response compression and request decompression
+//!
+//! The [`Compression`] middleware compresses response bodies with the best
+//! encoding the client lists in `Accept-Encoding`, buffered bodies at once
+//! and streamed bodies chunk by chunk as they are produced. The
+//! [`Decompression`] middleware decodes request bodies sent with a
+//! `Content-Encoding` before extractors read them. Each algorithm has its own
+//! cargo feature, and `compression` enables all of them:
+//!
+//! | Feature | `Content-Encoding` |
+//! |---|---|
+//! | `compression-br` | `br` |
+//! | `compression-zstd` | `zstd` |
+//! | `compression-gzip` | `gzip` |
+//! | `compression-deflate` | `deflate` |
+//!
+//! When the client accepts several of them equally, they are preferred in
+//! this order.
+//!
+//! Bodies smaller than [`Compression::min_size`] and content types that are
+//! already compressed, such as images (except SVG), audio, video, fonts and
+//! archives, are sent as they are. So are server-sent events
+//! (`text/event-stream`), responses that already have a `Content-Encoding`,
+//! partial content, and responses marked `Cache-Control: no-transform`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::compression::{Compression, Level};
+//! use ruffus::App;
+//! use std::sync::Arc;
+//!
+//! let mut app = App::new();
+//!
+//! app.use_middleware(Arc::new(Compression::new().gzip(Level::Best).min_size(256)));
+//! ```
+//!
+//! Accepting compressed uploads:
+//!
+//! ```no_run
+//! use ruffus::compression::Decompression;
+//! use ruffus::{App, Json};
+//! use std::sync::Arc;
+//!
+//! let mut app = App::new();
+//!
+//! app.use_middleware(Arc::new(Decompression::new().max_size(16 * 1024 * 1024)));
+//! app.post("/events", |Json(events): Json<Vec<serde_json::Value>>| async move {
+//!     format!("{} events", events.len())
+//! });
+//! ```
+
+use async_trait::async_trait;
+use http::header::{self, HeaderMap, HeaderValue};
+use http::StatusCode;
+use std::io::Cursor;
+use std::pin::Pin;
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, BufReader};
+
+use crate::{Body, Error, Middleware, Next, Request, Response, Result};
+
+/// Smallest body compressed by default, in bytes
+const DEFAULT_MIN_SIZE: u64 = 1024;
+
+/// How hard an algorithm works to compress
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Level {
+    /// The fastest level, which compresses the least
+    Fastest,
+    /// The level that compresses the most, and is the slowest
+    Best,
+    /// A level balancing speed and size: the algorithm's default, except
+    /// brotli uses quality 4 instead of 11, which is too slow for dynamic
+    /// responses
+    #[default]
+    Default,
+    /// A level specific to the algorithm, clamped to its range
+    Precise(i32),
+}
+
+/// A content coding this build can produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Coding {
+    #[cfg(feature = "compression-br")]
+    Brotli,
+    #[cfg(feature = "compression-zstd")]
+    Zstd,
+    #[cfg(feature = "compression-gzip")]
+    Gzip,
+    #[cfg(feature = "compression-deflate")]
+    Deflate,
+}
+
+impl Coding {
+    /// Every coding enabled, in order of preference
+    const ALL: &'static [Coding] = &[
+        #[cfg(feature = "compression-br")]
+        Coding::Brotli,
+        #[cfg(feature = "compression-zstd")]
+        Coding::Zstd,
+        #[cfg(feature = "compression-gzip")]
+        Coding::Gzip,
+        #[cfg(feature = "compression-deflate")]
+        Coding::Deflate,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "compression-br")]
+            Coding::Brotli => "br",
+            #[cfg(feature = "compression-zstd")]
+            Coding::Zstd => "zstd",
+            #[cfg(feature = "compression-gzip")]
+            Coding::Gzip => "gzip",
+            #[cfg(feature = "compression-deflate")]
+            Coding::Deflate => "deflate",
+        }
+    }
+
+    /// Whether `token`, from `Accept-Encoding`, names this coding
+    fn is_named(self, token: &str) -> bool {
+        token.eq_ignore_ascii_case(self.name()) || (self.name() == "gzip" && token.eq_ignore_ascii_case("x-gzip"))
+    }
+
+    /// Returns the coding named by a `Content-Encoding` token, if enabled.
+    fn from_token(token: &str) -> Option<Coding> {
+        Coding::ALL.iter().copied().find(|coding| coding.is_named(token))
+    }
+
+    /// Wraps `reader` so that reading from it produces the compressed data.
+    fn encode<R>(self, reader: R, level: Level) -> Pin<Box<dyn AsyncRead + Send>>
+    where
+        R: AsyncBufRead + Send + 'static,
+    {
+        use async_compression::tokio::bufread;
+
+        let quality = match level {
+            Level::Fastest => async_compression::Level::Fastest,
+            Level::Best => async_compression::Level::Best,
+            Level::Default => async_compression::Level::Default,
+            Level::Precise(quality) => async_compression::Level::Precise(quality),
+        };
+        match self {
+            #[cfg(feature = "compression-br")]
+            Coding::Brotli => {
+                let quality = match level {
+                    Level::Default => async_compression::Level::Precise(4),
+                    _ => quality,
+                };
+                Box::pin(bufread::BrotliEncoder::with_quality(reader, quality))
+            }
+            #[cfg(feature = "compression-zstd")]
+            Coding::Zstd => Box::pin(bufread::ZstdEncoder::with_quality(reader, quality)),
+            #[cfg(feature = "compression-gzip")]
+            Coding::Gzip => Box::pin(bufread::GzipEncoder::with_quality(reader, quality)),
+            // HTTP's `deflate` is the zlib format, not raw deflate
+            #[cfg(feature = "compression-deflate")]
+            Coding::Deflate => Box::pin(bufread::ZlibEncoder::with_quality(reader, quality)),
+        }
+    }
+
+    /// Wraps `reader` so that reading from it produces the decompressed data.
+    fn decode<R>(self, reader: R) -> Pin<Box<dyn AsyncRead + Send>>
+    where
+        R: AsyncBufRead + Send + 'static,
+    {
+        use async_compression::tokio::bufread;
+
+        match self {
+            #[cfg(feature = "compression-br")]
+            Coding::Brotli => Box::pin(bufread::BrotliDecoder::new(reader)),
+            #[cfg(feature = "compression-zstd")]
+            Coding::Zstd => Box::pin(bufread::ZstdDecoder::new(reader)),
+            #[cfg(feature = "compression-gzip")]
+            Coding::Gzip => Box::pin(bufread::GzipDecoder::new(reader)),
+            #[cfg(feature = "compression-deflate")]
+            Coding::Deflate => Box::pin(bufread::ZlibDecoder::new(reader)),
+        }
+    }
+}
+
+/// Middleware compressing response bodies
+///
+/// Every algorithm enabled through cargo features is offered, at
+/// [`Level::Default`] unless its builder method sets another level.
+/// Responses that could be compressed get `Vary: Accept-Encoding`, whether
+/// or not this client accepts an encoding.
+///
+/// Compressing a response removes its `Content-Length` and `Accept-Ranges`
+/// headers and makes a strong `ETag` weak, since they describe the
+/// uncompressed body.
+#[derive(Debug, Clone)]
+pub struct Compression {
+    codings: Vec<(Coding, Level)>,
+    min_size: u64,
+}
+
+impl Compression {
+    /// Creates a compression middleware offering every enabled algorithm.
+    pub fn new() -> Self {
+        Self {
+            codings: Coding::ALL.iter().map(|&coding| (coding, Level::Default)).collect(),
+            min_size: DEFAULT_MIN_SIZE,
+        }
+    }
+
+    /// Sets the brotli level.
+    #[cfg(feature = "compression-br")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression-br")))]
+    pub fn br(self, level: Level) -> Self {
+        self.level(Coding::Brotli, level)
+    }
+
+    /// Sets the zstd level.
+    #[cfg(feature = "compression-zstd")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression-zstd")))]
+    pub fn zstd(self, level: Level) -> Self {
+        self.level(Coding::Zstd, level)
+    }
+
+    /// Sets the gzip level.
+    #[cfg(feature = "compression-gzip")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression-gzip")))]
+    pub fn gzip(self, level: Level) -> Self {
+        self.level(Coding::Gzip, level)
+    }
+
+    /// Sets the deflate level.
+    #[cfg(feature = "compression-deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression-deflate")))]
+    pub fn deflate(self, level: Level) -> Self {
+        self.level(Coding::Deflate, level)
+    }
+
+    /// Sets the smallest body compressed, in bytes, 1024 by default.
+    ///
+    /// Streamed bodies are compressed unless a `Content-Length` header says
+    /// they are smaller.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    fn level(mut self, coding: Coding, level: Level) -> Self {
+        for (candidate, candidate_level) in &mut self.codings {
+            if *candidate == coding {
+                *candidate_level = level;
+            }
+        }
+        self
+    }
+
+    /// Whether the response may be compressed, depending on the encodings
+    /// the client accepts
+    fn is_compressible(&self, response: &Response) -> bool {
+        let status = response.get_status();
+        if status.is_informational()
+            || matches!(
+                status,
+                StatusCode::NO_CONTENT | StatusCode::PARTIAL_CONTENT | StatusCode::NOT_MODIFIED
+            )
+        {
+            return false;
+        }
+
+        let headers = response.get_headers();
+        if headers.contains_key(header::CONTENT_ENCODING) || headers.contains_key(header::CONTENT_RANGE) {
+            return false;
+        }
+        let no_transform = headers
+            .get_all(header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|directive| directive.trim().eq_ignore_ascii_case("no-transform"));
+        if no_transform {
+            return false;
+        }
+        if let Some(content_type) = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()) {
+            if is_compressed_type(content_type) || is_event_stream(content_type) {
+                return false;
+            }
+        }
+
+        let len = match response.buffered_body() {
+            Some(bytes) => Some(bytes.len() as u64),
+            None => headers
+                .get(header::CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok()),
+        };
+        len.is_none_or(|len| len >= self.min_size)
+    }
+
+    /// Picks the coding the client prefers among those offered, with
+    /// `accept_encoding` the values of its `Accept-Encoding` headers.
+    fn negotiate(&self, accept_encoding: &[&str]) -> Option<(Coding, Level)> {
+        let mut accepted: Vec<(&str, f32)> = Vec::new();
+        for item in accept_encoding.iter().flat_map(|value| value.split(',')) {
+            let mut params = item.split(';').map(str::trim);
+            let token = params.next().unwrap_or_default();
+            if token.is_empty() {
+                continue;
+            }
+            let quality = params
+                .find_map(|param| param.strip_prefix("q=").or_else(|| param.strip_prefix("Q=")))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q)));
+            if let Some(quality) = quality {
+                accepted.push((token, quality));
+            }
+        }
+
+        let wildcard = accepted.iter().find(|(token, _)| *token == "*").map(|&(_, q)| q);
+        let mut best: Option<((Coding, Level), f32)> = None;
+        for &(coding, level) in &self.codings {
+            let quality = accepted
+                .iter()
+                .find(|(token, _)| coding.is_named(token))
+                .map(|&(_, q)| q)
+                .or(wildcard)
+                .unwrap_or(0.0);
+            if quality > 0.0 && best.is_none_or(|(_, best)| quality > best) {
+                best = Some(((coding, level), quality));
+            }
+        }
+        best.map(|(choice, _)| choice)
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Middleware for Compression {
+    async fn handle(&self, req: Request, next: Next) -> Result<Response> {
+        let accept_encoding: Vec<String> = req
+            .headers()
+            .get_all(header::ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(str::to_string)
+            .collect();
+
+        let response = next.run(req).await?;
+        if !self.is_compressible(&response) {
+            return Ok(response);
+        }
+        let response = response.vary("Accept-Encoding");
+        let accept_encoding: Vec<&str> = accept_encoding.iter().map(String::as_str).collect();
+        match self.negotiate(&accept_encoding) {
+            Some((coding, level)) => compress(response, coding, level).await,
+            None => Ok(response),
+        }
+    }
+}
+
+/// Middleware decoding compressed request bodies
+///
+/// Requests with a `Content-Encoding` of enabled algorithms, possibly
+/// several applied in turn, get their body replaced by the decoded one, and
+/// lose their `Content-Encoding` and `Content-Length` headers. Requests with
+/// any other encoding are rejected with `415 Unsupported Media Type`.
+///
+/// The decoded body is limited to the request's
+/// [body limit](crate::Request::body_limit) unless
+/// [`max_size`](Self::max_size) sets another one, even when a handler
+/// streams it, so that a small compressed body can't expand without bound.
+/// Reading past the limit fails with `413 Payload Too Large`, and reading a
+/// corrupt body with `400 Bad Request`.
+#[derive(Debug, Clone, Default)]
+pub struct Decompression {
+    max_size: Option<usize>,
+}
+
+impl Decompression {
+    /// Creates a decompression middleware accepting every enabled algorithm.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the largest decoded body in bytes, replacing the request's body
+    /// limit.
+    pub fn max_size(mut self, bytes: usize) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+}
+
+#[async_trait]
+impl Middleware for Decompression {
+    async fn handle(&self, mut req: Request, next: Next) -> Result<Response> {
+        let mut codings = Vec::new();
+        for value in req.headers().get_all(header::CONTENT_ENCODING) {
+            let tokens = value.to_str().map_err(|_| unsupported_encoding("(invalid)"))?;
+            for token in tokens.split(',').map(str::trim) {
+                if token.is_empty() || token.eq_ignore_ascii_case("identity") {
+                    continue;
+                }
+                codings.push(Coding::from_token(token).ok_or_else(|| unsupported_encoding(token))?);
+            }
+        }
+        if codings.is_empty() {
+            return next.run(req).await;
+        }
+
+        // Codings are listed in the order they were applied
+        let mut body = req.take_body();
+        for coding in codings.into_iter().rev() {
+            body = Body::from_reader(coding.decode(BufReader::new(body)));
+        }
+        if let Some(limit) = self.max_size.or(req.body_limit()) {
+            body = body.with_limit(limit);
+        }
+        req.set_body(body);
+        req.headers_mut().remove(header::CONTENT_ENCODING);
+        req.headers_mut().remove(header::CONTENT_LENGTH);
+        next.run(req).await
+    }
+}
+
+fn unsupported_encoding(encoding: &str) -> Error {
+    Error::Custom {
+        status: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        message: format!("Unsupported Content-Encoding: {}", encoding),
+    }
+}
+
+/// Whether a content type is usually compressed already
+fn is_compressed_type(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    if essence == "image/svg+xml" {
+        return false;
+    }
+    ["image/", "audio/", "video/", "font/woff"]
+        .iter()
+        .any(|prefix| essence.starts_with(prefix))
+        || matches!(
+            essence.as_str(),
+            "application/zip"
+                | "application/gzip"
+                | "application/x-gzip"
+                | "application/zstd"
+                | "application/x-bzip2"
+                | "application/x-xz"
+                | "application/x-7z-compressed"
+                | "application/vnd.rar"
+                | "application/x-rar-compressed"
+        )
+}
+
+/// Whether a content type is a stream of server-sent events, which the
+/// client must receive as soon as each event is sent
+fn is_event_stream(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    essence.eq_ignore_ascii_case("text/event-stream")
+}
+
+async fn compress(response: Response, coding: Coding, level: Level) -> Result<Response> {
+    let (status, mut headers, body) = response.into_parts();
+    let body = match body.as_bytes() {
+        Some(bytes) => {
+            // Reading from memory never yields, so compressing a large body
+            // at a high level would hold up the worker thread it runs on
+            let mut encoder = coding.encode(Cursor::new(bytes.clone()), level);
+            let runtime = tokio::runtime::Handle::current();
+            let compressed = tokio::task::spawn_blocking(move || {
+                let mut compressed = Vec::new();
+                runtime.block_on(encoder.read_to_end(&mut compressed)).map(|_| compressed)
+            })
+            .await
+            .map_err(|e| Error::InternalServerError(format!("Failed to compress response: {}", e)))?
+            .map_err(|e| Error::InternalServerError(format!("Failed to compress response: {}", e)))?;
+            Body::from(compressed)
+        }
+        None => Body::from_reader(coding.encode(BufReader::new(body), level)),
+    };
+
+    headers.remove(header::CONTENT_LENGTH);
+    headers.remove(header::ACCEPT_RANGES);
+    weaken_etag(&mut headers);
+    headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(coding.name()));
+    Ok(Response::from_parts(status, headers, body))
+}
+
+/// Makes a strong `ETag` weak, as the compressed body isn't byte for byte
+/// the one it was computed for.
+fn weaken_etag(headers: &mut HeaderMap) {
+    let weak = match headers.get(header::ETAG) {
+        Some(etag) if !etag.as_bytes().starts_with(b"W/") => [b"W/", etag.as_bytes()].concat(),
+        _ => return,
+    };
+    if let Ok(weak) = HeaderValue::from_bytes(&weak) {
+        headers.insert(header::ETAG, weak);
+    }
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let compression = Compression::new();
+        let negotiate = |accept: &[&str]| compression.negotiate(accept).map(|(coding, _)| coding.name());
+
+        assert_eq!(negotiate(&[]), None);
+        assert_eq!(negotiate(&["identity"]), None);
+        assert_eq!(negotiate(&["gzip;q=0"]), None);
+        assert_eq!(negotiate(&["*;q=0"]), None);
+        assert_eq!(negotiate(&["gzip, deflate"]), Some("gzip"));
+        assert_eq!(negotiate(&["x-gzip"]), Some("gzip"));
+        assert_eq!(negotiate(&["deflate;q=0.9, gzip;q=0.5"]), Some("deflate"));
+        assert_eq!(negotiate(&["gzip;q=0.5", "zstd;q=0.8"]), Some("zstd"));
+        assert_eq!(negotiate(&["gzip, deflate, br, zstd"]), Some("br"));
+        assert_eq!(negotiate(&["*, br;q=0"]), Some("zstd"));
+        assert_eq!(negotiate(&["gzip;q=2, deflate;q=abc, zstd;q=0.1"]), Some("zstd"));
+    }
+}
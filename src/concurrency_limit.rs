@@ -0,0 +1,111 @@
+//! Load shedding
+//!
+//! The [`ConcurrencyLimit`] middleware caps how many requests are handled at
+//! once. Requests over the cap are rejected straight away with
+//! `503 Service Unavailable` instead of waiting, so that an overloaded server
+//! sheds load rather than queueing requests without bound.
+//!
+//! Register it with [`App::use_middleware`](crate::App::use_middleware) to
+//! cap every request together, or on a [`Router`](crate::Router) to cap its
+//! routes separately. Each instance, and its clones, share one cap.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::concurrency_limit::ConcurrencyLimit;
+//! use ruffus::{App, Router};
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! let mut app = App::new();
+//! app.use_middleware(Arc::new(ConcurrencyLimit::new(1024)));
+//!
+//! // Exports are expensive: at most 4 at a time
+//! let mut exports = Router::new("/exports");
+//! exports.use_middleware(Arc::new(
+//!     ConcurrencyLimit::new(4).retry_after(Duration::from_secs(30)),
+//! ));
+//! exports.post("/", || async { "started" });
+//! app.mount("", exports);
+//! ```
+
+use async_trait::async_trait;
+use http::StatusCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::{Error, Middleware, Next, Request, Response, Result};
+
+/// Middleware rejecting requests once too many are in flight
+///
+/// A request counts as in flight until the middleware registered after this
+/// one and the handler have returned its response. Streamed bodies are sent
+/// afterwards and don't count, so long-lived streams such as server-sent
+/// events don't hold on to the cap.
+///
+/// Rejected requests fail with an [`Error::Custom`] carrying
+/// `503 Service Unavailable`. When [`retry_after`](Self::retry_after) is set,
+/// they get a response with a `Retry-After` header instead, so that the
+/// header reaches the client.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    max: usize,
+    retry_after: Option<Duration>,
+}
+
+impl ConcurrencyLimit {
+    /// Allows at most `max` requests in flight.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn new(max: usize) -> Self {
+        assert!(max > 0, "a concurrency limit must allow at least one request");
+        Self {
+            semaphore: Arc::new(Semaphore::new(max)),
+            max,
+            retry_after: None,
+        }
+    }
+
+    /// Tells rejected clients to retry after `duration`, rounded up to whole
+    /// seconds.
+    pub fn retry_after(mut self, duration: Duration) -> Self {
+        self.retry_after = Some(duration);
+        self
+    }
+
+    /// Returns how many requests may be in flight.
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Returns how many requests are in flight.
+    pub fn in_flight(&self) -> usize {
+        self.max - self.semaphore.available_permits()
+    }
+}
+
+#[async_trait]
+impl Middleware for ConcurrencyLimit {
+    async fn handle(&self, req: Request, next: Next) -> Result<Response> {
+        let Ok(_permit) = self.semaphore.clone().try_acquire_owned() else {
+            let error = Error::Custom {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                message: format!("Too many requests in flight, at most {} are handled at once", self.max),
+            };
+            return match self.retry_after {
+                Some(retry_after) => {
+                    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                    Ok(error.into_response().header("Retry-After", &secs.to_string()))
+                }
+                None => Err(error),
+            };
+        };
+
+        // The permit is released when the response, or the error, is returned
+        next.run(req).await
+    }
+}
@@ -0,0 +1,307 @@
+//! Cross-Origin Resource Sharing
+//!
+//! The [`Cors`] middleware lets browsers call the app from other origins. It
+//! answers preflight `OPTIONS` requests itself, whether or not a route
+//! matches their path, and adds the `Access-Control-*` headers to the
+//! responses of allowed origins.
+//!
+//! Register it with [`App::use_middleware`](crate::App::use_middleware) so
+//! it sees every request.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::cors::Cors;
+//! use ruffus::App;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! let mut app = App::new();
+//!
+//! app.use_middleware(Arc::new(
+//!     Cors::new()
+//!         .allow_origins(["https://example.com"])
+//!         .allow_origin_regex(r"^https://[a-z0-9-]+\.example\.com$")
+//!         .allow_credentials(true)
+//!         .expose_headers(["x-request-id"])
+//!         .max_age(Duration::from_secs(600)),
+//! ));
+//! ```
+
+use async_trait::async_trait;
+use http::{header, Method, StatusCode};
+use regex::Regex;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{IntoResponse, Middleware, Next, Request, Response, Result};
+
+/// Middleware implementing CORS
+///
+/// No origin is allowed until one of the `allow_origin*` methods is called;
+/// an origin is allowed if any of them accepts it. Responses to other origins
+/// get no CORS headers, so browsers refuse them.
+///
+/// By default preflights allow the `GET`, `HEAD`, `POST`, `PUT`, `DELETE` and
+/// `PATCH` methods and whatever headers the browser asks for.
+#[derive(Clone)]
+pub struct Cors {
+    origins: Vec<AllowOrigin>,
+    methods: Vec<Method>,
+    allow_headers: Option<Vec<String>>,
+    expose_headers: Vec<String>,
+    max_age: Option<Duration>,
+    credentials: bool,
+}
+
+const ANY_ORIGIN_WITH_CREDENTIALS: &str =
+    "CORS can't allow credentials for any origin; use `allow_origin_fn(|_| true)` if that is really intended";
+
+#[derive(Clone)]
+enum AllowOrigin {
+    Any,
+    Exact(String),
+    Regex(Regex),
+    Predicate(Arc<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+impl Cors {
+    /// Creates a CORS middleware allowing no origin.
+    pub fn new() -> Self {
+        Self {
+            origins: Vec::new(),
+            methods: vec![
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PUT,
+                Method::DELETE,
+                Method::PATCH,
+            ],
+            allow_headers: None,
+            expose_headers: Vec::new(),
+            max_age: None,
+            credentials: false,
+        }
+    }
+
+    /// Allows every origin.
+    ///
+    /// Responses use `Access-Control-Allow-Origin: *`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if credentials are allowed: letting every site make requests
+    /// with the user's cookies defeats the point of CORS. To really allow
+    /// that, pass a predicate accepting every origin to
+    /// [`allow_origin_fn`](Cors::allow_origin_fn).
+    pub fn allow_any_origin(mut self) -> Self {
+        assert!(!self.credentials, "{}", ANY_ORIGIN_WITH_CREDENTIALS);
+        self.origins.push(AllowOrigin::Any);
+        self
+    }
+
+    /// Allows the listed origins, such as `https://example.com`.
+    pub fn allow_origins<I>(mut self, origins: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.origins
+            .extend(origins.into_iter().map(|origin| AllowOrigin::Exact(origin.as_ref().to_string())));
+        self
+    }
+
+    /// Allows the origins matching a regular expression.
+    ///
+    /// Anchor the expression with `^` and `$`, or it will match origins that
+    /// merely contain an allowed one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` isn't a valid regular expression.
+    pub fn allow_origin_regex(mut self, pattern: &str) -> Self {
+        let regex = Regex::new(pattern).unwrap_or_else(|e| panic!("invalid CORS origin pattern {:?}: {}", pattern, e));
+        self.origins.push(AllowOrigin::Regex(regex));
+        self
+    }
+
+    /// Allows the origins for which `predicate` returns `true`.
+    pub fn allow_origin_fn<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.origins.push(AllowOrigin::Predicate(Arc::new(predicate)));
+        self
+    }
+
+    /// Sets the methods preflights allow.
+    pub fn allow_methods<I>(mut self, methods: I) -> Self
+    where
+        I: IntoIterator<Item = Method>,
+    {
+        self.methods = methods.into_iter().collect();
+        self
+    }
+
+    /// Sets the request headers preflights allow, instead of any header the
+    /// browser asks for.
+    pub fn allow_headers<I>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.allow_headers = Some(headers.into_iter().map(|h| h.as_ref().to_string()).collect());
+        self
+    }
+
+    /// Sets the response headers scripts may read, besides the few that are
+    /// always exposed such as `Content-Type`.
+    pub fn expose_headers<I>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.expose_headers = headers.into_iter().map(|h| h.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Sets how long browsers may cache the result of a preflight.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Allows requests with credentials such as cookies.
+    ///
+    /// Responses then repeat the request's origin instead of `*`, as
+    /// browsers require.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `allow` is `true` and
+    /// [`allow_any_origin`](Cors::allow_any_origin) was called.
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        assert!(
+            !(allow && self.origins.iter().any(|allowed| matches!(allowed, AllowOrigin::Any))),
+            "{}",
+            ANY_ORIGIN_WITH_CREDENTIALS
+        );
+        self.credentials = allow;
+        self
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        self.origins.iter().any(|allowed| match allowed {
+            AllowOrigin::Any => true,
+            AllowOrigin::Exact(exact) => exact == origin,
+            AllowOrigin::Regex(regex) => regex.is_match(origin),
+            AllowOrigin::Predicate(predicate) => predicate(origin),
+        })
+    }
+
+    /// Whether the allowed origin is always `*`, so responses don't depend on
+    /// the request's origin
+    fn is_wildcard(&self) -> bool {
+        self.origins.iter().any(|allowed| matches!(allowed, AllowOrigin::Any))
+    }
+
+    /// Adds the headers shared by preflights and other responses for an
+    /// allowed origin.
+    fn allow_origin(&self, response: Response, origin: &str) -> Response {
+        let response = if self.is_wildcard() {
+            response.header("Access-Control-Allow-Origin", "*")
+        } else {
+            response.header("Access-Control-Allow-Origin", origin)
+        };
+        if self.credentials {
+            response.header("Access-Control-Allow-Credentials", "true")
+        } else {
+            response
+        }
+    }
+
+    fn preflight(&self, req: &Request, origin: Option<&str>) -> Response {
+        let response = Response::new()
+            .status(StatusCode::NO_CONTENT)
+            .vary("Origin, Access-Control-Request-Method, Access-Control-Request-Headers");
+        let origin = match origin {
+            Some(origin) if self.allows(origin) => origin,
+            _ => return response,
+        };
+
+        let methods = self.methods.iter().map(Method::as_str).collect::<Vec<_>>().join(", ");
+        let mut response = self
+            .allow_origin(response, origin)
+            .header("Access-Control-Allow-Methods", &methods);
+
+        let requested_headers = req
+            .headers()
+            .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+            .and_then(|value| value.to_str().ok());
+        let allowed_headers = match &self.allow_headers {
+            Some(headers) => Some(headers.join(", ")),
+            None => requested_headers.map(str::to_string),
+        };
+        if let Some(allowed_headers) = allowed_headers {
+            response = response.header("Access-Control-Allow-Headers", &allowed_headers);
+        }
+        if let Some(max_age) = self.max_age {
+            response = response.header("Access-Control-Max-Age", &max_age.as_secs().to_string());
+        }
+        response
+    }
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Cors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cors")
+            .field("methods", &self.methods)
+            .field("allow_headers", &self.allow_headers)
+            .field("expose_headers", &self.expose_headers)
+            .field("max_age", &self.max_age)
+            .field("credentials", &self.credentials)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Middleware for Cors {
+    async fn handle(&self, req: Request, next: Next) -> Result<Response> {
+        let origin = req
+            .headers()
+            .get(header::ORIGIN)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        if req.method() == Method::OPTIONS && req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD) {
+            return Ok(self.preflight(&req, origin.as_deref()));
+        }
+
+        let result = next.run(req).await;
+        let origin = match origin {
+            Some(origin) if self.allows(&origin) => origin,
+            // Caches must not reuse a response without CORS headers for
+            // allowed origins
+            _ if self.is_wildcard() => return result,
+            _ => return result.map(|response| response.vary("Origin")),
+        };
+
+        // Errors become responses, so that browsers let scripts read them
+        let mut response = self.allow_origin(result.into_response(), &origin);
+        if !self.expose_headers.is_empty() {
+            response = response.header("Access-Control-Expose-Headers", &self.expose_headers.join(", "));
+        }
+        if !self.is_wildcard() {
+            response = response.vary("Origin");
+        }
+        Ok(response)
+    }
+}
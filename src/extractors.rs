@@ -3,26 +3,68 @@
 //! Extractors provide a type-safe way to extract data from HTTP requests.
 //! They implement the `FromRequest` trait which allows them to be used
 //! as handler parameters.
+//!
+//! Extractors that don't need the request body implement `FromRequestParts`
+//! instead and can appear in any argument position. Extractors that read the
+//! body, such as `Json`, must be the last argument of a handler.
 
 use async_trait::async_trait;
+use bytes::Bytes;
 use serde::de::DeserializeOwned;
 use std::collections::HashMap;
 
 use crate::{Error, Request, Result};
 
 /// Trait for types that can be extracted from a request
+///
+/// Implemented for every [`FromRequestParts`] type as well. Implement this
+/// trait directly only for extractors that consume the request body.
 #[async_trait]
 pub trait FromRequest: Sized {
     /// Extract this type from the request
     async fn from_request(req: &mut Request) -> Result<Self>;
 }
 
+/// Trait for types that can be extracted without consuming the request body
+///
+/// These extractors may appear in any argument position of a handler.
+#[async_trait]
+pub trait FromRequestParts: Sized {
+    /// Extract this type from the request, leaving the body untouched
+    async fn from_request_parts(req: &mut Request) -> Result<Self>;
+}
+
+#[async_trait]
+impl<T> FromRequest for T
+where
+    T: FromRequestParts + Send,
+{
+    async fn from_request(req: &mut Request) -> Result<Self> {
+        T::from_request_parts(req).await
+    }
+}
+
+/// Takes the whole request, leaving an empty `GET /` request in its place
+#[async_trait]
+impl FromRequest for Request {
+    async fn from_request(req: &mut Request) -> Result<Self> {
+        let empty = Request::new(
+            http::Method::GET,
+            http::Uri::default(),
+            http::HeaderMap::new(),
+            Bytes::new(),
+        );
+        Ok(std::mem::replace(req, empty))
+    }
+}
+
 /// Extractor for path parameters
 ///
 /// # Example
 ///
-/// ```ignore
+/// ```no_run
 /// use ruffus::extractors::Path;
+/// use ruffus::{App, Response};
 /// use serde::Deserialize;
 ///
 /// #[derive(Deserialize)]
@@ -30,62 +72,87 @@ pub trait FromRequest: Sized {
 ///     id: String,
 /// }
 ///
-/// async fn get_user(Path(params): Path<UserParams>) -> Response {
-///     // Use params.id
+/// async fn get_user(Path(params): Path<UserParams>) -> ruffus::Result<Response> {
+///     Ok(Response::text(format!("User {}", params.id)))
 /// }
+///
+/// let mut app = App::new();
+/// app.get("/users/:id", get_user);
+/// ```
+///
+/// A route with a single parameter can also extract it as a plain value:
+///
+/// ```no_run
+/// use ruffus::{App, Path, Response};
+///
+/// let mut app = App::new();
+/// app.get("/users/:id<u64>", |Path(id): Path<u64>| async move {
+///     Response::text(format!("User {}", id))
+/// });
 /// ```
 pub struct Path<T>(pub T);
 
 #[async_trait]
-impl<T> FromRequest for Path<T>
+impl<T> FromRequestParts for Path<T>
 where
     T: DeserializeOwned + Send,
 {
-    async fn from_request(req: &mut Request) -> Result<Self> {
+    async fn from_request_parts(req: &mut Request) -> Result<Self> {
         // Convert the params HashMap to a serde_json::Value
         // Try to intelligently convert string values to appropriate JSON types
         let params_map: HashMap<String, serde_json::Value> = req
             .params()
             .iter()
-            .map(|(k, v)| {
-                // Try to parse as number first, then boolean, fall back to string
-                let value = if let Ok(num) = v.parse::<i64>() {
-                    serde_json::Value::Number(num.into())
-                } else if let Ok(num) = v.parse::<u64>() {
-                    serde_json::Value::Number(num.into())
-                } else if let Ok(num) = v.parse::<f64>() {
-                    serde_json::Number::from_f64(num)
-                        .map(serde_json::Value::Number)
-                        .unwrap_or_else(|| serde_json::Value::String(v.clone()))
-                } else if let Ok(b) = v.parse::<bool>() {
-                    serde_json::Value::Bool(b)
-                } else {
-                    serde_json::Value::String(v.clone())
-                };
-                (k.clone(), value)
-            })
+            .map(|(k, v)| (k.clone(), coerce_param(v)))
             .collect();
 
         let value = serde_json::Value::Object(
-            params_map
-                .into_iter()
-                .map(|(k, v)| (k, v))
-                .collect(),
+            params_map.into_iter().collect(),
         );
 
-        let params: T = serde_json::from_value(value)
-            .map_err(|e| Error::BadRequest(format!("Failed to parse path parameters: {}", e)))?;
+        let params: T = serde_json::from_value(value).or_else(|e| {
+            // A lone parameter can also be extracted as a plain value, e.g. `Path<u64>`
+            match req.params().values().next() {
+                Some(raw) if req.params().len() == 1 => serde_json::from_value(coerce_param(raw))
+                    .or_else(|_| serde_json::from_value(serde_json::Value::String(raw.clone())))
+                    .map_err(|_| e),
+                _ => Err(e),
+            }
+        })
+        .map_err(|e| Error::BadRequest(format!("Failed to parse path parameters: {}", e)))?;
 
         Ok(Path(params))
     }
 }
 
+/// Converts a path parameter to a JSON value.
+///
+/// Try to parse as number first, then boolean, fall back to string
+fn coerce_param(v: &str) -> serde_json::Value {
+    if let Ok(num) = v.parse::<i64>() {
+        serde_json::Value::Number(num.into())
+    } else if let Ok(num) = v.parse::<u64>() {
+        serde_json::Value::Number(num.into())
+    } else if let Ok(num) = v.parse::<f64>() {
+        serde_json::Number::from_f64(num)
+            .map(serde_json::Value::Number)
+            .unwrap_or_else(|| serde_json::Value::String(v.to_string()))
+    } else if let Ok(b) = v.parse::<bool>() {
+        serde_json::Value::Bool(b)
+    } else {
+        serde_json::Value::String(v.to_string())
+    }
+}
+
 /// Extractor for JSON request body
 ///
 /// # Example
 ///
-/// ```ignore
+/// `Json` reads the request body, so it must be the last handler argument.
+///
+/// ```no_run
 /// use ruffus::extractors::Json;
+/// use ruffus::{App, Response};
 /// use serde::Deserialize;
 ///
 /// #[derive(Deserialize)]
@@ -94,9 +161,12 @@ where
 ///     email: String,
 /// }
 ///
-/// async fn create_user(Json(user): Json<CreateUser>) -> Response {
-///     // Use user.name and user.email
+/// async fn create_user(Json(user): Json<CreateUser>) -> ruffus::Result<Response> {
+///     Ok(Response::text(format!("Created {} <{}>", user.name, user.email)))
 /// }
+///
+/// let mut app = App::new();
+/// app.post("/users", create_user);
 /// ```
 pub struct Json<T>(pub T);
 
@@ -111,12 +181,21 @@ where
     }
 }
 
+/// Buffers the whole request body, up to the request's body limit
+#[async_trait]
+impl FromRequest for Bytes {
+    async fn from_request(req: &mut Request) -> Result<Self> {
+        req.body().await
+    }
+}
+
 /// Extractor for query parameters
 ///
 /// # Example
 ///
-/// ```ignore
+/// ```no_run
 /// use ruffus::extractors::Query;
+/// use ruffus::{App, Response};
 /// use serde::Deserialize;
 ///
 /// #[derive(Deserialize)]
@@ -125,18 +204,21 @@ where
 ///     limit: u32,
 /// }
 ///
-/// async fn list_users(Query(params): Query<Pagination>) -> Response {
-///     // Use params.page and params.limit
+/// async fn list_users(Query(params): Query<Pagination>) -> ruffus::Result<Response> {
+///     Ok(Response::text(format!("Page {} of {}", params.page, params.limit)))
 /// }
+///
+/// let mut app = App::new();
+/// app.get("/users", list_users);
 /// ```
 pub struct Query<T>(pub T);
 
 #[async_trait]
-impl<T> FromRequest for Query<T>
+impl<T> FromRequestParts for Query<T>
 where
     T: DeserializeOwned + Send,
 {
-    async fn from_request(req: &mut Request) -> Result<Self> {
+    async fn from_request_parts(req: &mut Request) -> Result<Self> {
         // Convert the query params HashMap to a serde_json::Value
         // Try to parse as numbers/booleans first, fall back to strings
         let query_map: HashMap<String, serde_json::Value> = req
@@ -159,10 +241,7 @@ where
             .collect();
 
         let value = serde_json::Value::Object(
-            query_map
-                .into_iter()
-                .map(|(k, v)| (k, v))
-                .collect(),
+            query_map.into_iter().collect(),
         );
 
         let params: T = serde_json::from_value(value)
@@ -242,6 +321,22 @@ mod tests {
         assert_eq!(params.limit, 10);
     }
 
+    #[tokio::test]
+    async fn test_path_extractor_single_value() {
+        let mut req = Request::new(
+            Method::GET,
+            Uri::from_static("/users/007"),
+            HeaderMap::new(),
+            Bytes::new(),
+        );
+        req.set_param("id".to_string(), "007".to_string());
+
+        let Path(id) = Path::<u64>::from_request(&mut req).await.unwrap();
+        assert_eq!(id, 7);
+        let Path(raw) = Path::<String>::from_request(&mut req).await.unwrap();
+        assert_eq!(raw, "007");
+    }
+
     #[tokio::test]
     async fn test_path_extractor_with_dash() {
         #[derive(Deserialize, Debug, PartialEq)]
@@ -0,0 +1,467 @@
+//! Static file serving
+//!
+//! [`ServeDir`] serves the files below a directory and [`ServeFile`] a single
+//! file. Both are handlers, so they can be registered on any route; a
+//! `ServeDir` takes the file path from the route's `*path` wildcard.
+//! [`App::static_files`](crate::App::static_files) registers a directory
+//! under a prefix in one call.
+//!
+//! Files are streamed from disk with a `Content-Type` guessed from their
+//! extension, and with `ETag` and `Last-Modified` headers so that browsers
+//! can revalidate them with conditional requests, which are answered with
+//! `304 Not Modified`. `Range` requests, including `If-Range` and several
+//! ranges at once, are answered with `206 Partial Content`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::fs::{ServeDir, ServeFile};
+//! use ruffus::{App, Router};
+//!
+//! let mut app = App::new();
+//!
+//! // Everything below ./public at /static
+//! app.static_files("/static", "public");
+//!
+//! // A single page application with client-side routing
+//! let mut ui = Router::new("");
+//! ui.get("/*path", ServeDir::new("dist").precompressed_br().fallback("dist/index.html"));
+//! app.mount("/admin", ui);
+//!
+//! app.get("/favicon.ico", ServeFile::new("public/favicon.ico"));
+//! ```
+
+use bytes::Bytes;
+use futures_util::{future, stream, StreamExt, TryStreamExt};
+use http::header::{self, HeaderMap, HeaderValue};
+use http::StatusCode;
+use std::future::Future;
+use std::io;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::range::{self, Ranges};
+use crate::{Body, Error, Handler, Request, Response, Result};
+
+/// Handler serving the files below a directory
+///
+/// The file is taken from the `path` parameter of the route, usually a
+/// wildcard such as `/assets/*path`; without it the directory itself is
+/// requested. Paths that would leave the directory, through `..` segments or
+/// symbolic links, are answered with `404 Not Found`.
+///
+/// A request for a directory serves its `index.html`. If the request path
+/// doesn't end with a slash, the client is first redirected to the path with
+/// a slash, so relative links in the page resolve correctly.
+#[derive(Debug, Clone)]
+pub struct ServeDir {
+    root: PathBuf,
+    options: Options,
+    fallback: Option<PathBuf>,
+}
+
+impl ServeDir {
+    /// Creates a handler serving the files below `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            options: Options::default(),
+            fallback: None,
+        }
+    }
+
+    /// Serves `file.gz` in place of `file` to clients that accept gzip.
+    pub fn precompressed_gzip(mut self) -> Self {
+        self.options.gzip = true;
+        self
+    }
+
+    /// Serves `file.br` in place of `file` to clients that accept brotli.
+    ///
+    /// Brotli is preferred over gzip when both are enabled and accepted.
+    pub fn precompressed_br(mut self) -> Self {
+        self.options.br = true;
+        self
+    }
+
+    /// Serves `file` instead of answering `404 Not Found` when no file
+    /// matches the request, as single page applications with client-side
+    /// routing need.
+    ///
+    /// The fallback is served with status 200. It isn't used for rejected
+    /// paths, such as attempts to leave the directory.
+    pub fn fallback(mut self, file: impl Into<PathBuf>) -> Self {
+        self.fallback = Some(file.into());
+        self
+    }
+
+    async fn serve(&self, req: &Request) -> Result<Response> {
+        let requested = req.param("path").unwrap_or_default();
+        let relative = match sanitize(requested) {
+            Some(relative) => relative,
+            None => return Err(not_found()),
+        };
+
+        let mut path = self.root.join(&relative);
+        match resolve(&self.root, &path).await {
+            Ok(Resolved::File) => {}
+            Ok(Resolved::Directory) => {
+                let uri_path = req.uri().path();
+                if !uri_path.ends_with('/') {
+                    // A path starting with `//` would redirect to another host
+                    let uri_path = format!("/{}", uri_path.trim_start_matches(['/', '\\']));
+                    let location = match req.uri().query() {
+                        Some(query) => format!("{}/?{}", uri_path, query),
+                        None => format!("{}/", uri_path),
+                    };
+                    return Ok(Response::redirect(&location).status(StatusCode::MOVED_PERMANENTLY));
+                }
+                path.push("index.html");
+                if !matches!(resolve(&self.root, &path).await, Ok(Resolved::File)) {
+                    return self.not_found(req).await;
+                }
+            }
+            Ok(Resolved::Outside) => return Err(not_found()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return self.not_found(req).await,
+            Err(e) => return Err(io_error(e)),
+        }
+
+        serve_file(req.headers(), &path, &self.options).await
+    }
+
+    async fn not_found(&self, req: &Request) -> Result<Response> {
+        match &self.fallback {
+            Some(fallback) => serve_file(req.headers(), fallback, &self.options).await,
+            None => Err(not_found()),
+        }
+    }
+}
+
+impl Handler for ServeDir {
+    fn handle(&self, req: Request) -> Pin<Box<dyn Future<Output = Result<Response>> + Send + 'static>> {
+        let this = self.clone();
+        Box::pin(async move { this.serve(&req).await })
+    }
+}
+
+/// Handler serving a single file
+///
+/// Responds like [`ServeDir`] does for one of its files, whatever the
+/// request path.
+#[derive(Debug, Clone)]
+pub struct ServeFile {
+    path: Arc<Path>,
+    options: Options,
+}
+
+impl ServeFile {
+    /// Creates a handler serving the file at `path`.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: Arc::from(path.as_ref()),
+            options: Options::default(),
+        }
+    }
+
+    /// Serves `file.gz` in place of the file to clients that accept gzip.
+    pub fn precompressed_gzip(mut self) -> Self {
+        self.options.gzip = true;
+        self
+    }
+
+    /// Serves `file.br` in place of the file to clients that accept brotli.
+    pub fn precompressed_br(mut self) -> Self {
+        self.options.br = true;
+        self
+    }
+}
+
+impl Handler for ServeFile {
+    fn handle(&self, req: Request) -> Pin<Box<dyn Future<Output = Result<Response>> + Send + 'static>> {
+        let this = self.clone();
+        Box::pin(async move { serve_file(req.headers(), &this.path, &this.options).await })
+    }
+}
+
+/// Settings shared by `ServeDir` and `ServeFile`
+#[derive(Debug, Clone, Copy, Default)]
+struct Options {
+    gzip: bool,
+    br: bool,
+}
+
+fn not_found() -> Error {
+    Error::Custom {
+        status: StatusCode::NOT_FOUND,
+        message: "File not found".to_string(),
+    }
+}
+
+fn io_error(e: io::Error) -> Error {
+    Error::InternalServerError(format!("Failed to read file: {}", e))
+}
+
+/// Turns the requested (URL decoded) path into a relative path of normal
+/// components.
+///
+/// Returns `None` for paths trying to leave the directory, or containing
+/// characters that are special on some platforms.
+fn sanitize(requested: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for part in requested.split('/') {
+        if part.contains(['\\', ':', '\0']) {
+            return None;
+        }
+        match Path::new(part).components().next() {
+            None | Some(Component::CurDir) => {}
+            Some(Component::Normal(part)) => relative.push(part),
+            Some(_) => return None,
+        }
+    }
+    Some(relative)
+}
+
+enum Resolved {
+    File,
+    Directory,
+    /// A symbolic link pointing outside the root
+    Outside,
+}
+
+/// Checks what `path` is, and that it is still inside `root` once symbolic
+/// links are followed.
+async fn resolve(root: &Path, path: &Path) -> io::Result<Resolved> {
+    let canonical = tokio::fs::canonicalize(path).await?;
+    let root = tokio::fs::canonicalize(root).await?;
+    if !canonical.starts_with(&root) {
+        return Ok(Resolved::Outside);
+    }
+    let metadata = tokio::fs::metadata(&canonical).await?;
+    Ok(if metadata.is_dir() {
+        Resolved::Directory
+    } else {
+        Resolved::File
+    })
+}
+
+/// Serves the file at `path`, or a precompressed sibling of it.
+async fn serve_file(headers: &HeaderMap, path: &Path, options: &Options) -> Result<Response> {
+    let accepted = |encoding| accepts_encoding(headers, encoding);
+    let candidates = [
+        (options.br && accepted("br")).then_some(("br", "br")),
+        (options.gzip && accepted("gzip")).then_some(("gzip", "gz")),
+    ];
+
+    let mut encoding = None;
+    let mut file = None;
+    for (name, extension) in candidates.into_iter().flatten() {
+        let mut sibling = path.as_os_str().to_owned();
+        sibling.push(".");
+        sibling.push(extension);
+        let sibling = PathBuf::from(sibling);
+        if let Ok(opened) = open_file(&sibling).await {
+            encoding = Some(name);
+            file = Some((opened, sibling));
+            break;
+        }
+    }
+    let ((file, metadata), opened) = match file {
+        Some(file) => file,
+        None => {
+            let file = open_file(path).await.map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => not_found(),
+                _ => io_error(e),
+            })?;
+            (file, path.to_path_buf())
+        }
+    };
+
+    let len = metadata.len();
+    let modified = metadata.modified().ok();
+    let etag = entity_tag(len, modified);
+
+    let mut response = Response::new().header("ETag", &etag);
+    if let Some(modified) = modified {
+        response = response.header("Last-Modified", &httpdate::fmt_http_date(modified));
+    }
+    if let Some(encoding) = encoding {
+        response = response.header("Content-Encoding", encoding);
+    }
+    if options.gzip || options.br {
+        response = response.header("Vary", "Accept-Encoding");
+    }
+    if is_fresh(headers, &etag, modified) {
+        return Ok(response.status(StatusCode::NOT_MODIFIED));
+    }
+
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    response = response.header("Accept-Ranges", "bytes");
+    let response = match range::requested(headers, response.get_headers(), len) {
+        Ranges::Full => response
+            .header("Content-Type", mime.as_ref())
+            .header("Content-Length", &len.to_string())
+            .body_stream(Body::from_reader(file)),
+        Ranges::Unsatisfiable => response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header("Content-Range", &range::unsatisfied_range(len)),
+        Ranges::Partial(ranges) => match ranges.as_slice() {
+            [range] => response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header("Content-Type", mime.as_ref())
+                .header("Content-Range", &range::content_range(range, len))
+                .header("Content-Length", &(range.end - range.start).to_string())
+                .body_stream(read_range(file, range.clone()).await.map_err(io_error)?),
+            _ => {
+                let boundary = range::boundary();
+                let closing = range::closing(&boundary);
+                let mut content_length = closing.len() as u64;
+                let parts: Vec<(Bytes, Range<u64>)> = ranges
+                    .into_iter()
+                    .map(|range| {
+                        let header = range::part_header(&boundary, Some(mime.as_ref()), &range, len);
+                        content_length += header.len() as u64 + range.end - range.start;
+                        (Bytes::from(header), range)
+                    })
+                    .collect();
+
+                // Each part reads from its own handle, opened when it's reached
+                let parts = stream::iter(parts).then(move |(header, range)| {
+                    let opened = opened.clone();
+                    async move {
+                        let file = tokio::fs::File::open(&opened).await?;
+                        let data = read_range(file, range).await?;
+                        Ok::<_, io::Error>(stream::once(future::ready(Ok(header))).chain(data))
+                    }
+                });
+                let body = parts
+                    .try_flatten()
+                    .chain(stream::once(future::ready(Ok(Bytes::from(closing)))));
+
+                response
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header("Content-Type", &format!("multipart/byteranges; boundary={}", boundary))
+                    .header("Content-Length", &content_length.to_string())
+                    .body_stream(Body::from_stream(body))
+            }
+        },
+    };
+    Ok(response)
+}
+
+/// Streams `range` of a file.
+async fn read_range(mut file: tokio::fs::File, range: Range<u64>) -> io::Result<Body> {
+    file.seek(io::SeekFrom::Start(range.start)).await?;
+    Ok(Body::from_reader(file.take(range.end - range.start)))
+}
+
+/// Opens a regular file, failing with `NotFound` for directories.
+async fn open_file(path: &Path) -> io::Result<(tokio::fs::File, std::fs::Metadata)> {
+    let file = tokio::fs::File::open(path).await?;
+    let metadata = file.metadata().await?;
+    if !metadata.is_file() {
+        return Err(io::ErrorKind::NotFound.into());
+    }
+    Ok((file, metadata))
+}
+
+/// Builds an entity tag from the size and modification time of a file.
+fn entity_tag(len: u64, modified: Option<SystemTime>) -> String {
+    let modified = modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos());
+    format!("\"{:x}-{:x}\"", modified, len)
+}
+
+/// Returns whether the client's cached copy is current, so `304 Not
+/// Modified` can be sent instead of the file.
+fn is_fresh(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    // If-None-Match takes precedence over If-Modified-Since
+    if let Some(if_none_match) = header_str(headers, &header::IF_NONE_MATCH) {
+        return if_none_match.split(',').map(str::trim).any(|tag| {
+            // Weak comparison: W/"x" matches "x"
+            tag == "*" || tag.trim_start_matches("W/") == etag
+        });
+    }
+
+    let since = header_str(headers, &header::IF_MODIFIED_SINCE).and_then(|since| httpdate::parse_http_date(since).ok());
+    match (since, modified) {
+        // HTTP dates have a resolution of one second
+        (Some(since), Some(modified)) => modified
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .zip(since.duration_since(UNIX_EPOCH).ok())
+            .is_some_and(|(modified, since)| modified.as_secs() <= since.as_secs()),
+        _ => false,
+    }
+}
+
+/// Returns whether `Accept-Encoding` allows `encoding`, with a non-zero
+/// quality.
+fn accepts_encoding(headers: &HeaderMap, encoding: &str) -> bool {
+    let mut explicit = None;
+    let mut wildcard = None;
+    let entries = headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+    for entry in entries {
+        let mut parts = entry.split(';');
+        let name = parts.next().unwrap_or_default().trim();
+        let quality = parts
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .and_then(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(encoding) {
+            explicit = Some(quality);
+        } else if name == "*" {
+            wildcard = Some(quality);
+        }
+    }
+    // `*` only covers the codings that aren't listed
+    explicit.or(wildcard).is_some_and(|quality| quality > 0.0)
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &header::HeaderName) -> Option<&'a str> {
+    headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("css/site.css"), Some(PathBuf::from("css/site.css")));
+        assert_eq!(sanitize("./a//b/"), Some(PathBuf::from("a/b")));
+        assert_eq!(sanitize(""), Some(PathBuf::new()));
+        assert_eq!(sanitize("../secret"), None);
+        assert_eq!(sanitize("a/../../secret"), None);
+        assert_eq!(sanitize("..\\secret"), None);
+        assert_eq!(sanitize("C:/Windows"), None);
+        assert_eq!(sanitize("a\0b"), None);
+    }
+
+    #[test]
+    fn test_accepts_encoding() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_encoding(&headers, "gzip"));
+
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip, deflate, br;q=0"));
+        assert!(accepts_encoding(&headers, "gzip"));
+        assert!(!accepts_encoding(&headers, "br"));
+
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("*;q=0.5"));
+        assert!(accepts_encoding(&headers, "br"));
+
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip;q=0, *"));
+        assert!(!accepts_encoding(&headers, "gzip"));
+        assert!(accepts_encoding(&headers, "br"));
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("*, gzip;q=0"));
+        assert!(!accepts_encoding(&headers, "gzip"));
+    }
+}
@@ -0,0 +1,113 @@
+//! Handler trait and its implementations for extractor functions
+//!
+//! Any async function or closure whose arguments are extractors can be used as
+//! a route handler. Every argument but the last must implement
+//! [`FromRequestParts`], so it leaves the request body alone; the last argument
+//! only needs [`FromRequest`] and may consume the body.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::{App, Json, Path, Response};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct NewTask {
+//!     title: String,
+//! }
+//!
+//! async fn create(Path(id): Path<u64>, Json(body): Json<NewTask>) -> ruffus::Result<Response> {
+//!     Ok(Response::text(format!("Created {} in list {}", body.title, id)))
+//! }
+//!
+//! let mut app = App::new();
+//! app.post("/lists/:id/tasks", create);
+//! ```
+
+use crate::extractors::{FromRequest, FromRequestParts};
+use crate::{IntoResponse, Request, Response, Result};
+use std::future::Future;
+use std::pin::Pin;
+
+/// Trait for request handlers.
+///
+/// Handlers are async functions that process requests and return responses.
+/// This trait is automatically implemented for async closures and functions
+/// taking up to 12 extractors, including a plain [`Request`], and returning
+/// any [`IntoResponse`] type. The type parameter only tells those
+/// implementations apart and can be ignored.
+///
+/// Implement it with the default parameter to use your own type as a handler:
+///
+/// ```
+/// use ruffus::{Handler, Request, Response, Result};
+/// use std::future::Future;
+/// use std::pin::Pin;
+///
+/// struct Hello;
+///
+/// impl Handler for Hello {
+///     fn handle(&self, _req: Request) -> Pin<Box<dyn Future<Output = Result<Response>> + Send + 'static>> {
+///         Box::pin(async { Ok(Response::text("Hello!".to_string())) })
+///     }
+/// }
+///
+/// let mut app = ruffus::App::new();
+/// app.get("/hello", Hello);
+/// ```
+pub trait Handler<T = Request>: Send + Sync + 'static {
+    /// Handles a request and returns a response.
+    ///
+    /// This method is automatically implemented for async closures and functions.
+    fn handle(&self, req: Request) -> Pin<Box<dyn Future<Output = Result<Response>> + Send + 'static>>;
+}
+
+impl<F, Fut> Handler<()> for F
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoResponse,
+{
+    fn handle(&self, _req: Request) -> Pin<Box<dyn Future<Output = Result<Response>> + Send + 'static>> {
+        let future = self();
+        Box::pin(async move { future.await.into_result() })
+    }
+}
+
+/// Implements `Handler` for functions taking the listed extractors, where all
+/// but the last extract from the request parts.
+macro_rules! impl_handler {
+    ([$($part:ident),*], $last:ident) => {
+        #[allow(non_snake_case)]
+        impl<F, Fut, $($part,)* $last> Handler<($($part,)* $last,)> for F
+        where
+            F: Fn($($part,)* $last) -> Fut + Clone + Send + Sync + 'static,
+            Fut: Future + Send + 'static,
+            Fut::Output: IntoResponse,
+            $($part: FromRequestParts + Send + 'static,)*
+            $last: FromRequest + Send + 'static,
+        {
+            fn handle(&self, mut req: Request) -> Pin<Box<dyn Future<Output = Result<Response>> + Send + 'static>> {
+                let handler = self.clone();
+                Box::pin(async move {
+                    $(let $part = $part::from_request_parts(&mut req).await?;)*
+                    let $last = $last::from_request(&mut req).await?;
+                    handler($($part,)* $last).await.into_result()
+                })
+            }
+        }
+    };
+}
+
+impl_handler!([], T1);
+impl_handler!([T1], T2);
+impl_handler!([T1, T2], T3);
+impl_handler!([T1, T2, T3], T4);
+impl_handler!([T1, T2, T3, T4], T5);
+impl_handler!([T1, T2, T3, T4, T5], T6);
+impl_handler!([T1, T2, T3, T4, T5, T6], T7);
+impl_handler!([T1, T2, T3, T4, T5, T6, T7], T8);
+impl_handler!([T1, T2, T3, T4, T5, T6, T7, T8], T9);
+impl_handler!([T1, T2, T3, T4, T5, T6, T7, T8, T9], T10);
+impl_handler!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10], T11);
+impl_handler!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11], T12);
@@ -21,7 +21,7 @@
 //!     let mut app = App::new();
 //!     
 //!     app.get("/", |_req: Request| async {
-//!         Ok(Response::text("Hello, World!".to_string()))
+//!         Response::text("Hello, World!".to_string())
 //!     });
 //!     
 //!     app.listen("127.0.0.1:3000").await.unwrap();
@@ -39,7 +39,7 @@
 //! # let mut app = App::new();
 //! app.get("/users/:id", |req: Request| async move {
 //!     let id = req.param("id").unwrap();
-//!     Ok(Response::text(format!("User ID: {}", id)))
+//!     Response::text(format!("User ID: {}", id))
 //! });
 //! # }
 //! ```
@@ -117,12 +117,12 @@
 //! let mut api = Router::new("/api");
 //!
 //! api.get("/users", |_req: Request| async {
-//!     Ok(Response::json(&serde_json::json!({"users": []}))?)
+//!     Response::json(&serde_json::json!({"users": []}))
 //! });
 //!
 //! api.post("/users", |mut req: Request| async move {
 //!     // Handle user creation
-//!     Ok(Response::json(&serde_json::json!({"status": "created"}))?)
+//!     Response::json(&serde_json::json!({"status": "created"}))
 //! });
 //!
 //! let mut app = App::new();
@@ -130,24 +130,89 @@
 //! // Routes are now available at /api/users
 //! # }
 //! ```
+//!
+//! # Cargo Features
+//!
+//! - `tls`: HTTPS support through rustls, with `App::listen_tls` and `TlsConfig`
+//! - `ws`: WebSockets through tungstenite, with the `WebSocketUpgrade` extractor
+//! - `multipart`: `multipart/form-data` bodies and file uploads, with the
+//!   `Multipart` extractor
+//! - `compression`: the `Compression` and `Decompression` middleware, with
+//!   every algorithm. `compression-br`, `compression-zstd`,
+//!   `compression-gzip` and `compression-deflate` enable them one by one.
+
+#![cfg_attr(docsrs, feature(doc_cfg))]
 
 pub mod app;
+pub mod body;
+#[cfg(any(
+    feature = "compression-br",
+    feature = "compression-zstd",
+    feature = "compression-gzip",
+    feature = "compression-deflate"
+))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(any(
+        feature = "compression-br",
+        feature = "compression-zstd",
+        feature = "compression-gzip",
+        feature = "compression-deflate"
+    )))
+)]
+pub mod compression;
+pub mod concurrency_limit;
+pub mod cors;
 pub mod error;
 pub mod extractors;
+pub mod fs;
+pub mod handler;
+pub mod listener;
 pub mod method;
 pub mod middleware;
+#[cfg(feature = "multipart")]
+#[cfg_attr(docsrs, doc(cfg(feature = "multipart")))]
+pub mod multipart;
+mod range;
+pub mod rate_limit;
 pub mod request;
 pub mod response;
 pub mod router;
+pub mod server;
+pub mod sse;
+pub mod state;
+pub mod timeout;
+#[cfg(feature = "tls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+pub mod tls;
+#[cfg(feature = "ws")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+pub mod ws;
 
 // Re-export main types for convenience
 pub use app::App;
+pub use body::Body;
+pub use concurrency_limit::ConcurrencyLimit;
+pub use cors::Cors;
 pub use error::Error;
-pub use extractors::{FromRequest, Json, Path, Query};
+pub use extractors::{FromRequest, FromRequestParts, Json, Path, Query};
+pub use fs::{ServeDir, ServeFile};
 pub use method::Method;
-pub use middleware::{Handler, Middleware, Next};
+pub use handler::Handler;
+pub use listener::{ConnectInfo, Listener};
+pub use middleware::{Middleware, Next};
+#[cfg(feature = "multipart")]
+pub use multipart::Multipart;
+pub use rate_limit::RateLimit;
 pub use request::Request;
-pub use response::Response;
-pub use router::{PathPattern, Route, Router, Segment};
+pub use response::{IntoResponse, Response};
+pub use router::{Constraint, PathPattern, Route, RouteMatch, Router, Segment};
+pub use server::Server;
+pub use state::{FromRef, State};
+pub use timeout::Timeout;
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+#[cfg(feature = "ws")]
+pub use ws::{WebSocket, WebSocketUpgrade};
 
 pub type Result<T> = std::result::Result<T, Error>;
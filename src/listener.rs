@@ -0,0 +1,225 @@
+//! Listeners the server accepts connections from
+//!
+//! [`App::serve`](crate::App::serve) runs an application on any [`Listener`]:
+//! a Tokio `TcpListener` or `UnixListener` bound by the caller, a socket
+//! inherited through systemd socket activation with [`listen_fds`], or a
+//! custom acceptor.
+//!
+//! The address of the peer of each connection is available to handlers
+//! through the [`ConnectInfo`] extractor.
+//!
+//! # Examples
+//!
+//! Binding to port 0 and reading the port the system picked:
+//!
+//! ```no_run
+//! use ruffus::App;
+//! use tokio::net::TcpListener;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+//!     println!("Listening on port {}", listener.local_addr().unwrap().port());
+//!
+//!     App::new().serve(listener).await.unwrap();
+//! }
+//! ```
+
+use async_trait::async_trait;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::extractors::FromRequestParts;
+use crate::{Error, Request, Result};
+
+/// A source of connections for the server.
+///
+/// Implement this trait to serve from a custom acceptor. Errors returned by
+/// [`accept`](Listener::accept) that concern a single connection, such as
+/// [`ConnectionAborted`](io::ErrorKind::ConnectionAborted), are skipped, and
+/// other errors, such as running out of file descriptors, are logged and
+/// retried after a short pause. Errors of kind
+/// [`InvalidInput`](io::ErrorKind::InvalidInput),
+/// [`NotConnected`](io::ErrorKind::NotConnected),
+/// [`BrokenPipe`](io::ErrorKind::BrokenPipe),
+/// [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) or
+/// [`Unsupported`](io::ErrorKind::Unsupported) mean the listener is unusable:
+/// the server shuts down gracefully and returns the error.
+#[async_trait]
+pub trait Listener: Send + 'static {
+    /// The connection type.
+    type Io: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+    /// The address of a connected peer.
+    type Addr: Clone + fmt::Debug + Send + Sync + 'static;
+
+    /// Waits for the next connection.
+    ///
+    /// The server may drop the returned future before it completes, so it
+    /// must be cancel safe: no connection may be lost when that happens.
+    async fn accept(&mut self) -> io::Result<(Self::Io, Self::Addr)>;
+
+    /// Returns the local address this listener is bound to.
+    fn local_addr(&self) -> io::Result<Self::Addr>;
+}
+
+#[async_trait]
+impl Listener for TcpListener {
+    type Io = TcpStream;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> io::Result<(Self::Io, Self::Addr)> {
+        TcpListener::accept(self).await
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        TcpListener::local_addr(self)
+    }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl Listener for tokio::net::UnixListener {
+    type Io = tokio::net::UnixStream;
+    type Addr = tokio::net::unix::SocketAddr;
+
+    async fn accept(&mut self) -> io::Result<(Self::Io, Self::Addr)> {
+        tokio::net::UnixListener::accept(self).await
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        tokio::net::UnixListener::local_addr(self)
+    }
+}
+
+/// Extractor for the address of the connected peer
+///
+/// `T` is the address type of the [`Listener`] the app is served from:
+/// [`SocketAddr`] for TCP, `tokio::net::unix::SocketAddr` for Unix sockets.
+/// Extraction fails with a 500 error if the request didn't come from such a
+/// listener, e.g. in tests calling `App::handle_request` directly.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ruffus::{App, ConnectInfo, Response};
+/// use std::net::SocketAddr;
+///
+/// let mut app = App::new();
+/// app.get("/ip", |ConnectInfo(addr): ConnectInfo<SocketAddr>| async move {
+///     Response::text(addr.ip().to_string())
+/// });
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectInfo<T>(pub T);
+
+#[async_trait]
+impl<T> FromRequestParts for ConnectInfo<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    async fn from_request_parts(req: &mut Request) -> Result<Self> {
+        req.extensions().get::<ConnectInfo<T>>().cloned().ok_or_else(|| {
+            Error::InternalServerError(format!(
+                "No connection info of type `{}` is available",
+                std::any::type_name::<T>()
+            ))
+        })
+    }
+}
+
+/// The first file descriptor passed by socket activation
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// Takes the sockets passed to this process by systemd socket activation.
+///
+/// Reads `LISTEN_PID` and `LISTEN_FDS` and returns the inherited file
+/// descriptors in order, or an empty list if the process wasn't socket
+/// activated. The sockets are only handed out once: later calls return an
+/// empty list. The environment is left untouched, which makes this safe to
+/// call from any thread; child processes ignore the variables since
+/// `LISTEN_PID` names this process.
+///
+/// # Errors
+///
+/// Returns an error if `LISTEN_FDS` isn't a number, or is too large to be a
+/// range of file descriptors.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[tokio::main]
+/// # async fn main() -> ruffus::Result<()> {
+/// use ruffus::listener::listen_fds;
+///
+/// let fd = listen_fds()?.into_iter().next().expect("not socket activated");
+/// let listener = std::net::TcpListener::from(fd);
+/// listener.set_nonblocking(true).unwrap();
+///
+/// ruffus::App::new()
+///     .serve(tokio::net::TcpListener::from_std(listener).unwrap())
+///     .await
+/// # }
+/// ```
+#[cfg(unix)]
+pub fn listen_fds() -> Result<Vec<std::os::fd::OwnedFd>> {
+    use std::os::fd::FromRawFd;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Set once the descriptors have been handed out
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+    let count = parse_listen_fds(pid.as_deref(), fds.as_deref(), std::process::id())?;
+    if count == 0 || TAKEN.swap(true, Ordering::SeqCst) {
+        return Ok(Vec::new());
+    }
+
+    Ok((LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            // SAFETY: systemd passes `count` open descriptors starting at 3,
+            // and `TAKEN` keeps them from being taken twice
+            unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) }
+        })
+        .collect())
+}
+
+/// Returns how many descriptors were passed to the process `own_pid`.
+#[cfg(unix)]
+fn parse_listen_fds(pid: Option<&str>, fds: Option<&str>, own_pid: u32) -> Result<i32> {
+    // The sockets are meant for another process, e.g. our parent
+    if pid.and_then(|pid| pid.parse::<u32>().ok()) != Some(own_pid) {
+        return Ok(0);
+    }
+
+    let Some(fds) = fds else {
+        return Ok(0);
+    };
+    // The descriptors, up to 3 + count, must all be valid `RawFd`s
+    fds.parse::<i32>()
+        .ok()
+        .filter(|count| (0..=i32::MAX - LISTEN_FDS_START).contains(count))
+        .ok_or_else(|| Error::InternalServerError(format!("Invalid LISTEN_FDS: {}", fds)))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listen_fds() {
+        assert_eq!(parse_listen_fds(Some("42"), Some("2"), 42).unwrap(), 2);
+        assert_eq!(parse_listen_fds(Some("41"), Some("2"), 42).unwrap(), 0);
+        assert_eq!(parse_listen_fds(None, Some("2"), 42).unwrap(), 0);
+        assert_eq!(parse_listen_fds(Some("42"), None, 42).unwrap(), 0);
+        assert!(parse_listen_fds(Some("42"), Some("two"), 42).is_err());
+        assert!(parse_listen_fds(Some("42"), Some("-1"), 42).is_err());
+        assert!(parse_listen_fds(Some("42"), Some("4294967298"), 42).is_err());
+        assert!(parse_listen_fds(Some("42"), Some("2147483645"), 42).is_err());
+        assert_eq!(parse_listen_fds(Some("42"), Some("2147483644"), 42).unwrap(), 2147483644);
+    }
+}
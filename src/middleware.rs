@@ -34,27 +34,7 @@ pub type BoxedHandler = Arc<
         + 'static,
 >;
 
-/// Trait for request handlers.
-///
-/// Handlers are async functions that process requests and return responses.
-/// This trait is automatically implemented for async closures and functions.
-pub trait Handler: Send + Sync + 'static {
-    /// Handles a request and returns a response.
-    ///
-    /// This method is automatically implemented for async closures and functions.
-    fn handle(&self, req: Request) -> Pin<Box<dyn Future<Output = Result<Response>> + Send + 'static>>;
-}
-
-/// Implement Handler for async closures and functions
-impl<F, Fut> Handler for F
-where
-    F: Fn(Request) -> Fut + Send + Sync + 'static,
-    Fut: Future<Output = Result<Response>> + Send + 'static,
-{
-    fn handle(&self, req: Request) -> Pin<Box<dyn Future<Output = Result<Response>> + Send + 'static>> {
-        Box::pin(self(req))
-    }
-}
+pub use crate::handler::Handler;
 
 /// Trait for middleware that can process requests.
 ///
@@ -0,0 +1,378 @@
+//! `multipart/form-data` bodies
+//!
+//! The [`Multipart`] extractor reads the fields of an HTML form, including
+//! file uploads, as they arrive. Each [`Field`] exposes its name, file name,
+//! content type and headers, and its data can be streamed chunk by chunk,
+//! buffered, or written to a temporary file once it grows past a threshold.
+//!
+//! The whole body is limited to the request's
+//! [body limit](crate::Request::body_limit) unless
+//! [`Multipart::total_limit`] sets another one, and
+//! [`Multipart::field_limit`] bounds each field. Exceeding a limit fails with
+//! `413 Payload Too Large`; a body that doesn't parse fails with
+//! `400 Bad Request`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::multipart::FieldData;
+//! use ruffus::{App, Multipart, Response};
+//!
+//! let mut app = App::new();
+//!
+//! app.post("/upload", |multipart: Multipart| async move {
+//!     let mut multipart = multipart
+//!         .total_limit(100 * 1024 * 1024)
+//!         .spool_threshold(1024 * 1024);
+//!
+//!     let mut names = Vec::new();
+//!     while let Some(field) = multipart.next_field().await? {
+//!         let path = format!("uploads/{}", names.len());
+//!         names.push(field.file_name().unwrap_or("upload").to_string());
+//!         match field.data().await? {
+//!             FieldData::Memory(bytes) => {
+//!                 tokio::fs::write(path, bytes)
+//!                     .await
+//!                     .map_err(|e| ruffus::Error::InternalServerError(e.to_string()))?;
+//!             }
+//!             FieldData::File(file) => file.persist(path).await?,
+//!         }
+//!     }
+//!     Response::json(&names)
+//! });
+//! ```
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_core::Stream;
+use http::header::{self, HeaderMap};
+use http::StatusCode;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncWriteExt;
+
+use crate::body;
+use crate::extractors::FromRequest;
+use crate::{Body, Error, Request, Result};
+
+/// Extractor for a `multipart/form-data` request body
+///
+/// Extraction fails with `400 Bad Request` if the request isn't
+/// `multipart/form-data` or has no boundary. Limits and spooling are set on
+/// the extracted value, before the first call to
+/// [`next_field`](Self::next_field).
+pub struct Multipart {
+    state: State,
+    declared: Option<u64>,
+    total_limit: Option<usize>,
+    field_limit: Option<usize>,
+    spool: Spool,
+}
+
+enum State {
+    Pending { body: Body, boundary: String },
+    Parsing(multer::Multipart<'static>),
+    Failed,
+}
+
+/// Where and from which size field data is written to disk
+#[derive(Debug, Clone)]
+struct Spool {
+    threshold: Option<usize>,
+    dir: Option<PathBuf>,
+}
+
+impl Multipart {
+    /// Sets the largest body in bytes, replacing the request's body limit.
+    pub fn total_limit(mut self, limit: usize) -> Self {
+        self.total_limit = Some(limit);
+        self
+    }
+
+    /// Sets the largest field in bytes. Fields are only bounded by the total
+    /// limit by default.
+    pub fn field_limit(mut self, limit: usize) -> Self {
+        self.field_limit = Some(limit);
+        self
+    }
+
+    /// Makes [`Field::data`] write fields larger than `bytes` to a temporary
+    /// file instead of keeping them in memory.
+    pub fn spool_threshold(mut self, bytes: usize) -> Self {
+        self.spool.threshold = Some(bytes);
+        self
+    }
+
+    /// Sets the directory of spooled files, the system's temporary directory
+    /// by default.
+    pub fn spool_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spool.dir = Some(dir.into());
+        self
+    }
+
+    /// Returns the next field, or `None` after the last one.
+    ///
+    /// The previous field must have been dropped or read to the end, as
+    /// fields are read from the body in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `413 Payload Too Large` if the body exceeds the total limit,
+    /// and `400 Bad Request` if it isn't valid `multipart/form-data`.
+    pub async fn next_field(&mut self) -> Result<Option<Field>> {
+        self.state = match std::mem::replace(&mut self.state, State::Failed) {
+            State::Pending { body, boundary } => {
+                if let (Some(declared), Some(limit)) = (self.declared, self.total_limit) {
+                    if declared > limit as u64 {
+                        return Err(too_large(limit as u64));
+                    }
+                }
+                State::Parsing(self.parser(body, boundary))
+            }
+            state => state,
+        };
+
+        let parser = match &mut self.state {
+            State::Parsing(parser) => parser,
+            _ => return Err(Error::BadRequest("Multipart body can't be read again".to_string())),
+        };
+        let field = parser.next_field().await.map_err(multipart_error)?;
+        Ok(field.map(|inner| Field {
+            inner,
+            spool: self.spool.clone(),
+        }))
+    }
+
+    fn parser(&self, body: Body, boundary: String) -> multer::Multipart<'static> {
+        let mut size_limit = multer::SizeLimit::new();
+        if let Some(limit) = self.total_limit {
+            size_limit = size_limit.whole_stream(limit as u64);
+        }
+        if let Some(limit) = self.field_limit {
+            size_limit = size_limit.per_field(limit as u64);
+        }
+        let constraints = multer::Constraints::new().size_limit(size_limit);
+        multer::Multipart::with_constraints(body, boundary, constraints)
+    }
+}
+
+#[async_trait]
+impl FromRequest for Multipart {
+    async fn from_request(req: &mut Request) -> Result<Self> {
+        let boundary = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|content_type| multer::parse_boundary(content_type).ok())
+            .ok_or_else(|| Error::BadRequest("Expected a multipart/form-data body with a boundary".to_string()))?;
+        let declared = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+
+        Ok(Multipart {
+            state: State::Pending {
+                body: req.take_body(),
+                boundary,
+            },
+            declared,
+            total_limit: req.body_limit(),
+            field_limit: None,
+            spool: Spool {
+                threshold: None,
+                dir: None,
+            },
+        })
+    }
+}
+
+/// A field of a `multipart/form-data` body
+///
+/// A field is also a [`Stream`] of its data in chunks.
+pub struct Field {
+    inner: multer::Field<'static>,
+    spool: Spool,
+}
+
+impl Field {
+    /// Returns the name of the field, from its `Content-Disposition` header.
+    pub fn name(&self) -> Option<&str> {
+        self.inner.name()
+    }
+
+    /// Returns the file name of an uploaded file, as sent by the client.
+    ///
+    /// The name is untrusted input: don't use it as a path without
+    /// sanitizing it.
+    pub fn file_name(&self) -> Option<&str> {
+        self.inner.file_name()
+    }
+
+    /// Returns the `Content-Type` of the field, if it has one.
+    pub fn content_type(&self) -> Option<&str> {
+        self.inner.content_type().map(|mime| mime.as_ref())
+    }
+
+    /// Returns all the headers of the field.
+    pub fn headers(&self) -> &HeaderMap {
+        self.inner.headers()
+    }
+
+    /// Reads the next chunk of the field's data, or `None` at its end.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        self.inner.chunk().await.map_err(multipart_error)
+    }
+
+    /// Reads the whole field into memory.
+    pub async fn bytes(self) -> Result<Bytes> {
+        self.inner.bytes().await.map_err(multipart_error)
+    }
+
+    /// Reads the whole field as text, decoded with the charset of its
+    /// content type (UTF-8 by default).
+    pub async fn text(self) -> Result<String> {
+        self.inner.text().await.map_err(multipart_error)
+    }
+
+    /// Reads the whole field, into memory up to the
+    /// [spool threshold](Multipart::spool_threshold) and into a temporary
+    /// file beyond it.
+    pub async fn data(mut self) -> Result<FieldData> {
+        let threshold = match self.spool.threshold {
+            Some(threshold) => threshold,
+            None => return self.bytes().await.map(FieldData::Memory),
+        };
+
+        let mut buffer = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            if buffer.len() > threshold {
+                return self.spool(buffer).await.map(FieldData::File);
+            }
+        }
+        Ok(FieldData::Memory(Bytes::from(buffer)))
+    }
+
+    /// Writes the data read so far, then the rest of the field, to a new
+    /// temporary file.
+    async fn spool(mut self, head: Vec<u8>) -> Result<SpooledFile> {
+        let dir = self.spool.dir.clone().unwrap_or_else(std::env::temp_dir);
+        let temp = tokio::task::spawn_blocking(move || tempfile::Builder::new().prefix("ruffus-upload-").tempfile_in(dir))
+            .await
+            .map_err(|e| Error::InternalServerError(e.to_string()))?
+            .map_err(spool_error)?;
+        let (file, path) = temp.into_parts();
+        let mut file = tokio::fs::File::from_std(file);
+
+        let mut len = head.len() as u64;
+        file.write_all(&head).await.map_err(spool_error)?;
+        while let Some(chunk) = self.chunk().await? {
+            len += chunk.len() as u64;
+            file.write_all(&chunk).await.map_err(spool_error)?;
+        }
+        file.flush().await.map_err(spool_error)?;
+        Ok(SpooledFile { path, len })
+    }
+}
+
+impl Stream for Field {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner)
+            .poll_next(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map_err(multipart_error)))
+    }
+}
+
+/// The data of a field read with [`Field::data`]
+#[derive(Debug)]
+pub enum FieldData {
+    /// A field up to the spool threshold, kept in memory
+    Memory(Bytes),
+    /// A field beyond the spool threshold, written to a temporary file
+    File(SpooledFile),
+}
+
+/// A field written to a temporary file
+///
+/// The file is deleted when this value is dropped, unless it is
+/// [persisted](Self::persist).
+#[derive(Debug)]
+pub struct SpooledFile {
+    path: tempfile::TempPath,
+    len: u64,
+}
+
+impl SpooledFile {
+    /// Returns the path of the temporary file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the size of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Moves the file to `path`, replacing any file there, so it is kept.
+    ///
+    /// The file is copied when it can't be moved, such as to another file
+    /// system.
+    pub async fn persist(self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        match self.path.persist(path) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                // The temporary file is removed when `e.path` is dropped
+                tokio::fs::copy(&e.path, path).await.map_err(spool_error)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn too_large(limit: u64) -> Error {
+    Error::Custom {
+        status: StatusCode::PAYLOAD_TOO_LARGE,
+        message: format!("Request body is larger than {} bytes", limit),
+    }
+}
+
+fn spool_error(e: io::Error) -> Error {
+    Error::InternalServerError(format!("Failed to spool upload: {}", e))
+}
+
+fn multipart_error(e: multer::Error) -> Error {
+    match e {
+        multer::Error::StreamSizeExceeded { limit } => too_large(limit),
+        multer::Error::FieldSizeExceeded { limit, field_name } => Error::Custom {
+            status: StatusCode::PAYLOAD_TOO_LARGE,
+            message: format!(
+                "Field {:?} is larger than {} bytes",
+                field_name.as_deref().unwrap_or_default(),
+                limit
+            ),
+        },
+        multer::Error::StreamReadFailed(e) => {
+            let io_error = e.downcast_ref::<io::Error>();
+            match (io_error.and_then(body::limit_exceeded), io_error.map(io::Error::kind)) {
+                (Some(limit), _) => too_large(limit as u64),
+                (None, Some(io::ErrorKind::TimedOut)) => Error::Custom {
+                    status: StatusCode::REQUEST_TIMEOUT,
+                    message: "Timed out reading the request body".to_string(),
+                },
+                _ => Error::BadRequest(format!("Failed to read request body: {}", e)),
+            }
+        }
+        e => Error::BadRequest(format!("Invalid multipart body: {}", e)),
+    }
+}
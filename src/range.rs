@@ -0,0 +1,187 @@
+//! Range requests
+//!
+//! Parsing of the `Range` and `If-Range` request headers, and the framing of
+//! `multipart/byteranges` bodies, shared by [`Response::ranged`] and the
+//! static file handlers.
+//!
+//! [`Response::ranged`]: crate::Response::ranged
+
+use http::header::{self, HeaderMap};
+use std::hash::{BuildHasher, Hasher};
+use std::ops::Range;
+
+/// Most ranges served from one request; more are likely an attempt to make
+/// the server do a lot of work for little data, so the whole body is sent.
+const MAX_RANGES: usize = 64;
+
+/// What a request asks for from a representation of some length
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Ranges {
+    /// The whole representation, with status 200
+    Full,
+    /// None of the ranges overlap the representation, answered with 416
+    Unsatisfiable,
+    /// Byte ranges, clamped to the representation, answered with 206
+    Partial(Vec<Range<u64>>),
+}
+
+/// Reads the ranges requested for a representation of `len` bytes.
+///
+/// `response_headers` are the headers of the full response, whose `ETag` or
+/// `Last-Modified` must match `If-Range` for ranges to be served.
+pub(crate) fn requested(request_headers: &HeaderMap, response_headers: &HeaderMap, len: u64) -> Ranges {
+    let range = match request_headers.get(header::RANGE).and_then(|value| value.to_str().ok()) {
+        Some(range) => range,
+        None => return Ranges::Full,
+    };
+    if let Some(if_range) = request_headers.get(header::IF_RANGE) {
+        // Weak entity tags never match, and dates must match exactly
+        let matches = match if_range.as_bytes() {
+            [b'W', b'/', ..] => false,
+            [b'"', ..] => response_headers.get(header::ETAG) == Some(if_range),
+            _ => response_headers.get(header::LAST_MODIFIED) == Some(if_range),
+        };
+        if !matches {
+            return Ranges::Full;
+        }
+    }
+
+    match parse(range, len).map(coalesce) {
+        Some(ranges) if ranges.is_empty() => Ranges::Unsatisfiable,
+        Some(ranges) if ranges.len() <= MAX_RANGES => Ranges::Partial(ranges),
+        _ => Ranges::Full,
+    }
+}
+
+/// Sorts `ranges` and merges those that overlap or touch, as RFC 9110
+/// §14.2 suggests.
+///
+/// No byte is then served twice, so the ranges never add up to more than the
+/// representation, however many times a client repeats them.
+fn coalesce(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Parses a `Range` header into the satisfiable ranges it lists.
+///
+/// Returns `None` when the header is malformed or uses another unit than
+/// bytes, in which case it is ignored.
+fn parse(header: &str, len: u64) -> Option<Vec<Range<u64>>> {
+    let (unit, specs) = header.split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let mut ranges = Vec::new();
+    for spec in specs.split(',').map(str::trim).filter(|spec| !spec.is_empty()) {
+        let (first, last) = spec.split_once('-')?;
+        let range = if first.is_empty() {
+            // The last `suffix` bytes
+            let suffix: u64 = parse_number(last)?;
+            len.saturating_sub(suffix)..len
+        } else {
+            let first = parse_number(first)?;
+            let end = match last {
+                "" => len,
+                last => {
+                    let last = parse_number(last)?;
+                    if last < first {
+                        return None;
+                    }
+                    last.saturating_add(1).min(len)
+                }
+            };
+            first..end
+        };
+        if range.start < range.end {
+            ranges.push(range);
+        }
+    }
+    Some(ranges)
+}
+
+fn parse_number(digits: &str) -> Option<u64> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Formats a `Content-Range` value for `range` of a representation of `len`
+/// bytes.
+pub(crate) fn content_range(range: &Range<u64>, len: u64) -> String {
+    format!("bytes {}-{}/{}", range.start, range.end - 1, len)
+}
+
+/// Formats the `Content-Range` value of a 416 response.
+pub(crate) fn unsatisfied_range(len: u64) -> String {
+    format!("bytes */{}", len)
+}
+
+/// Generates a boundary for a `multipart/byteranges` body.
+pub(crate) fn boundary() -> String {
+    let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+    format!("ruffus-{:016x}", random)
+}
+
+/// Formats the delimiter and headers preceding the part for `range`.
+pub(crate) fn part_header(boundary: &str, content_type: Option<&str>, range: &Range<u64>, len: u64) -> String {
+    let mut header = format!("\r\n--{}\r\n", boundary);
+    if let Some(content_type) = content_type {
+        header.push_str(&format!("Content-Type: {}\r\n", content_type));
+    }
+    header.push_str(&format!("Content-Range: {}\r\n\r\n", content_range(range, len)));
+    header
+}
+
+/// Formats the delimiter closing a `multipart/byteranges` body.
+pub(crate) fn closing(boundary: &str) -> String {
+    format!("\r\n--{}--\r\n", boundary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_parse() {
+        assert_eq!(parse("bytes=0-4", 10), Some(vec![0..5]));
+        assert_eq!(parse("bytes=5-", 10), Some(vec![5..10]));
+        assert_eq!(parse("bytes=-3", 10), Some(vec![7..10]));
+        assert_eq!(parse("bytes=-30, 8-100", 10), Some(vec![0..10, 8..10]));
+        assert_eq!(parse("Bytes = 1-2,,4-4", 10), Some(vec![1..3, 4..5]));
+        assert_eq!(parse("bytes=10-, -0", 10), Some(vec![]));
+        assert_eq!(parse("bytes=5-4", 10), None);
+        assert_eq!(parse("bytes=15-12", 10), None);
+        assert_eq!(parse("bytes=a-4", 10), None);
+        assert_eq!(parse("bytes=+1-4", 10), None);
+        assert_eq!(parse("items=0-4", 10), None);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_coalesce() {
+        assert_eq!(coalesce(vec![6..8, 0..2]), vec![0..2, 6..8]);
+        assert_eq!(coalesce(vec![0..5, 3..7, 7..9]), vec![0..9]);
+        assert_eq!(coalesce(vec![0..10; 64]), vec![0..10]);
+        assert_eq!(coalesce(vec![2..3, 0..10]), vec![0..10]);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_repeated_ranges_are_served_once() {
+        let mut headers = HeaderMap::new();
+        let range = format!("bytes={}", vec!["0-"; MAX_RANGES].join(","));
+        headers.insert(header::RANGE, range.parse().unwrap());
+        assert_eq!(requested(&headers, &HeaderMap::new(), 10), Ranges::Partial(vec![0..10]));
+    }
+}
@@ -0,0 +1,483 @@
+//! Rate limiting
+//!
+//! The [`RateLimit`] middleware counts the requests of each client against a
+//! [`Quota`] and rejects the excess with `429 Too Many Requests`. Clients are
+//! told where they stand through the `RateLimit-Limit`, `RateLimit-Remaining`,
+//! `RateLimit-Reset` and `RateLimit-Policy` headers, and rejected requests
+//! carry `Retry-After`.
+//!
+//! Two algorithms are available:
+//!
+//! - a token bucket, which allows bursts of up to the quota's limit and
+//!   refills steadily over its period
+//! - a sliding window, which counts the requests of the last period,
+//!   weighting the previous window by how much of it still overlaps
+//!
+//! Counters live in a [`RateLimitStore`], by default a [`MemoryStore`] local
+//! to the process. Implement the trait to share counters between instances,
+//! e.g. in Redis.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::rate_limit::{Quota, RateLimit};
+//! use ruffus::{App, Router};
+//! use std::sync::Arc;
+//!
+//! let mut app = App::new();
+//!
+//! // 100 requests a minute per client IP
+//! app.use_middleware(Arc::new(RateLimit::sliding_window(Quota::per_minute(100))));
+//!
+//! // Bursts of 10 logins, then one every 6 seconds, per account
+//! let mut auth = Router::new("/auth");
+//! auth.use_middleware(Arc::new(
+//!     RateLimit::token_bucket(Quota::per_minute(10)).by_header("x-account"),
+//! ));
+//! app.mount("", auth);
+//! ```
+
+use async_trait::async_trait;
+use http::StatusCode;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::listener::ConnectInfo;
+use crate::{Error, Middleware, Next, Request, Response, Result};
+
+/// How often a shard of a `MemoryStore` drops the counters of idle clients
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default number of shards of a `MemoryStore`
+const DEFAULT_SHARDS: usize = 64;
+
+/// Key of the bucket shared by requests whose client IP is unknown
+const UNKNOWN_CLIENT: &str = "unknown";
+
+/// How many requests a client may make in a period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    limit: u32,
+    period: Duration,
+}
+
+impl Quota {
+    /// Allows `limit` requests every `period`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` or `period` is zero.
+    pub fn new(limit: u32, period: Duration) -> Self {
+        assert!(limit > 0, "a quota must allow at least one request");
+        assert!(!period.is_zero(), "a quota's period can't be zero");
+        Self { limit, period }
+    }
+
+    /// Allows `limit` requests a second.
+    pub fn per_second(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(1))
+    }
+
+    /// Allows `limit` requests a minute.
+    pub fn per_minute(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60))
+    }
+
+    /// Allows `limit` requests an hour.
+    pub fn per_hour(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(3600))
+    }
+
+    /// Returns the number of requests allowed per period.
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Returns the period.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+/// How requests are counted against a [`Quota`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// A bucket holding up to `limit` tokens, refilled at `limit` tokens per
+    /// period. Each request takes a token.
+    TokenBucket,
+    /// At most `limit` requests in any period, estimated from the counts of
+    /// the current and previous windows.
+    SlidingWindow,
+}
+
+/// The outcome of counting a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    /// Whether the request is allowed
+    pub allowed: bool,
+    /// How many more requests are allowed right now
+    pub remaining: u32,
+    /// How long until the client's full quota is available again
+    pub reset: Duration,
+    /// For rejected requests, how long until a request would be allowed
+    pub retry_after: Option<Duration>,
+}
+
+/// Storage for the counters of a [`RateLimit`]
+///
+/// A store runs the algorithm itself, so that a shared store such as Redis
+/// can update a counter atomically, e.g. with a script.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync + 'static {
+    /// Counts a request of the client identified by `key`.
+    ///
+    /// Rejected requests must not be counted.
+    async fn hit(&self, key: &str, algorithm: Algorithm, quota: Quota) -> Result<Decision>;
+}
+
+/// A [`RateLimitStore`] keeping counters in memory
+///
+/// Counters are spread over shards, each behind its own lock, so that
+/// concurrent requests rarely contend. Counters of clients that have been
+/// idle for long enough to get their full quota back are dropped.
+pub struct MemoryStore {
+    shards: Box<[Mutex<Shard>]>,
+    hasher: std::collections::hash_map::RandomState,
+}
+
+struct Shard {
+    entries: HashMap<String, Entry>,
+    last_sweep: Instant,
+}
+
+struct Entry {
+    state: State,
+    /// When the state becomes the same as having no entry
+    idle_at: Instant,
+}
+
+enum State {
+    Bucket {
+        tokens: f64,
+        updated: Instant,
+    },
+    Window {
+        start: Instant,
+        current: u32,
+        previous: u32,
+    },
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// Creates an empty store with `shards` shards, at least one.
+    pub fn with_shards(shards: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            shards: (0..shards.max(1))
+                .map(|_| {
+                    Mutex::new(Shard {
+                        entries: HashMap::new(),
+                        last_sweep: now,
+                    })
+                })
+                .collect(),
+            hasher: Default::default(),
+        }
+    }
+
+    fn shard(&self, key: &str) -> &Mutex<Shard> {
+        &self.shards[self.hasher.hash_one(key) as usize % self.shards.len()]
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MemoryStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryStore")
+            .field("shards", &self.shards.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn hit(&self, key: &str, algorithm: Algorithm, quota: Quota) -> Result<Decision> {
+        let now = Instant::now();
+        let mut shard = self
+            .shard(key)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if now.duration_since(shard.last_sweep) >= SWEEP_INTERVAL {
+            shard.entries.retain(|_, entry| entry.idle_at > now);
+            shard.last_sweep = now;
+        }
+
+        let entry = shard
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                state: match algorithm {
+                    Algorithm::TokenBucket => State::Bucket {
+                        tokens: quota.limit as f64,
+                        updated: now,
+                    },
+                    Algorithm::SlidingWindow => State::Window {
+                        start: now,
+                        current: 0,
+                        previous: 0,
+                    },
+                },
+                idle_at: now,
+            });
+        let (decision, idle_at) = match &mut entry.state {
+            State::Bucket { tokens, updated } => token_bucket(tokens, updated, quota, now),
+            State::Window {
+                start,
+                current,
+                previous,
+            } => sliding_window(start, current, previous, quota, now),
+        };
+        entry.idle_at = idle_at;
+        Ok(decision)
+    }
+}
+
+/// Takes a token from a bucket last refilled at `updated`, returning the
+/// decision and when the bucket will be full.
+fn token_bucket(
+    tokens: &mut f64,
+    updated: &mut Instant,
+    quota: Quota,
+    now: Instant,
+) -> (Decision, Instant) {
+    let capacity = quota.limit as f64;
+    let rate = capacity / quota.period.as_secs_f64();
+    *tokens = (*tokens + now.duration_since(*updated).as_secs_f64() * rate).min(capacity);
+    *updated = now;
+
+    let allowed = *tokens >= 1.0;
+    let retry_after = if allowed {
+        *tokens -= 1.0;
+        None
+    } else {
+        Some(Duration::from_secs_f64((1.0 - *tokens) / rate))
+    };
+    let reset = Duration::from_secs_f64((capacity - *tokens) / rate);
+    let decision = Decision {
+        allowed,
+        remaining: tokens.floor() as u32,
+        reset,
+        retry_after,
+    };
+    (decision, now + reset)
+}
+
+/// Counts a request in the window starting at `start`, returning the
+/// decision and when both windows will have no weight left.
+fn sliding_window(
+    start: &mut Instant,
+    current: &mut u32,
+    previous: &mut u32,
+    quota: Quota,
+    now: Instant,
+) -> (Decision, Instant) {
+    let period = quota.period;
+    let elapsed = now.duration_since(*start).as_nanos();
+    let elapsed_windows = elapsed / period.as_nanos();
+    if elapsed_windows > 0 {
+        *previous = if elapsed_windows == 1 { *current } else { 0 };
+        *current = 0;
+        *start = now - Duration::from_nanos((elapsed % period.as_nanos()) as u64);
+    }
+
+    // The previous window counts for the part of it still within a period
+    let limit = quota.limit as f64;
+    let weight = |at: Instant| 1.0 - at.duration_since(*start).as_secs_f64() / period.as_secs_f64();
+    let estimate = |previous: u32, current: u32| previous as f64 * weight(now) + current as f64;
+
+    let allowed = estimate(*previous, *current) + 1.0 <= limit;
+    let retry_after = if allowed {
+        *current += 1;
+        None
+    } else if *current < quota.limit {
+        // Once enough of the previous window has slid out
+        let needed = 1.0 - (limit - 1.0 - *current as f64) / *previous as f64;
+        Some((*start + period.mul_f64(needed.clamp(0.0, 1.0))).duration_since(now))
+    } else {
+        // Once enough of this window has slid out, in the next one
+        let needed = 1.0 - (limit - 1.0) / *current as f64;
+        Some((*start + period + period.mul_f64(needed.clamp(0.0, 1.0))).duration_since(now))
+    };
+
+    let idle_at = if *current > 0 {
+        *start + period * 2
+    } else {
+        *start + period
+    };
+    let decision = Decision {
+        allowed,
+        remaining: (limit - estimate(*previous, *current)).floor().max(0.0) as u32,
+        reset: idle_at.duration_since(now),
+        retry_after,
+    };
+    (decision, idle_at)
+}
+
+type KeyFn = Arc<dyn Fn(&Request) -> Option<String> + Send + Sync>;
+
+/// Middleware limiting how often each client can make requests
+///
+/// Clients are told apart by their IP address unless another key is set
+/// with [`by_header`](Self::by_header), [`by_param`](Self::by_param) or
+/// [`by_key`](Self::by_key). Requests whose IP address is unknown, because
+/// the app isn't served from a listener that provides [`ConnectInfo`], all
+/// share one bucket rather than going unlimited, so a misconfigured server
+/// fails closed. With another key, requests without one, such as those
+/// missing the header, aren't limited.
+///
+/// Allowed requests get the `RateLimit-*` headers on their response.
+/// Rejected requests get a `429 Too Many Requests` response with the JSON
+/// body of [`Error::Custom`] and a `Retry-After` header. It is returned as a
+/// response rather than an error, so that the headers reach the client.
+#[derive(Clone)]
+pub struct RateLimit {
+    algorithm: Algorithm,
+    quota: Quota,
+    key: KeyFn,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimit {
+    /// Limits requests with a token bucket per client.
+    pub fn token_bucket(quota: Quota) -> Self {
+        Self::new(Algorithm::TokenBucket, quota)
+    }
+
+    /// Limits requests with a sliding window per client.
+    pub fn sliding_window(quota: Quota) -> Self {
+        Self::new(Algorithm::SlidingWindow, quota)
+    }
+
+    /// Limits requests with `algorithm`, keeping counters in a new
+    /// [`MemoryStore`].
+    ///
+    /// Clients are told apart by IP address, and requests without a
+    /// [`ConnectInfo<SocketAddr>`](ConnectInfo) share a single bucket.
+    pub fn new(algorithm: Algorithm, quota: Quota) -> Self {
+        Self {
+            algorithm,
+            quota,
+            key: Arc::new(|req: &Request| {
+                let ip = req
+                    .extensions()
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|ConnectInfo(addr)| addr.ip().to_string());
+                Some(ip.unwrap_or_else(|| UNKNOWN_CLIENT.to_string()))
+            }),
+            store: Arc::new(MemoryStore::new()),
+        }
+    }
+
+    /// Tells clients apart by the value of a request header, such as an API
+    /// key.
+    pub fn by_header(self, name: &str) -> Self {
+        let name = name.to_string();
+        self.by_key(move |req| {
+            let value = req.headers().get(name.as_str())?;
+            Some(String::from_utf8_lossy(value.as_bytes()).into_owned())
+        })
+    }
+
+    /// Tells clients apart by a path parameter of the matched route, such as
+    /// a tenant id.
+    pub fn by_param(self, name: &str) -> Self {
+        let name = name.to_string();
+        self.by_key(move |req| req.param(&name).map(str::to_string))
+    }
+
+    /// Tells clients apart by the key `key` returns. Requests for which it
+    /// returns `None` aren't limited.
+    pub fn by_key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Request) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key = Arc::new(key);
+        self
+    }
+
+    /// Keeps counters in `store`, which may be shared with other limits as
+    /// long as their keys don't collide.
+    pub fn store(mut self, store: impl RateLimitStore) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    fn headers(&self, response: Response, decision: &Decision) -> Response {
+        response
+            .header("RateLimit-Limit", &self.quota.limit.to_string())
+            .header("RateLimit-Remaining", &decision.remaining.to_string())
+            .header("RateLimit-Reset", &ceil_secs(decision.reset).to_string())
+            .header(
+                "RateLimit-Policy",
+                &format!("{};w={}", self.quota.limit, ceil_secs(self.quota.period)),
+            )
+    }
+}
+
+impl fmt::Debug for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimit")
+            .field("algorithm", &self.algorithm)
+            .field("quota", &self.quota)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Middleware for RateLimit {
+    async fn handle(&self, req: Request, next: Next) -> Result<Response> {
+        let key = match (self.key)(&req) {
+            Some(key) => key,
+            None => return next.run(req).await,
+        };
+        let decision = self.store.hit(&key, self.algorithm, self.quota).await?;
+
+        if !decision.allowed {
+            let retry_after = decision.retry_after.unwrap_or(decision.reset);
+            let error = Error::Custom {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                message: format!(
+                    "Rate limit exceeded, retry in {} seconds",
+                    ceil_secs(retry_after)
+                ),
+            };
+            let response = error
+                .into_response()
+                .header("Retry-After", &ceil_secs(retry_after).to_string());
+            return Ok(self.headers(response, &decision));
+        }
+
+        let response = next.run(req).await?;
+        Ok(self.headers(response, &decision))
+    }
+}
+
+/// Whole seconds in `duration`, rounded up, as rate limit headers use
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
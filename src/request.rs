@@ -3,10 +3,15 @@
 //! This module provides the [`Request`] type which represents an incoming HTTP request.
 
 use bytes::Bytes;
-use http::{HeaderMap, Method, Uri};
-use http_body_util::BodyExt;
+use http::{HeaderMap, Method, StatusCode, Uri};
 use serde::de::DeserializeOwned;
 use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::body::Body;
+
+/// The default limit for buffering request bodies: 2 MiB
+pub const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
 
 /// Type for storing request extensions.
 ///
@@ -18,7 +23,7 @@ pub type Extensions = http::Extensions;
 /// The `Request` type provides access to:
 /// - HTTP method and URI
 /// - Headers
-/// - Request body
+/// - Request body, read lazily from the connection
 /// - Path parameters (extracted from the route pattern)
 /// - Query parameters (from the URL query string)
 /// - Extensions (for storing custom data)
@@ -40,7 +45,8 @@ pub struct Request {
     method: Method,
     uri: Uri,
     headers: HeaderMap,
-    body: Bytes,
+    body: Body,
+    body_limit: Option<usize>,
     params: HashMap<String, String>,
     query: HashMap<String, String>,
     extensions: Extensions,
@@ -55,7 +61,7 @@ impl Request {
         method: Method,
         uri: Uri,
         headers: HeaderMap,
-        body: Bytes,
+        body: impl Into<Body>,
     ) -> Self {
         // Parse query parameters from URI
         let query = Self::parse_query_params(&uri);
@@ -64,7 +70,8 @@ impl Request {
             method,
             uri,
             headers,
-            body,
+            body: body.into(),
+            body_limit: Some(DEFAULT_BODY_LIMIT),
             params: HashMap::new(),
             query,
             extensions: Extensions::new(),
@@ -117,6 +124,12 @@ impl Request {
         &self.headers
     }
 
+    /// Returns the request headers mutably, for middleware that rewrites
+    /// requests.
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
     /// Returns a path parameter by name.
     ///
     /// Path parameters are extracted from the route pattern (e.g., `/users/:id`).
@@ -128,7 +141,7 @@ impl Request {
     /// # let mut app = App::new();
     /// app.get("/users/:id", |req: Request| async move {
     ///     let id = req.param("id").unwrap_or("unknown");
-    ///     Ok(Response::text(format!("User ID: {}", id)))
+    ///     Response::text(format!("User ID: {}", id))
     /// });
     /// ```
     pub fn param(&self, name: &str) -> Option<&str> {
@@ -148,7 +161,7 @@ impl Request {
     /// app.get("/search", |req: Request| async move {
     ///     let query = req.query("q").unwrap_or("");
     ///     let limit = req.query("limit").unwrap_or("20");
-    ///     Ok(Response::text(format!("Search: {}, Limit: {}", query, limit)))
+    ///     Response::text(format!("Search: {}, Limit: {}", query, limit))
     /// });
     /// ```
     pub fn query(&self, name: &str) -> Option<&str> {
@@ -196,10 +209,12 @@ impl Request {
 
     /// Deserializes the request body as JSON.
     ///
+    /// The body is buffered first, see [`body`](Self::body).
+    ///
     /// # Errors
     ///
     /// Returns an error if the body is not valid JSON or cannot be deserialized
-    /// into the target type.
+    /// into the target type, or if it can't be buffered.
     ///
     /// # Examples
     ///
@@ -216,28 +231,103 @@ impl Request {
     /// # let mut app = App::new();
     /// app.post("/users", |mut req: Request| async move {
     ///     let user: User = req.json().await?;
-    ///     Ok(Response::text(format!("Created user: {}", user.name)))
+    ///     Ok::<_, ruffus::Error>(Response::text(format!("Created user: {}", user.name)))
     /// });
     /// ```
     pub async fn json<T: DeserializeOwned>(&mut self) -> crate::Result<T> {
-        let body_bytes = &self.body;
-        serde_json::from_slice(body_bytes)
-            .map_err(|e| crate::Error::JsonParseError(e))
+        let body_bytes = self.body().await?;
+        serde_json::from_slice(&body_bytes)
+            .map_err(crate::Error::JsonParseError)
     }
 
-    /// Returns the request body as bytes.
+    /// Reads the whole request body into memory and returns it.
+    ///
+    /// The buffered body is kept, so calling this again returns the same
+    /// bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `413 Payload Too Large` if the body is larger than the
+    /// [body limit](Self::set_body_limit), `408 Request Timeout` if the
+    /// server's request body timeout expires, and `400 Bad Request` if the
+    /// body can't be read from the connection.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # use ruffus::Request;
-    /// # async fn example(req: Request) {
-    /// let body_bytes = req.body();
+    /// # async fn example(mut req: Request) -> ruffus::Result<()> {
+    /// let body_bytes = req.body().await?;
     /// println!("Body size: {} bytes", body_bytes.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn body(&mut self) -> crate::Result<Bytes> {
+        // Refuse a declared oversized body without reading any of it
+        let declared = self
+            .headers
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if let (Some(declared), Some(limit)) = (declared, self.body_limit) {
+            if declared > limit as u64 {
+                return Err(crate::Error::Custom {
+                    status: StatusCode::PAYLOAD_TOO_LARGE,
+                    message: format!("Request body is larger than {} bytes", limit),
+                });
+            }
+        }
+
+        let bytes = self.take_body().to_bytes(self.body_limit).await?;
+        self.body = Body::from(bytes.clone());
+        Ok(bytes)
+    }
+
+    /// Takes the request body as a stream, leaving an empty body in its place.
+    ///
+    /// The returned [`Body`] is a `Stream` of chunks and implements
+    /// `AsyncRead`. The body limit doesn't apply to it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use ruffus::Request;
+    /// # async fn example(mut req: Request) -> std::io::Result<()> {
+    /// let mut body = req.take_body();
+    /// let mut file = tokio::fs::File::create("upload.bin").await?;
+    /// tokio::io::copy(&mut body, &mut file).await?;
+    /// # Ok(())
     /// # }
     /// ```
-    pub fn body(&self) -> &Bytes {
-        &self.body
+    pub fn take_body(&mut self) -> Body {
+        std::mem::take(&mut self.body)
+    }
+
+    /// Replaces the request body, such as with a decoded version of the body
+    /// taken with [`take_body`](Self::take_body).
+    pub fn set_body(&mut self, body: impl Into<Body>) {
+        self.body = body.into();
+    }
+
+    /// Returns the largest body in bytes that [`body`](Self::body),
+    /// [`json`](Self::json) and body extractors will buffer.
+    pub fn body_limit(&self) -> Option<usize> {
+        self.body_limit
+    }
+
+    /// Sets the largest body in bytes that [`body`](Self::body),
+    /// [`json`](Self::json) and body extractors will buffer, or removes the
+    /// limit with `None`.
+    ///
+    /// The limit defaults to [`App::body_limit`](crate::App::body_limit).
+    /// Middleware can raise it for routes that accept large uploads.
+    pub fn set_body_limit(&mut self, limit: Option<usize>) {
+        self.body_limit = limit;
+    }
+
+    /// Fails reads of the body once `timeout` has passed.
+    pub(crate) fn set_body_timeout(&mut self, timeout: Duration) {
+        self.body = self.take_body().with_timeout(timeout);
     }
 
     /// Returns mutable access to request extensions.
@@ -282,6 +372,9 @@ impl Request {
 }
 
 /// Convert from hyper::Request
+///
+/// The body isn't read: it streams from the connection as the handler
+/// consumes it. Extensions of the hyper request are kept.
 impl<B> TryFrom<hyper::Request<B>> for Request
 where
     B: hyper::body::Body + Send + 'static,
@@ -291,38 +384,31 @@ where
     type Error = crate::Error;
 
     fn try_from(req: hyper::Request<B>) -> Result<Self, Self::Error> {
-        let (_parts, _body) = req.into_parts();
-        
-        // We need to collect the body asynchronously, but this is a sync trait
-        // This will be handled by the async conversion function below
-        Err(crate::Error::InternalServerError(
-            "Use from_hyper_async instead".to_string()
-        ))
+        let (parts, body) = req.into_parts();
+
+        let mut request = Request::new(
+            parts.method,
+            parts.uri,
+            parts.headers,
+            Body::from_http_body(body),
+        );
+        // Keeps hyper's own extensions, such as the handle for upgrades
+        request.extensions = parts.extensions;
+        Ok(request)
     }
 }
 
 impl Request {
     /// Async conversion from hyper::Request
+    ///
+    /// Kept for compatibility: the body is no longer read here, so this is
+    /// the same as `Request::try_from`.
     pub async fn from_hyper<B>(req: hyper::Request<B>) -> crate::Result<Self>
     where
         B: hyper::body::Body + Send + 'static,
         B::Data: Send,
         B::Error: std::error::Error + Send + Sync + 'static,
     {
-        let (parts, body) = req.into_parts();
-        
-        // Collect the body
-        let body_bytes = body
-            .collect()
-            .await
-            .map_err(|e| crate::Error::InternalServerError(e.to_string()))?
-            .to_bytes();
-        
-        Ok(Request::new(
-            parts.method,
-            parts.uri,
-            parts.headers,
-            body_bytes,
-        ))
+        Request::try_from(req)
     }
 }
@@ -1,15 +1,41 @@
 //! HTTP Response type
 //!
-//! This module provides the [`Response`] type for building HTTP responses.
+//! This module provides the [`Response`] type for building HTTP responses,
+//! and the [`IntoResponse`] trait for values that handlers can return.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::{App, Json, Path};
+//! use http::StatusCode;
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct User {
+//!     id: u64,
+//! }
+//!
+//! let mut app = App::new();
+//! app.get("/hello", || async { "Hello!" });
+//! app.post("/users/:id", |Path(id): Path<u64>| async move {
+//!     (StatusCode::CREATED, Json(User { id }))
+//! });
+//! ```
 
 use bytes::Bytes;
+use futures_core::Stream;
 use http::{HeaderMap, StatusCode};
 use serde::Serialize;
+use tokio::io::AsyncRead;
+
+use crate::range::{self, Ranges};
+use crate::{Body, Error, Json, Result};
 
 /// Represents an outgoing HTTP response.
 ///
 /// The `Response` type provides a builder-style API for constructing HTTP responses
-/// with status codes, headers, and bodies.
+/// with status codes, headers, and bodies. Bodies are either buffered in memory
+/// or streamed to the client, see [`Response::stream`].
 ///
 /// # Examples
 ///
@@ -33,7 +59,7 @@ use serde::Serialize;
 pub struct Response {
     status: StatusCode,
     headers: HeaderMap,
-    body: Bytes,
+    body: Body,
 }
 
 impl Response {
@@ -50,7 +76,7 @@ impl Response {
         Self {
             status: StatusCode::OK,
             headers: HeaderMap::new(),
-            body: Bytes::new(),
+            body: Body::empty(),
         }
     }
 
@@ -105,7 +131,7 @@ impl Response {
         Self {
             status: StatusCode::OK,
             headers: HeaderMap::new(),
-            body: Bytes::from(text),
+            body: Body::from(text),
         }
     }
 
@@ -139,7 +165,7 @@ impl Response {
         Ok(Self {
             status: StatusCode::OK,
             headers: HeaderMap::new(),
-            body: Bytes::from(json_string),
+            body: Body::from(json_string),
         }
         .header("Content-Type", "application/json"))
     }
@@ -155,7 +181,7 @@ impl Response {
     ///     .body("Hello, World!".to_string());
     /// ```
     pub fn body(mut self, body: String) -> Self {
-        self.body = Bytes::from(body);
+        self.body = Body::from(body);
         self
     }
 
@@ -171,10 +197,168 @@ impl Response {
     ///     .body_bytes(Bytes::from("Hello"));
     /// ```
     pub fn body_bytes(mut self, body: Bytes) -> Self {
+        self.body = Body::from(body);
+        self
+    }
+
+    /// Creates a response with status 200 OK that streams its body.
+    ///
+    /// Chunks are sent to the client as the stream yields them, using chunked
+    /// transfer encoding over HTTP/1.1 unless a `Content-Length` header is
+    /// set. If the stream fails, the connection is aborted, since the status
+    /// line has already been sent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bytes::Bytes;
+    /// use ruffus::Response;
+    ///
+    /// let rows = (1..=3).map(|i| Ok::<_, std::io::Error>(Bytes::from(format!("row {}\n", i))));
+    /// let response = Response::stream(futures_util::stream::iter(rows))
+    ///     .header("Content-Type", "text/csv");
+    /// ```
+    pub fn stream<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        Self::new().body_stream(Body::from_stream(stream))
+    }
+
+    /// Creates a response with status 200 OK that streams its body from an
+    /// [`AsyncRead`], such as a file or a proxied connection.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ruffus::{App, Response};
+    ///
+    /// let mut app = App::new();
+    /// app.get("/export", || async {
+    ///     let file = tokio::fs::File::open("export.csv")
+    ///         .await
+    ///         .map_err(|e| ruffus::Error::InternalServerError(e.to_string()))?;
+    ///     Ok::<_, ruffus::Error>(Response::reader(file).header("Content-Type", "text/csv"))
+    /// });
+    /// ```
+    pub fn reader<R>(reader: R) -> Self
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        Self::new().body_stream(Body::from_reader(reader))
+    }
+
+    /// Sets the response body to a [`Body`], which may be streamed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruffus::{Body, Response};
+    ///
+    /// let response = Response::new().body_stream(Body::from("Hello"));
+    /// ```
+    pub fn body_stream(mut self, body: Body) -> Self {
         self.body = body;
         self
     }
 
+    /// Answers the `Range` header of a request from this response's buffered
+    /// body.
+    ///
+    /// A `200 OK` response becomes `206 Partial Content` with the requested
+    /// bytes, as a `multipart/byteranges` body when several ranges are
+    /// requested, or `416 Range Not Satisfiable` when no range overlaps the
+    /// body. Overlapping and adjacent ranges are merged and sent in
+    /// ascending order. `If-Range` is compared with the response's `ETag` or
+    /// `Last-Modified` header, so set those first. The response advertises
+    /// `Accept-Ranges: bytes`. Other statuses and streamed bodies are left
+    /// unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use http::{HeaderMap, StatusCode};
+    /// use ruffus::Response;
+    ///
+    /// let mut request_headers = HeaderMap::new();
+    /// request_headers.insert("range", "bytes=0-4".parse().unwrap());
+    ///
+    /// let response = Response::text("Hello, World!".to_string()).ranged(&request_headers);
+    /// assert_eq!(response.get_status(), StatusCode::PARTIAL_CONTENT);
+    /// assert_eq!(response.get_headers()["content-range"], "bytes 0-4/13");
+    /// assert_eq!(response.buffered_body().unwrap(), "Hello");
+    /// ```
+    pub fn ranged(mut self, request_headers: &HeaderMap) -> Self {
+        use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+
+        let bytes = match (self.status, self.body.as_bytes()) {
+            (StatusCode::OK, Some(bytes)) => bytes.clone(),
+            _ => return self,
+        };
+        self = self.header("Accept-Ranges", "bytes");
+
+        let len = bytes.len() as u64;
+        let ranges = match range::requested(request_headers, &self.headers, len) {
+            Ranges::Full => return self,
+            Ranges::Unsatisfiable => {
+                self.headers.remove(CONTENT_TYPE);
+                self.headers.remove(CONTENT_LENGTH);
+                return self
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header("Content-Range", &range::unsatisfied_range(len))
+                    .body_bytes(Bytes::new());
+            }
+            Ranges::Partial(ranges) => ranges,
+        };
+        self.headers.remove(CONTENT_LENGTH);
+        let slice = |range: &std::ops::Range<u64>| bytes.slice(range.start as usize..range.end as usize);
+
+        if let [range] = ranges.as_slice() {
+            return self
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header("Content-Range", &range::content_range(range, len))
+                .body_bytes(slice(range));
+        }
+
+        let boundary = range::boundary();
+        let content_type = self.headers.get(CONTENT_TYPE).and_then(|value| value.to_str().ok());
+        let mut body = Vec::new();
+        for range in &ranges {
+            body.extend_from_slice(range::part_header(&boundary, content_type, range, len).as_bytes());
+            body.extend_from_slice(&slice(range));
+        }
+        body.extend_from_slice(range::closing(&boundary).as_bytes());
+
+        self.status(StatusCode::PARTIAL_CONTENT)
+            .header("Content-Type", &format!("multipart/byteranges; boundary={}", boundary))
+            .body_bytes(Bytes::from(body))
+    }
+
+    /// Adds the comma-separated header names in `names` to the `Vary`
+    /// header, skipping those it already lists.
+    pub(crate) fn vary(self, names: &str) -> Self {
+        let mut listed: Vec<String> = self
+            .headers
+            .get_all(http::header::VARY)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        if listed.iter().any(|name| name == "*") {
+            return self;
+        }
+        for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            if !listed.iter().any(|listed| listed.eq_ignore_ascii_case(name)) {
+                listed.push(name.to_string());
+            }
+        }
+        self.header("Vary", &listed.join(", "))
+    }
+
     /// Creates an HTML response with the appropriate Content-Type header.
     ///
     /// # Examples
@@ -188,7 +372,7 @@ impl Response {
         Self {
             status: StatusCode::OK,
             headers: HeaderMap::new(),
-            body: Bytes::from(html),
+            body: Body::from(html),
         }
         .header("Content-Type", "text/html; charset=utf-8")
     }
@@ -206,7 +390,7 @@ impl Response {
         Self {
             status: StatusCode::NOT_FOUND,
             headers: HeaderMap::new(),
-            body: Bytes::from("Not Found"),
+            body: Body::from("Not Found"),
         }
     }
 
@@ -223,7 +407,7 @@ impl Response {
         Self {
             status: StatusCode::BAD_REQUEST,
             headers: HeaderMap::new(),
-            body: Bytes::from(message),
+            body: Body::from(message),
         }
     }
 
@@ -240,7 +424,7 @@ impl Response {
         Self {
             status: StatusCode::INTERNAL_SERVER_ERROR,
             headers: HeaderMap::new(),
-            body: Bytes::from(message),
+            body: Body::from(message),
         }
     }
 
@@ -257,7 +441,7 @@ impl Response {
         Self {
             status: StatusCode::FOUND,
             headers: HeaderMap::new(),
-            body: Bytes::new(),
+            body: Body::empty(),
         }
         .header("Location", location)
     }
@@ -275,7 +459,7 @@ impl Response {
         Self {
             status: StatusCode::NO_CONTENT,
             headers: HeaderMap::new(),
-            body: Bytes::new(),
+            body: Body::empty(),
         }
     }
 
@@ -308,7 +492,8 @@ impl Response {
         &self.headers
     }
 
-    /// Returns the response body as bytes.
+    /// Returns the response body if it is buffered in memory, or `None` if
+    /// it is streamed.
     ///
     /// # Examples
     ///
@@ -316,10 +501,82 @@ impl Response {
     /// use ruffus::Response;
     ///
     /// let response = Response::text("Hello".to_string());
-    /// let body = response.get_body();
+    /// assert_eq!(response.buffered_body().unwrap(), "Hello");
     /// ```
+    pub fn buffered_body(&self) -> Option<&Bytes> {
+        self.body.as_bytes()
+    }
+
+    /// Returns the response body as bytes.
+    ///
+    /// Streamed bodies can't be borrowed as bytes, so this returns an empty
+    /// slice for them. Use [`buffered_body`](Self::buffered_body) to inspect a
+    /// body that may be streamed, or [`into_bytes`](Self::into_bytes) to read
+    /// any body.
+    #[deprecated(note = "use `buffered_body` or `into_bytes`, which handle streamed bodies")]
     pub fn get_body(&self) -> &Bytes {
-        &self.body
+        static EMPTY: Bytes = Bytes::from_static(b"");
+        self.body.as_bytes().unwrap_or(&EMPTY)
+    }
+
+    /// Takes the response body, buffered or streamed.
+    pub fn into_body(self) -> Body {
+        self.body
+    }
+
+    /// Splits the response into its status, headers and body, for middleware
+    /// that rewrites a response.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruffus::Response;
+    ///
+    /// let response = Response::text("Hello".to_string()).header("X-Internal", "1");
+    /// let (status, mut headers, body) = response.into_parts();
+    /// headers.remove("x-internal");
+    /// let response = Response::from_parts(status, headers, body);
+    /// assert!(response.get_headers().is_empty());
+    /// ```
+    pub fn into_parts(self) -> (StatusCode, HeaderMap, Body) {
+        (self.status, self.headers, self.body)
+    }
+
+    /// Builds a response from a status, headers and body, such as those
+    /// returned by [`into_parts`](Self::into_parts).
+    pub fn from_parts(status: StatusCode, headers: HeaderMap, body: Body) -> Self {
+        Self { status, headers, body }
+    }
+
+    /// Reads the whole response body into memory, waiting for a streamed
+    /// body to finish.
+    ///
+    /// # Errors
+    ///
+    /// Returns a 500 error if the body stream fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ruffus::Result<()> {
+    /// use bytes::Bytes;
+    /// use ruffus::Response;
+    ///
+    /// let chunks = vec![Ok::<_, std::io::Error>(Bytes::from("Hello, ")), Ok(Bytes::from("World!"))];
+    /// let response = Response::stream(futures_util::stream::iter(chunks));
+    /// assert_eq!(response.into_bytes().await?, "Hello, World!");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn into_bytes(self) -> Result<Bytes> {
+        use http_body_util::BodyExt;
+
+        self.body
+            .collect()
+            .await
+            .map(|collected| collected.to_bytes())
+            .map_err(|e| Error::InternalServerError(format!("Failed to read response body: {}", e)))
     }
 }
 
@@ -329,7 +586,7 @@ impl Default for Response {
     }
 }
 
-impl From<Response> for hyper::Response<http_body_util::Full<Bytes>> {
+impl From<Response> for hyper::Response<Body> {
     fn from(response: Response) -> Self {
         let mut builder = hyper::Response::builder()
             .status(response.status);
@@ -340,7 +597,136 @@ impl From<Response> for hyper::Response<http_body_util::Full<Bytes>> {
         }
 
         builder
-            .body(http_body_util::Full::new(response.body))
+            .body(response.body)
             .expect("Failed to build hyper response")
     }
 }
+
+/// Trait for values that can be turned into a [`Response`].
+///
+/// Handlers may return any type implementing this trait. It is implemented for
+/// `Response`, `String`, `&'static str`, [`Json<T>`](crate::Json), `()`,
+/// `(StatusCode, T)`, `(StatusCode, HeaderMap, T)`, [`Error`] and
+/// `Result<T, E>` where both `T` and `E` implement it, so handlers can
+/// return their own error types.
+///
+/// Since the error type of a `Result` is generic, a closure returning a bare
+/// `Ok(...)` needs it spelled out, as in `Ok::<_, Error>(...)`, unless
+/// something else in the closure, such as `?` on a `ruffus::Result`,
+/// determines it.
+///
+/// [`Error`]s are passed back through the middleware chain as `Err` before
+/// they become responses, so middleware sees them the same way it always
+/// has. Other error types become responses right away.
+///
+/// # Examples
+///
+/// ```
+/// use ruffus::{IntoResponse, Response};
+/// use http::StatusCode;
+///
+/// struct Teapot;
+///
+/// impl IntoResponse for Teapot {
+///     fn into_response(self) -> Response {
+///         (StatusCode::IM_A_TEAPOT, "short and stout").into_response()
+///     }
+/// }
+///
+/// assert_eq!(Teapot.into_response().get_status(), StatusCode::IM_A_TEAPOT);
+/// ```
+pub trait IntoResponse {
+    /// Converts this value into a response.
+    fn into_response(self) -> Response;
+
+    /// Converts this value into a response, keeping errors as `Err` so they
+    /// flow through middleware like handler errors.
+    #[doc(hidden)]
+    fn into_result(self) -> Result<Response>
+    where
+        Self: Sized,
+    {
+        Ok(self.into_response())
+    }
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Response {
+        Response::text(self)
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> Response {
+        Response::new().body_bytes(Bytes::from_static(self.as_bytes()))
+    }
+}
+
+impl IntoResponse for () {
+    fn into_response(self) -> Response {
+        Response::new()
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        self.into_result().unwrap_or_else(Error::into_response)
+    }
+
+    fn into_result(self) -> Result<Response> {
+        Response::json(&self.0)
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for (StatusCode, T) {
+    fn into_response(self) -> Response {
+        self.into_result().unwrap_or_else(Error::into_response)
+    }
+
+    fn into_result(self) -> Result<Response> {
+        let (status, body) = self;
+        body.into_result().map(|response| response.status(status))
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for (StatusCode, HeaderMap, T) {
+    fn into_response(self) -> Response {
+        self.into_result().unwrap_or_else(Error::into_response)
+    }
+
+    fn into_result(self) -> Result<Response> {
+        let (status, headers, body) = self;
+        let mut response = body.into_result()?.status(status);
+        response.headers.extend(headers);
+        Ok(response)
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        Error::into_response(self)
+    }
+
+    fn into_result(self) -> Result<Response> {
+        Err(self)
+    }
+}
+
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for std::result::Result<T, E> {
+    fn into_response(self) -> Response {
+        self.into_result().unwrap_or_else(Error::into_response)
+    }
+
+    fn into_result(self) -> Result<Response> {
+        match self {
+            Ok(value) => value.into_result(),
+            Err(error) => error.into_result(),
+        }
+    }
+}
@@ -11,39 +11,276 @@
 //! let mut api = Router::new("/api");
 //!
 //! api.get("/users", |_req: Request| async {
-//!     Ok(Response::text("Users list".to_string()))
+//!     Response::text("Users list".to_string())
 //! });
 //!
 //! api.post("/users", |mut req: Request| async move {
-//!     Ok(Response::text("User created".to_string()))
+//!     Response::text("User created".to_string())
 //! });
 //! ```
 
-use crate::{Method, Middleware, Request, Response, Result};
+use crate::state::{FromRef, ProjectState};
+use crate::{Handler, Method, Middleware, Request, Response, Result};
 use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 
 /// Represents a segment in a path pattern.
 ///
-/// Path patterns are composed of static segments (literal strings) and
-/// dynamic segments (parameters that can match any value).
+/// Path patterns are composed of static segments (literal strings),
+/// dynamic segments (parameters that can match any value), constrained
+/// segments (parameters that only match values passing a [`Constraint`]),
+/// mixed segments combining literal text and parameters, and an optional
+/// trailing wildcard that matches the rest of the path. Parameters at the end
+/// of a pattern may be marked optional.
+///
+/// When several routes could match a path, static segments take priority over
+/// mixed ones, mixed over constrained, constrained over dynamic, and dynamic
+/// over wildcards.
 ///
 /// # Examples
 ///
 /// - `/users/123` contains two static segments: "users" and "123"
 /// - `/users/:id` contains one static segment "users" and one dynamic segment ":id"
+/// - `/users/:id<u64>` contains one static segment "users" and one constrained segment
+/// - `/report.:format` contains one mixed segment: "report." followed by ":format"
+/// - `/flights/:from-:to` contains one static segment "flights" and one mixed segment
+/// - `/posts/:page?` contains one static segment "posts" and one optional segment
+/// - `/files/*path` contains one static segment "files" and one wildcard "*path"
 #[derive(Debug, Clone, PartialEq)]
 pub enum Segment {
     /// Static path segment (e.g., "users")
     Static(String),
     /// Dynamic path parameter (e.g., ":id")
     Dynamic(String),
+    /// Dynamic path parameter with a constraint (e.g., ":id<u64>")
+    Constrained(String, Constraint),
+    /// Literal text and parameters sharing one segment (e.g., ":name.:ext")
+    ///
+    /// The parts are `Static`, `Dynamic` or `Constrained` segments, and two
+    /// parameters are always separated by literal text. Parameter names
+    /// consist of ASCII letters, digits and `_`, so any other character,
+    /// such as `-` or `.`, ends the name.
+    Mixed(Vec<Segment>),
+    /// Trailing parameter that may be left out (e.g., ":page?")
+    ///
+    /// Wraps a `Dynamic` or `Constrained` segment. When the path stops before
+    /// it, the parameter is simply absent.
+    Optional(Box<Segment>),
+    /// Wildcard capturing the rest of the path, including slashes (e.g., "*path")
+    ///
+    /// A bare `*` is captured under the name `*`.
+    Wildcard(String),
+}
+
+impl Segment {
+    /// Appends the names of the parameters this segment captures, in order.
+    fn param_names<'s>(&'s self, names: &mut Vec<&'s String>) {
+        match self {
+            Segment::Static(_) => {}
+            Segment::Dynamic(name) | Segment::Constrained(name, _) | Segment::Wildcard(name) => {
+                names.push(name)
+            }
+            Segment::Mixed(parts) => parts.iter().for_each(|part| part.param_names(names)),
+            Segment::Optional(inner) => inner.param_names(names),
+        }
+    }
+
+    /// Matches one `/`-delimited piece of a path, pushing the raw values of
+    /// captured parameters. Wildcards are handled by the caller.
+    fn matches<'p>(&self, value: &'p str, captures: &mut Vec<&'p str>) -> bool {
+        match self {
+            Segment::Static(literal) => literal == value,
+            Segment::Dynamic(_) => {
+                captures.push(value);
+                true
+            }
+            Segment::Constrained(_, constraint) => {
+                let allowed = constraint.allows(&decode_param(value));
+                if allowed {
+                    captures.push(value);
+                }
+                allowed
+            }
+            Segment::Mixed(parts) => {
+                let len = captures.len();
+                let matched = match_parts(parts, value, captures);
+                if !matched {
+                    captures.truncate(len);
+                }
+                matched
+            }
+            Segment::Optional(inner) => inner.matches(value, captures),
+            Segment::Wildcard(_) => unreachable!("wildcards are handled by the caller"),
+        }
+    }
+}
+
+/// Matches the parts of a mixed segment against `value`.
+///
+/// Each parameter is non-empty and takes as much as it can while still
+/// letting the remaining parts match, so `:name.:ext` splits `app.min.js`
+/// into `app.min` and `js`. Captures pushed by a failed attempt are left for
+/// the caller to truncate.
+fn match_parts<'p>(parts: &[Segment], value: &'p str, captures: &mut Vec<&'p str>) -> bool {
+    let (part, rest) = match parts.split_first() {
+        Some(split) => split,
+        None => return value.is_empty(),
+    };
+
+    if let Segment::Static(literal) = part {
+        return value
+            .strip_prefix(literal.as_str())
+            .is_some_and(|tail| match_parts(rest, tail, captures));
+    }
+
+    // Parameters are followed by literal text or end the segment
+    let ends: Vec<usize> = match rest.first() {
+        Some(Segment::Static(literal)) => (1..value.len())
+            .rev()
+            .filter(|&i| value.is_char_boundary(i) && value[i..].starts_with(literal.as_str()))
+            .collect(),
+        _ if value.is_empty() => Vec::new(),
+        _ => vec![value.len()],
+    };
+
+    let len = captures.len();
+    for end in ends {
+        if part.matches(&value[..end], captures) && match_parts(rest, &value[end..], captures) {
+            return true;
+        }
+        captures.truncate(len);
+    }
+    false
+}
+
+/// A constraint on the values a path parameter accepts.
+///
+/// Constraints are written in angle brackets after the parameter name. The
+/// names below select a built-in type; anything else is compiled as a regular
+/// expression that must match the whole (URL decoded) segment. Since patterns
+/// are split on `/` first, a constraint cannot contain a slash.
+///
+/// | Syntax | Accepts |
+/// |--------|---------|
+/// | `:id<u8>` … `:id<u64>`, `:id<usize>` | unsigned integers in range |
+/// | `:id<i8>` … `:id<i64>`, `:id<isize>` | signed integers in range |
+/// | `:id<uuid>` | hyphenated UUIDs, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8` |
+/// | `:slug<[a-z0-9-]+>` | values matching the regular expression |
+///
+/// # Examples
+///
+/// ```
+/// use ruffus::PathPattern;
+///
+/// let pattern = PathPattern::parse("/users/:id<u64>");
+/// assert!(pattern.matches("/users/42").is_some());
+/// assert!(pattern.matches("/users/alice").is_none());
+///
+/// let pattern = PathPattern::parse("/posts/:slug<[a-z0-9-]+>");
+/// assert!(pattern.matches("/posts/hello-world").is_some());
+/// assert!(pattern.matches("/posts/Hello").is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub enum Constraint {
+    /// Unsigned 8-bit integer (`u8`)
+    U8,
+    /// Unsigned 16-bit integer (`u16`)
+    U16,
+    /// Unsigned 32-bit integer (`u32`)
+    U32,
+    /// Unsigned 64-bit integer (`u64`)
+    U64,
+    /// Pointer-sized unsigned integer (`usize`)
+    Usize,
+    /// Signed 8-bit integer (`i8`)
+    I8,
+    /// Signed 16-bit integer (`i16`)
+    I16,
+    /// Signed 32-bit integer (`i32`)
+    I32,
+    /// Signed 64-bit integer (`i64`)
+    I64,
+    /// Pointer-sized signed integer (`isize`)
+    Isize,
+    /// Hyphenated UUID (`uuid`)
+    Uuid,
+    /// Regular expression anchored to the whole segment
+    Regex(regex::Regex),
+}
+
+impl Constraint {
+    /// Parses the text between the angle brackets of a parameter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the text is neither a built-in type nor a valid regular expression.
+    pub fn parse(spec: &str) -> Self {
+        match spec {
+            "u8" => Constraint::U8,
+            "u16" => Constraint::U16,
+            "u32" => Constraint::U32,
+            "u64" => Constraint::U64,
+            "usize" => Constraint::Usize,
+            "i8" => Constraint::I8,
+            "i16" => Constraint::I16,
+            "i32" => Constraint::I32,
+            "i64" => Constraint::I64,
+            "isize" => Constraint::Isize,
+            "uuid" => Constraint::Uuid,
+            _ => match regex::Regex::new(&format!("^(?:{})$", spec)) {
+                Ok(regex) => Constraint::Regex(regex),
+                Err(e) => panic!("invalid route parameter constraint `<{}>`: {}", spec, e),
+            },
+        }
+    }
+
+    /// Returns `true` if the (URL decoded) value satisfies this constraint.
+    pub fn allows(&self, value: &str) -> bool {
+        match self {
+            Constraint::U8 => value.parse::<u8>().is_ok(),
+            Constraint::U16 => value.parse::<u16>().is_ok(),
+            Constraint::U32 => value.parse::<u32>().is_ok(),
+            Constraint::U64 => value.parse::<u64>().is_ok(),
+            Constraint::Usize => value.parse::<usize>().is_ok(),
+            Constraint::I8 => value.parse::<i8>().is_ok(),
+            Constraint::I16 => value.parse::<i16>().is_ok(),
+            Constraint::I32 => value.parse::<i32>().is_ok(),
+            Constraint::I64 => value.parse::<i64>().is_ok(),
+            Constraint::Isize => value.parse::<isize>().is_ok(),
+            Constraint::Uuid => is_uuid(value),
+            Constraint::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+impl PartialEq for Constraint {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Constraint::Regex(a), Constraint::Regex(b)) => a.as_str() == b.as_str(),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+/// Checks for the hyphenated 8-4-4-4-12 hex form of a UUID.
+fn is_uuid(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() == 36
+        && bytes.iter().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => *b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
 }
 
 /// Represents a parsed path pattern with static and dynamic segments.
 ///
-/// Path patterns support dynamic parameters using the `:param` syntax.
+/// Path patterns support dynamic parameters using the `:param` syntax,
+/// constrained parameters using the `:param<constraint>` syntax, parameters
+/// mixed with literal text such as `report.:format`, optional trailing
+/// parameters using the `:param?` syntax, and a trailing wildcard using the
+/// `*name` syntax.
 ///
 /// # Examples
 ///
@@ -52,6 +289,18 @@ pub enum Segment {
 ///
 /// let pattern = PathPattern::parse("/users/:id/posts/:post_id");
 /// // This pattern will match paths like "/users/123/posts/456"
+///
+/// let files = PathPattern::parse("/files/*path");
+/// let params = files.matches("/files/docs/report.pdf").unwrap();
+/// assert_eq!(params.get("path"), Some(&"docs/report.pdf".to_string()));
+///
+/// let report = PathPattern::parse("/report.:format");
+/// let params = report.matches("/report.csv").unwrap();
+/// assert_eq!(params.get("format"), Some(&"csv".to_string()));
+///
+/// let posts = PathPattern::parse("/posts/:page?");
+/// assert_eq!(posts.matches("/posts").unwrap().get("page"), None);
+/// assert_eq!(posts.matches("/posts/2").unwrap().get("page"), Some(&"2".to_string()));
 /// ```
 #[derive(Debug, Clone)]
 pub struct PathPattern {
@@ -62,7 +311,24 @@ pub struct PathPattern {
 impl PathPattern {
     /// Parses a path pattern string into segments.
     ///
-    /// Segments starting with `:` are treated as dynamic parameters.
+    /// A `:` starts a parameter, whose name runs over ASCII letters, digits
+    /// and `_`. A parameter followed by `<...>` only matches values that pass
+    /// the [`Constraint`] inside the brackets. Parameters may share a segment
+    /// with literal text, as in `report.:format` or `:name.:ext`; inside a
+    /// segment, only a `:` followed by a letter or `_` starts one, so the
+    /// colon in `/time/12:30` is literal. A parameter
+    /// segment ending in `?` is optional, and only optional segments may
+    /// follow it. A final segment starting with `*` is a wildcard that matches
+    /// the rest of the path, including slashes, and also matches when nothing
+    /// is left.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a wildcard or optional segment is followed by a required
+    /// segment, if a segment other than a single parameter is marked
+    /// optional, if a parameter has an empty name, if two parameters in a
+    /// segment are not separated by literal text, or if a constraint is
+    /// unclosed or not a valid regular expression.
     ///
     /// # Examples
     ///
@@ -70,20 +336,31 @@ impl PathPattern {
     /// use ruffus::PathPattern;
     ///
     /// let pattern = PathPattern::parse("/users/:id");
+    /// let spa = PathPattern::parse("/app/*");
     /// ```
     pub fn parse(pattern: &str) -> Self {
-        let segments = pattern
+        let segments: Vec<Segment> = pattern
             .split('/')
             .filter(|s| !s.is_empty())
-            .map(|segment| {
-                if segment.starts_with(':') {
-                    Segment::Dynamic(segment[1..].to_string())
-                } else {
-                    Segment::Static(segment.to_string())
-                }
-            })
+            .map(|segment| parse_segment(segment, pattern))
             .collect();
 
+        if let Some(position) = segments.iter().position(|s| matches!(s, Segment::Wildcard(_))) {
+            assert!(
+                position == segments.len() - 1,
+                "wildcard must be the last segment in route pattern `{}`",
+                pattern
+            );
+        }
+
+        if let Some(position) = segments.iter().position(|s| matches!(s, Segment::Optional(_))) {
+            assert!(
+                segments[position..].iter().all(|s| matches!(s, Segment::Optional(_))),
+                "optional segments must come last in route pattern `{}`",
+                pattern
+            );
+        }
+
         Self {
             segments,
             raw: pattern.to_string(),
@@ -105,35 +382,83 @@ impl PathPattern {
     /// assert_eq!(params.get("id"), Some(&"123".to_string()));
     /// ```
     pub fn matches(&self, path: &str) -> Option<HashMap<String, String>> {
-        let path_segments: Vec<&str> = path
-            .split('/')
-            .filter(|s| !s.is_empty())
-            .collect();
+        let mut captures = Vec::new();
+        let mut rest = path;
 
-        // Must have same number of segments
-        if path_segments.len() != self.segments.len() {
-            return None;
-        }
+        for pattern_seg in &self.segments {
+            rest = rest.trim_start_matches('/');
 
-        let mut params = HashMap::new();
+            // A wildcard takes whatever is left, so it always matches
+            if let Segment::Wildcard(_) = pattern_seg {
+                captures.push(rest);
+                return Some(self.params_from_captures(&captures));
+            }
 
-        for (pattern_seg, path_seg) in self.segments.iter().zip(path_segments.iter()) {
-            match pattern_seg {
-                Segment::Static(expected) => {
-                    if expected != path_seg {
-                        return None;
-                    }
-                }
-                Segment::Dynamic(param_name) => {
-                    // URL decode the parameter value
-                    let decoded = urlencoding::decode(path_seg)
-                        .unwrap_or_else(|_| std::borrow::Cow::Borrowed(*path_seg));
-                    params.insert(param_name.clone(), decoded.into_owned());
+            if rest.is_empty() {
+                // Only optional segments may be left over
+                if let Segment::Optional(_) = pattern_seg {
+                    break;
                 }
+                return None;
+            }
+            let (path_seg, tail) = rest.split_once('/').unwrap_or((rest, ""));
+            rest = tail;
+
+            if !pattern_seg.matches(path_seg, &mut captures) {
+                return None;
+            }
+        }
+
+        // Must have consumed the whole path
+        if rest.trim_start_matches('/').is_empty() {
+            Some(self.params_from_captures(&captures))
+        } else {
+            None
+        }
+    }
+
+    /// Checks if this pattern matches the start of a path, segment by segment.
+    ///
+    /// Used to find the mounted router a path falls under, whether or not
+    /// one of its routes matches the whole path.
+    fn matches_prefix(&self, path: &str) -> bool {
+        let mut captures = Vec::new();
+        let mut rest = path;
+
+        for pattern_seg in &self.segments {
+            rest = rest.trim_start_matches('/');
+
+            if let Segment::Wildcard(_) = pattern_seg {
+                return true;
+            }
+            if rest.is_empty() {
+                return matches!(pattern_seg, Segment::Optional(_));
+            }
+            let (path_seg, tail) = rest.split_once('/').unwrap_or((rest, ""));
+            rest = tail;
+
+            if !pattern_seg.matches(path_seg, &mut captures) {
+                return false;
             }
         }
 
-        Some(params)
+        true
+    }
+
+    /// Builds the parameter map from raw captured values, in segment order.
+    ///
+    /// Missing trailing captures belong to optional segments and are left out.
+    fn params_from_captures(&self, captures: &[&str]) -> HashMap<String, String> {
+        let mut names = Vec::new();
+        for segment in &self.segments {
+            segment.param_names(&mut names);
+        }
+
+        names
+            .into_iter()
+            .zip(captures)
+            .map(|(name, value)| (name.clone(), decode_param(value)))
+            .collect()
     }
 
     /// Returns the raw pattern string.
@@ -166,6 +491,267 @@ impl PathPattern {
     }
 }
 
+/// Parses one `/`-delimited piece of a route pattern.
+fn parse_segment(segment: &str, pattern: &str) -> Segment {
+    if let Some(name) = segment.strip_prefix('*') {
+        let name = if name.is_empty() { "*" } else { name };
+        return Segment::Wildcard(name.to_string());
+    }
+
+    if let Some(inner) = segment.strip_suffix('?') {
+        let inner = parse_segment(inner, pattern);
+        assert!(
+            matches!(inner, Segment::Dynamic(_) | Segment::Constrained(..)),
+            "only parameters can be optional in route pattern `{}`",
+            pattern
+        );
+        return Segment::Optional(Box::new(inner));
+    }
+
+    let mut parts = Vec::new();
+    let mut rest = segment;
+    while !rest.is_empty() {
+        // A segment starting with `:` is a parameter, as is a `:` followed
+        // by a name inside one; other colons, as in `12:30`, are literal
+        let param = rest
+            .strip_prefix(':')
+            .filter(|param| rest.len() == segment.len() || starts_name(param));
+        let Some(param) = param else {
+            let end = rest
+                .char_indices()
+                .skip(1)
+                .find(|&(i, c)| c == ':' && starts_name(&rest[i + 1..]))
+                .map_or(rest.len(), |(i, _)| i);
+            parts.push(Segment::Static(rest[..end].to_string()));
+            rest = &rest[end..];
+            continue;
+        };
+
+        if matches!(parts.last(), Some(Segment::Dynamic(_) | Segment::Constrained(..))) {
+            panic!(
+                "parameters must be separated by literal text in route pattern `{}`",
+                pattern
+            );
+        }
+
+        let name_end = param
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(param.len());
+        let name = param[..name_end].to_string();
+        assert!(!name.is_empty(), "parameter without a name in route pattern `{}`", pattern);
+        rest = &param[name_end..];
+
+        match rest.strip_prefix('<') {
+            Some(spec) => {
+                let end = constraint_end(spec).unwrap_or_else(|| {
+                    panic!("unclosed parameter constraint in route pattern `{}`", pattern)
+                });
+                parts.push(Segment::Constrained(name, Constraint::parse(&spec[..end])));
+                rest = &spec[end + 1..];
+            }
+            None => parts.push(Segment::Dynamic(name)),
+        }
+    }
+
+    match parts.len() {
+        1 => parts.remove(0),
+        _ => Segment::Mixed(parts),
+    }
+}
+
+/// Whether `text` starts with a character that can begin a parameter name
+/// in the middle of a segment.
+fn starts_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+}
+
+/// Finds the `>` that closes a constraint, skipping nested `<...>` pairs.
+fn constraint_end(spec: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' if depth == 0 => return Some(i),
+            '>' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// URL decodes a captured parameter value, keeping the raw value if it isn't
+/// valid percent-encoded UTF-8.
+fn decode_param(value: &str) -> String {
+    urlencoding::decode(value)
+        .unwrap_or(std::borrow::Cow::Borrowed(value))
+        .into_owned()
+}
+
+/// A node in the route tree.
+///
+/// Routes are compiled into a tree keyed on [`Segment`]s so that a lookup walks
+/// the request path once instead of testing every registered pattern. Each node
+/// lists the routes that end at it, keyed by method.
+#[derive(Default)]
+struct Node {
+    /// Children for static segments, keyed by the literal segment
+    statics: HashMap<String, Node>,
+    /// Children for mixed segments, keyed by their parts without parameter
+    /// names, in registration order
+    mixed: Vec<(Vec<Segment>, Node)>,
+    /// Children for constrained segments, in registration order
+    constrained: Vec<(Constraint, Node)>,
+    /// Child for a dynamic segment, shared by all parameter names
+    dynamic: Option<Box<Node>>,
+    /// Child for a trailing wildcard; it never has children of its own
+    wildcard: Option<Box<Node>>,
+    /// Routes ending at this node as (method, index into `Router::routes`)
+    endpoints: Vec<(Method, usize)>,
+}
+
+impl Node {
+    /// Inserts a route's segments below this node.
+    ///
+    /// A route with optional segments ends at every node from the first
+    /// optional segment down to the last one.
+    fn insert(&mut self, segments: &[Segment], method: Method, index: usize) {
+        let mut node = self;
+        for segment in segments {
+            if let Segment::Optional(_) = segment {
+                node.add_endpoint(method, index);
+            }
+            node = node.child_mut(segment);
+        }
+        node.add_endpoint(method, index);
+    }
+
+    /// Returns the child for `segment`, creating it if needed.
+    fn child_mut(&mut self, segment: &Segment) -> &mut Node {
+        match segment {
+            Segment::Static(literal) => self.statics.entry(literal.clone()).or_default(),
+            Segment::Mixed(parts) => {
+                let shape: Vec<Segment> = parts
+                    .iter()
+                    .map(|part| match part {
+                        Segment::Dynamic(_) => Segment::Dynamic(String::new()),
+                        Segment::Constrained(_, c) => Segment::Constrained(String::new(), c.clone()),
+                        other => other.clone(),
+                    })
+                    .collect();
+                let position = self.mixed.iter().position(|(s, _)| *s == shape);
+                let position = position.unwrap_or_else(|| {
+                    self.mixed.push((shape, Node::default()));
+                    self.mixed.len() - 1
+                });
+                &mut self.mixed[position].1
+            }
+            Segment::Constrained(_, constraint) => {
+                let position = self.constrained.iter().position(|(c, _)| c == constraint);
+                let position = position.unwrap_or_else(|| {
+                    self.constrained.push((constraint.clone(), Node::default()));
+                    self.constrained.len() - 1
+                });
+                &mut self.constrained[position].1
+            }
+            Segment::Optional(inner) => self.child_mut(inner),
+            Segment::Dynamic(_) => self.dynamic.get_or_insert_with(Default::default),
+            Segment::Wildcard(_) => self.wildcard.get_or_insert_with(Default::default),
+        }
+    }
+
+    /// Records a route ending at this node; the first route registered for a
+    /// method and shape wins.
+    fn add_endpoint(&mut self, method: Method, index: usize) {
+        if !self.endpoints.iter().any(|(m, _)| *m == method) {
+            self.endpoints.push((method, index));
+        }
+    }
+
+    /// Walks the tree along `path`, calling `visit` for every node the full
+    /// path resolves to, together with the values captured by dynamic segments.
+    ///
+    /// Static children are tried first, then mixed children, then constrained
+    /// children whose constraint accepts the segment, then the dynamic child
+    /// and finally the wildcard, so `visit` sees matches in priority order.
+    /// The walk stops as soon as `visit` returns `true`.
+    fn walk<'p>(
+        &self,
+        path: &'p str,
+        captures: &mut Vec<&'p str>,
+        visit: &mut dyn FnMut(&Node, &[&'p str]) -> bool,
+    ) -> bool {
+        let path = path.trim_start_matches('/');
+        if path.is_empty() {
+            if visit(self, captures) {
+                return true;
+            }
+        } else {
+            let (segment, rest) = path.split_once('/').unwrap_or((path, ""));
+
+            if let Some(child) = self.statics.get(segment) {
+                if child.walk(rest, captures, visit) {
+                    return true;
+                }
+            }
+
+            for (parts, child) in &self.mixed {
+                let len = captures.len();
+                let stop = match_parts(parts, segment, captures) && child.walk(rest, captures, visit);
+                captures.truncate(len);
+                if stop {
+                    return true;
+                }
+            }
+
+            if !self.constrained.is_empty() {
+                let value = urlencoding::decode(segment)
+                    .unwrap_or(std::borrow::Cow::Borrowed(segment));
+                for (constraint, child) in &self.constrained {
+                    if constraint.allows(&value) {
+                        captures.push(segment);
+                        let stop = child.walk(rest, captures, visit);
+                        captures.pop();
+                        if stop {
+                            return true;
+                        }
+                    }
+                }
+            }
+
+            if let Some(child) = &self.dynamic {
+                captures.push(segment);
+                let stop = child.walk(rest, captures, visit);
+                captures.pop();
+                if stop {
+                    return true;
+                }
+            }
+        }
+
+        // A wildcard captures whatever is left of the path, even nothing
+        if let Some(child) = &self.wildcard {
+            captures.push(path);
+            let stop = visit(child, captures);
+            captures.pop();
+            if stop {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// The result of resolving a method and path against a [`Router`].
+pub enum RouteMatch<'a> {
+    /// A route matched; includes the extracted path parameters
+    Found(&'a Route, HashMap<String, String>),
+    /// The path matched, but not for this method; includes the allowed methods
+    MethodNotAllowed(Vec<Method>),
+    /// No route matches the path
+    NotFound,
+}
+
 /// Type alias for handler functions.
 ///
 /// Handlers are async functions that take a `Request` and return a `Result<Response>`.
@@ -180,29 +766,34 @@ pub type HandlerFn = std::sync::Arc<
 ///
 /// Routes are typically created through the `App` or `Router` methods
 /// (e.g., `get()`, `post()`) rather than directly.
+///
+/// Each route carries its own middleware chain. When a router is mounted, its
+/// middleware is prepended to the chain of every route it contributes, so a
+/// route only runs the middleware of the routers it was mounted through.
 pub struct Route {
     method: Method,
     pattern: PathPattern,
     handler: HandlerFn,
+    middleware: Vec<Arc<dyn Middleware>>,
 }
 
 impl Route {
     /// Creates a new route with the specified method, pattern, and handler.
     ///
     /// This is typically used internally by the framework.
-    pub fn new<F, Fut>(method: Method, pattern: &str, handler: F) -> Self
+    pub fn new<H, T>(method: Method, pattern: &str, handler: H) -> Self
     where
-        F: Fn(Request) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Response>> + Send + 'static,
+        H: Handler<T>,
     {
         let handler_fn = std::sync::Arc::new(move |req: Request| {
-            Box::pin(handler(req)) as Pin<Box<dyn Future<Output = Result<Response>> + Send>>
+            handler.handle(req) as Pin<Box<dyn Future<Output = Result<Response>> + Send>>
         });
 
         Self {
             method,
             pattern: PathPattern::parse(pattern),
             handler: handler_fn,
+            middleware: Vec::new(),
         }
     }
 
@@ -216,6 +807,20 @@ impl Route {
         &self.pattern
     }
 
+    /// Returns the middleware chain for this route.
+    ///
+    /// The chain is ordered outermost first: middleware from the router that
+    /// was mounted last (closest to the application) comes before middleware
+    /// from the routers nested inside it.
+    pub fn middleware(&self) -> &[Arc<dyn Middleware>] {
+        &self.middleware
+    }
+
+    /// Builds the parameter map from values captured by the route tree.
+    fn params_from_captures(&self, captures: &[&str]) -> HashMap<String, String> {
+        self.pattern.params_from_captures(captures)
+    }
+
     /// Checks if this route matches the given method and path.
     ///
     /// Returns extracted parameters if the route matches, or `None` otherwise.
@@ -228,6 +833,8 @@ impl Route {
     }
 
     /// Executes the route handler with the given request.
+    ///
+    /// This calls the handler directly and does not run the route's middleware.
     pub async fn handle(&self, req: Request) -> Result<Response> {
         (self.handler)(req).await
     }
@@ -253,17 +860,26 @@ impl Route {
 /// let mut api = Router::new("/api");
 ///
 /// api.get("/users", |_req: Request| async {
-///     Ok(Response::text("Users".to_string()))
+///     Response::text("Users".to_string())
 /// });
 ///
 /// api.get("/posts", |_req: Request| async {
-///     Ok(Response::text("Posts".to_string()))
+///     Response::text("Posts".to_string())
 /// });
 /// ```
 pub struct Router {
     prefix: String,
     routes: Vec<Route>,
-    middleware: Vec<std::sync::Arc<dyn Middleware>>,
+    tree: Node,
+    middleware: Vec<Arc<dyn Middleware>>,
+    scopes: Vec<Scope>,
+}
+
+/// The prefix of a mounted router together with its middleware chain, so
+/// that chain also runs for requests under the prefix that no route matches.
+struct Scope {
+    prefix: PathPattern,
+    middleware: Vec<Arc<dyn Middleware>>,
 }
 
 impl Router {
@@ -284,114 +900,249 @@ impl Router {
         Self {
             prefix: prefix.to_string(),
             routes: Vec::new(),
+            tree: Node::default(),
             middleware: Vec::new(),
+            scopes: Vec::new(),
         }
     }
 
     /// Registers a GET route on this router.
     ///
     /// The route path will be prefixed with the router's prefix.
-    pub fn get<F, Fut>(&mut self, path: &str, handler: F) -> &mut Self
+    pub fn get<H, T>(&mut self, path: &str, handler: H) -> &mut Self
     where
-        F: Fn(Request) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Response>> + Send + 'static,
+        H: Handler<T>,
     {
         let full_path = format!("{}{}", self.prefix, path);
-        self.routes.push(Route::new(Method::GET, &full_path, handler));
+        self.add_route(Route::new(Method::GET, &full_path, handler));
         self
     }
 
     /// Registers a POST route on this router.
     ///
     /// The route path will be prefixed with the router's prefix.
-    pub fn post<F, Fut>(&mut self, path: &str, handler: F) -> &mut Self
+    pub fn post<H, T>(&mut self, path: &str, handler: H) -> &mut Self
     where
-        F: Fn(Request) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Response>> + Send + 'static,
+        H: Handler<T>,
     {
         let full_path = format!("{}{}", self.prefix, path);
-        self.routes.push(Route::new(Method::POST, &full_path, handler));
+        self.add_route(Route::new(Method::POST, &full_path, handler));
         self
     }
 
     /// Registers a PUT route on this router.
     ///
     /// The route path will be prefixed with the router's prefix.
-    pub fn put<F, Fut>(&mut self, path: &str, handler: F) -> &mut Self
+    pub fn put<H, T>(&mut self, path: &str, handler: H) -> &mut Self
     where
-        F: Fn(Request) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Response>> + Send + 'static,
+        H: Handler<T>,
     {
         let full_path = format!("{}{}", self.prefix, path);
-        self.routes.push(Route::new(Method::PUT, &full_path, handler));
+        self.add_route(Route::new(Method::PUT, &full_path, handler));
         self
     }
 
     /// Registers a DELETE route on this router.
     ///
     /// The route path will be prefixed with the router's prefix.
-    pub fn delete<F, Fut>(&mut self, path: &str, handler: F) -> &mut Self
+    pub fn delete<H, T>(&mut self, path: &str, handler: H) -> &mut Self
     where
-        F: Fn(Request) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Response>> + Send + 'static,
+        H: Handler<T>,
     {
         let full_path = format!("{}{}", self.prefix, path);
-        self.routes.push(Route::new(Method::DELETE, &full_path, handler));
+        self.add_route(Route::new(Method::DELETE, &full_path, handler));
         self
     }
 
     /// Registers a PATCH route on this router.
     ///
     /// The route path will be prefixed with the router's prefix.
-    pub fn patch<F, Fut>(&mut self, path: &str, handler: F) -> &mut Self
+    pub fn patch<H, T>(&mut self, path: &str, handler: H) -> &mut Self
     where
-        F: Fn(Request) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Response>> + Send + 'static,
+        H: Handler<T>,
     {
         let full_path = format!("{}{}", self.prefix, path);
-        self.routes.push(Route::new(Method::PATCH, &full_path, handler));
+        self.add_route(Route::new(Method::PATCH, &full_path, handler));
         self
     }
 
     /// Adds middleware to this router.
     ///
-    /// The middleware will only apply to routes registered on this router.
-    pub fn use_middleware(&mut self, middleware: std::sync::Arc<dyn Middleware>) -> &mut Self {
+    /// The middleware will only apply to routes registered on this router,
+    /// including routes from routers mounted into it. It is attached to those
+    /// routes when this router is mounted, so it runs after application-level
+    /// middleware and after the middleware of any router this one is mounted into.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use ruffus::{App, Middleware, Next, Request, Response, Router};
+    /// # use async_trait::async_trait;
+    /// # use std::sync::Arc;
+    /// struct RequireAuth;
+    ///
+    /// #[async_trait]
+    /// impl Middleware for RequireAuth {
+    ///     async fn handle(&self, req: Request, next: Next) -> ruffus::Result<Response> {
+    ///         if req.headers().get("authorization").is_none() {
+    ///             return Ok(Response::new().status(http::StatusCode::UNAUTHORIZED));
+    ///         }
+    ///         next.run(req).await
+    ///     }
+    /// }
+    ///
+    /// let mut admin = Router::new("/admin");
+    /// admin.use_middleware(Arc::new(RequireAuth));
+    /// admin.get("/stats", |_req: Request| async {
+    ///     Response::text("secret".to_string())
+    /// });
+    ///
+    /// let mut app = App::new();
+    /// app.get("/public", |_req: Request| async {
+    ///     Response::text("hello".to_string())
+    /// });
+    /// // RequireAuth runs for /admin/stats but not for /public
+    /// app.mount("", admin);
+    /// ```
+    pub fn use_middleware(&mut self, middleware: Arc<dyn Middleware>) -> &mut Self {
         self.middleware.push(middleware);
         self
     }
 
+    /// Declares a substate `S` for this router, projected from the parent state `P`.
+    ///
+    /// For every request to this router's routes, the state of type `P` (the
+    /// application state, or a substate of an enclosing router) is mapped to
+    /// `S` with [`FromRef`], so handlers and middleware of this router can
+    /// extract `State<S>`. The projection runs before the router's other
+    /// middleware.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruffus::{App, FromRef, Response, Router, State};
+    ///
+    /// #[derive(Clone)]
+    /// struct AppState {
+    ///     admin_token: String,
+    /// }
+    ///
+    /// #[derive(Clone)]
+    /// struct AdminState {
+    ///     token: String,
+    /// }
+    ///
+    /// impl FromRef<AppState> for AdminState {
+    ///     fn from_ref(state: &AppState) -> Self {
+    ///         AdminState { token: state.admin_token.clone() }
+    ///     }
+    /// }
+    ///
+    /// let mut admin = Router::new("/admin");
+    /// admin.substate::<AppState, AdminState>();
+    /// admin.get("/token", |State(admin): State<AdminState>| async move {
+    ///     Response::text(admin.token)
+    /// });
+    ///
+    /// let mut app = App::with_state(AppState { admin_token: "secret".to_string() });
+    /// app.mount("", admin);
+    /// ```
+    pub fn substate<P, S>(&mut self) -> &mut Self
+    where
+        P: Send + Sync + 'static,
+        S: FromRef<P> + Clone + Send + Sync + 'static,
+    {
+        self.middleware.insert(0, Arc::new(ProjectState::<P, S>::new()));
+        self
+    }
+
     /// Returns all routes registered on this router.
     pub fn routes(&self) -> &[Route] {
         &self.routes
     }
 
+    /// Registers a route and compiles it into the route tree.
+    fn add_route(&mut self, route: Route) {
+        let index = self.routes.len();
+        self.tree.insert(route.pattern.segments(), route.method, index);
+        self.routes.push(route);
+    }
+
+    /// Resolves a method and path in a single walk of the route tree.
+    ///
+    /// Static segments take priority over dynamic ones. If the highest
+    /// priority match for the path doesn't accept the method, lower priority
+    /// matches are tried before giving up, and the methods accepted by every
+    /// matching route are reported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruffus::{Method, Request, Response, RouteMatch, Router};
+    ///
+    /// let mut router = Router::new("");
+    /// router.get("/users/:id", |_req: Request| async { Response::new() });
+    ///
+    /// match router.resolve(&Method::POST, "/users/42") {
+    ///     RouteMatch::MethodNotAllowed(allowed) => assert_eq!(allowed, vec![Method::GET]),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn resolve(&self, method: &Method, path: &str) -> RouteMatch<'_> {
+        let mut found = None;
+        let mut allowed = Vec::new();
+
+        self.tree.walk(path, &mut Vec::new(), &mut |node, captures| {
+            for (route_method, index) in &node.endpoints {
+                if route_method == method {
+                    let route = &self.routes[*index];
+                    found = Some((route, route.params_from_captures(captures)));
+                    return true;
+                }
+                if !allowed.contains(route_method) {
+                    allowed.push(*route_method);
+                }
+            }
+            false
+        });
+
+        match found {
+            Some((route, params)) => RouteMatch::Found(route, params),
+            None if !allowed.is_empty() => RouteMatch::MethodNotAllowed(allowed),
+            None => RouteMatch::NotFound,
+        }
+    }
+
     /// Finds a matching route for the given method and path.
     ///
     /// Returns the route and extracted parameters if a match is found.
     pub fn find_route(&self, method: &Method, path: &str) -> Option<(&Route, HashMap<String, String>)> {
-        for route in &self.routes {
-            if let Some(params) = route.matches(method, path) {
-                return Some((route, params));
-            }
+        match self.resolve(method, path) {
+            RouteMatch::Found(route, params) => Some((route, params)),
+            _ => None,
         }
-        None
     }
 
     /// Checks if any route matches the path (regardless of HTTP method).
     pub fn path_exists(&self, path: &str) -> bool {
-        self.routes.iter().any(|route| {
-            route.pattern.matches(path).is_some()
-        })
+        self.tree
+            .walk(path, &mut Vec::new(), &mut |node, _| !node.endpoints.is_empty())
     }
 
     /// Returns the allowed HTTP methods for a given path.
     pub fn allowed_methods(&self, path: &str) -> Vec<Method> {
-        self.routes
-            .iter()
-            .filter(|route| route.pattern.matches(path).is_some())
-            .map(|route| *route.method())
-            .collect()
+        let mut allowed = Vec::new();
+
+        self.tree.walk(path, &mut Vec::new(), &mut |node, _| {
+            for (method, _) in &node.endpoints {
+                if !allowed.contains(method) {
+                    allowed.push(*method);
+                }
+            }
+            false
+        });
+
+        allowed
     }
 
     /// Returns the prefix of this router.
@@ -400,10 +1151,20 @@ impl Router {
     }
 
     /// Returns the middleware stack for this router.
-    pub fn middleware(&self) -> &[std::sync::Arc<dyn Middleware>] {
+    pub fn middleware(&self) -> &[Arc<dyn Middleware>] {
         &self.middleware
     }
 
+    /// Returns the middleware chain of the innermost mounted router whose
+    /// prefix covers `path`, or an empty chain if there is none.
+    pub(crate) fn scope_middleware(&self, path: &str) -> &[Arc<dyn Middleware>] {
+        self.scopes
+            .iter()
+            .filter(|scope| scope.prefix.matches_prefix(path))
+            .max_by_key(|scope| scope.prefix.segments.len())
+            .map_or(&[], |scope| &scope.middleware)
+    }
+
     /// Collects all routes with their full paths.
     ///
     /// This is used internally when mounting routers.
@@ -415,6 +1176,9 @@ impl Router {
     ///
     /// The mounted router's routes will have the mount prefix prepended.
     /// The mounting router's own prefix is also prepended to all routes.
+    /// The mounted router's middleware is prepended to each of its routes'
+    /// middleware chains, so it only runs for routes under that router, and
+    /// for requests under its prefix that no route matches.
     ///
     /// # Examples
     ///
@@ -424,45 +1188,94 @@ impl Router {
     /// let mut sub_router = Router::new("/v1");
     ///
     /// sub_router.get("/users", |_req: Request| async {
-    ///     Ok(Response::text("Users".to_string()))
+    ///     Response::text("Users".to_string())
     /// });
     ///
     /// main_router.mount("", sub_router);
     /// // Route is now at /api/v1/users
     /// ```
+    ///
+    /// A mount prefix may end in a wildcard, which lets a router registered at
+    /// its root handle everything below the prefix. As the wildcard must stay
+    /// the last segment, such a router may only have routes at its root, an
+    /// empty prefix, and no nested routers below it:
+    ///
+    /// ```
+    /// # use ruffus::{Method, Router, Request, Response};
+    /// let mut assets = Router::new("");
+    /// assets.get("/", |req: Request| async move {
+    ///     Response::text(req.param("file").unwrap_or_default().to_string())
+    /// });
+    ///
+    /// let mut main_router = Router::new("");
+    /// main_router.mount("/assets/*file", assets);
+    ///
+    /// let (_, params) = main_router.find_route(&Method::GET, "/assets/css/site.css").unwrap();
+    /// assert_eq!(params.get("file"), Some(&"css/site.css".to_string()));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mount prefix ends in a wildcard and the mounted router
+    /// has a route, prefix or nested router below its root.
     pub fn mount(&mut self, mount_prefix: &str, mut router: Router) -> &mut Self {
+        // Combine: self.prefix + mount_prefix + existing route pattern
+        let combined_prefix = format!("{}{}", self.prefix, mount_prefix);
+
+        // Nothing may follow a wildcard, so only the router's root fits under one
+        let wildcard_prefix = matches!(
+            PathPattern::parse(&combined_prefix).segments().last(),
+            Some(Segment::Wildcard(_))
+        );
+        if wildcard_prefix {
+            let below_root = std::iter::once(router.prefix.as_str())
+                .chain(router.routes.iter().map(|route| route.pattern.raw()))
+                .chain(router.scopes.iter().map(|scope| scope.prefix.raw()))
+                .find(|pattern| !PathPattern::parse(pattern).segments().is_empty());
+            if let Some(pattern) = below_root {
+                panic!(
+                    "can't mount a router at wildcard prefix `{}`: `{}` is below the router's root",
+                    combined_prefix, pattern
+                );
+            }
+        }
+
+        // Remember where the mounted router lives, outermost first, so its
+        // middleware can also see requests under it that match no route
+        if !router.middleware.is_empty() {
+            self.scopes.push(Scope {
+                prefix: PathPattern::parse(&format!("{}{}", combined_prefix, router.prefix)),
+                middleware: router.middleware.clone(),
+            });
+        }
+        for scope in router.scopes.drain(..) {
+            self.scopes.push(Scope {
+                prefix: PathPattern::parse(&format!("{}{}", combined_prefix, scope.prefix.raw())),
+                middleware: router.middleware.iter().cloned().chain(scope.middleware).collect(),
+            });
+        }
+
         // Add each route with both the router's prefix and mount prefix prepended
         for route in router.routes.drain(..) {
-            // Combine: self.prefix + mount_prefix + existing route pattern
-            let combined_prefix = if self.prefix.is_empty() && mount_prefix.is_empty() {
-                String::new()
-            } else if self.prefix.is_empty() {
-                mount_prefix.to_string()
-            } else if mount_prefix.is_empty() {
-                self.prefix.clone()
-            } else {
-                format!("{}{}", self.prefix, mount_prefix)
-            };
-            
-            let new_pattern = if combined_prefix.is_empty() {
-                route.pattern.raw().to_string()
-            } else {
-                format!("{}{}", combined_prefix, route.pattern.raw())
-            };
-            
+            let new_pattern = format!("{}{}", combined_prefix, route.pattern.raw());
+
+            // The mounted router's middleware wraps the route's existing chain
+            let middleware = router
+                .middleware
+                .iter()
+                .cloned()
+                .chain(route.middleware)
+                .collect();
+
             // Create a new route with the updated pattern
             let new_route = Route {
                 method: route.method,
                 pattern: PathPattern::parse(&new_pattern),
                 handler: route.handler,
+                middleware,
             };
             
-            self.routes.push(new_route);
-        }
-        
-        // Also merge middleware from the mounted router
-        for middleware in router.middleware.drain(..) {
-            self.middleware.push(middleware);
+            self.add_route(new_route);
         }
         
         self
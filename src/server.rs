@@ -0,0 +1,630 @@
+//! HTTP server for Ruffus applications
+//!
+//! [`App::listen`](crate::App::listen) is enough for most applications. Use
+//! [`Server`] directly to configure how the server runs and how it shuts down,
+//! and [`serve`](Server::serve) to run it on a listener you bound yourself,
+//! such as a Unix socket (see [`Listener`]).
+//!
+//! The server speaks HTTP/1.1 and HTTP/2 on the same port, detecting the
+//! protocol from the first bytes of each connection. HTTP/2 is served over
+//! cleartext with prior knowledge (h2c), as used by gRPC clients and load
+//! balancers.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::{App, Request, Response, Server};
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut app = App::new();
+//!     app.get("/", |_req: Request| async { Response::text("Hello!".to_string()) });
+//!
+//!     Server::new(app)
+//!         .header_read_timeout(Duration::from_secs(5))
+//!         .request_body_timeout(Duration::from_secs(30))
+//!         .max_connections(10_000)
+//!         .drain_timeout(Duration::from_secs(10))
+//!         .http2_max_concurrent_streams(100)
+//!         .listen_with_shutdown("127.0.0.1:3000", async {
+//!             tokio::signal::ctrl_c().await.ok();
+//!         })
+//!         .await
+//!         .unwrap();
+//! }
+//! ```
+
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::service::service_fn;
+use hyper::StatusCode;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+
+#[cfg(feature = "tls")]
+use crate::TlsConfig;
+use crate::listener::{ConnectInfo, Listener};
+use crate::{App, Body, Error, Request, Result};
+
+/// An HTTP server running an [`App`].
+///
+/// Each connection is handled in its own Tokio task. When the shutdown
+/// signal passed to [`listen_with_shutdown`](Server::listen_with_shutdown)
+/// completes, the server stops accepting connections, lets in-flight requests
+/// finish, closes idle keep-alive connections, and returns once every
+/// connection is closed or the drain timeout has expired.
+pub struct Server {
+    app: Arc<App>,
+    builder: auto::Builder<TokioExecutor>,
+    drain_timeout: Option<Duration>,
+    limits: RequestLimits,
+    max_connections: Option<usize>,
+}
+
+/// Limits checked by the server for each request
+#[derive(Debug, Clone, Copy, Default)]
+struct RequestLimits {
+    body_timeout: Option<Duration>,
+    max_header_size: Option<usize>,
+}
+
+/// How long to pause accepting after an error such as running out of file
+/// descriptors
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The smallest read buffer hyper accepts for HTTP/1 connections
+const MIN_HTTP1_BUFFER_SIZE: usize = 8192;
+
+impl Server {
+    /// Creates a server for the given application.
+    ///
+    /// By default there is no drain timeout: shutdown waits for every
+    /// in-flight request to finish.
+    pub fn new(app: App) -> Self {
+        let mut builder = auto::Builder::new(TokioExecutor::new());
+        builder.http1().timer(TokioTimer::new());
+        builder.http2().timer(TokioTimer::new());
+
+        Self {
+            app: Arc::new(app),
+            builder,
+            drain_timeout: None,
+            limits: RequestLimits::default(),
+            max_connections: None,
+        }
+    }
+
+    /// Sets how long a client may take to send the request headers.
+    ///
+    /// Connections that don't send a complete request head in time are
+    /// closed, which protects against slowloris attacks. The timeout applies
+    /// to every request on a keep-alive connection. Defaults to 30 seconds.
+    /// Only applies to HTTP/1.
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.builder.http1().header_read_timeout(timeout);
+        self
+    }
+
+    /// Sets how long a client may take to send the request body.
+    ///
+    /// The timeout starts when the request head arrives. Buffering a body
+    /// that isn't received in time fails with `408 Request Timeout`, and
+    /// streaming reads fail with [`std::io::ErrorKind::TimedOut`]. There is
+    /// no timeout by default.
+    pub fn request_body_timeout(mut self, timeout: Duration) -> Self {
+        self.limits.body_timeout = Some(timeout);
+        self
+    }
+
+    /// Enables or disables HTTP/1 keep-alive.
+    ///
+    /// When disabled, each connection is closed after one response. Enabled
+    /// by default.
+    pub fn keep_alive(mut self, enabled: bool) -> Self {
+        self.builder.http1().keep_alive(enabled);
+        self
+    }
+
+    /// Sets the maximum number of headers in an HTTP/1 request.
+    ///
+    /// Requests with more headers are answered with
+    /// `431 Request Header Fields Too Large`. Defaults to 100.
+    pub fn max_headers(mut self, max: usize) -> Self {
+        self.builder.http1().max_headers(max);
+        self
+    }
+
+    /// Sets the maximum size of the request head, in bytes.
+    ///
+    /// The size counts the request line and every header line as they are
+    /// sent over HTTP/1. Larger requests are answered with
+    /// `431 Request Header Fields Too Large`. HTTP/2 clients are also told
+    /// the limit through the `SETTINGS_MAX_HEADER_LIST_SIZE` setting. Without
+    /// a limit, HTTP/1 heads are limited to about 400KB and HTTP/2 header
+    /// lists to 16KB.
+    pub fn max_header_size(mut self, max: usize) -> Self {
+        self.limits.max_header_size = Some(max);
+        // Bounds how much of an oversized head is buffered before it's
+        // rejected; hyper doesn't go below 8KB
+        self.builder
+            .http1()
+            .max_buf_size(max.max(MIN_HTTP1_BUFFER_SIZE));
+        self.builder
+            .http2()
+            .max_header_list_size(max.try_into().unwrap_or(u32::MAX));
+        self
+    }
+
+    /// Sets whether HTTP/1 connections support half-closures.
+    ///
+    /// When enabled, a client may shut down its side of the connection after
+    /// sending a request and still receive the response. When disabled, the
+    /// connection is closed as soon as the client shuts down its side.
+    /// Disabled by default.
+    pub fn half_close(mut self, enabled: bool) -> Self {
+        self.builder.http1().half_close(enabled);
+        self
+    }
+
+    /// Limits how many connections are served at the same time.
+    ///
+    /// Once the limit is reached, the server stops accepting until a
+    /// connection closes, leaving new clients in the listener's backlog.
+    /// There is no limit by default.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Sets how long shutdown waits for in-flight connections.
+    ///
+    /// Connections still open when the timeout expires are closed without
+    /// waiting for their requests to complete.
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum number of concurrent HTTP/2 streams per connection.
+    ///
+    /// Defaults to 200.
+    pub fn http2_max_concurrent_streams(mut self, max: u32) -> Self {
+        self.builder.http2().max_concurrent_streams(max);
+        self
+    }
+
+    /// Sets the initial HTTP/2 flow control window size of each stream, in
+    /// bytes.
+    ///
+    /// Defaults to 1MB.
+    pub fn http2_initial_stream_window_size(mut self, size: u32) -> Self {
+        self.builder.http2().initial_stream_window_size(size);
+        self
+    }
+
+    /// Sets the initial HTTP/2 flow control window size of each connection,
+    /// in bytes.
+    ///
+    /// Defaults to 1MB.
+    pub fn http2_initial_connection_window_size(mut self, size: u32) -> Self {
+        self.builder.http2().initial_connection_window_size(size);
+        self
+    }
+
+    /// Sends an HTTP/2 ping on every connection at this interval to keep it
+    /// alive.
+    ///
+    /// Pings are disabled by default.
+    pub fn http2_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.builder.http2().keep_alive_interval(interval);
+        self
+    }
+
+    /// Sets how long to wait for a keep-alive ping to be acknowledged before
+    /// closing the connection.
+    ///
+    /// Only used when [`http2_keep_alive_interval`](Server::http2_keep_alive_interval)
+    /// is set. Defaults to 20 seconds.
+    pub fn http2_keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.builder.http2().keep_alive_timeout(timeout);
+        self
+    }
+
+    /// Binds to the address and serves requests until the process exits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address is invalid, binding fails, or the
+    /// listener fails.
+    pub async fn listen(self, addr: &str) -> Result<()> {
+        self.listen_with_shutdown(addr, std::future::pending()).await
+    }
+
+    /// Binds to the address and serves requests until `signal` completes,
+    /// then shuts down gracefully.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address is invalid, binding fails, or the
+    /// listener fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use ruffus::{App, Server};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    ///
+    /// let server = tokio::spawn(
+    ///     Server::new(App::new()).listen_with_shutdown("127.0.0.1:3000", async {
+    ///         stopped.await.ok();
+    ///     }),
+    /// );
+    ///
+    /// // ...
+    /// stop.send(()).unwrap();
+    /// server.await.unwrap().unwrap();
+    /// # }
+    /// ```
+    pub async fn listen_with_shutdown<F>(self, addr: &str, signal: F) -> Result<()>
+    where
+        F: Future<Output = ()> + Send,
+    {
+        let listener = bind(addr).await?;
+        println!("Ruffus server listening on http://{}", addr);
+
+        self.serve_with_shutdown(listener, signal).await
+    }
+
+    /// Serves requests from an already bound listener until the process
+    /// exits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the listener fails.
+    pub async fn serve<L: Listener>(self, listener: L) -> Result<()> {
+        self.serve_with_shutdown(listener, std::future::pending())
+            .await
+    }
+
+    /// Serves requests from an already bound listener until `signal`
+    /// completes, then shuts down gracefully.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the listener fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use ruffus::{App, Server};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let listener = tokio::net::UnixListener::bind("/run/app.sock").unwrap();
+    ///
+    /// Server::new(App::new())
+    ///     .serve_with_shutdown(listener, async {
+    ///         tokio::signal::ctrl_c().await.ok();
+    ///     })
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn serve_with_shutdown<L, F>(self, listener: L, signal: F) -> Result<()>
+    where
+        L: Listener,
+        F: Future<Output = ()> + Send,
+    {
+        self.serve_listener(listener, |stream| std::future::ready(Ok(stream)), signal)
+            .await
+    }
+
+    /// Binds to the address and serves HTTPS requests until the process
+    /// exits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address is invalid, binding fails, the TLS
+    /// configuration is invalid, or the listener fails.
+    #[cfg(feature = "tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+    pub async fn listen_tls(self, addr: &str, tls: TlsConfig) -> Result<()> {
+        self.listen_tls_with_shutdown(addr, tls, std::future::pending())
+            .await
+    }
+
+    /// Binds to the address and serves HTTPS requests until `signal`
+    /// completes, then shuts down gracefully.
+    ///
+    /// TLS handshakes run in the connection tasks, so a slow client can't
+    /// hold up other connections, and are bounded by the configuration's
+    /// [`handshake_timeout`](TlsConfig::handshake_timeout). If the configuration has a
+    /// [`reload_interval`](TlsConfig::reload_interval), the certificate files
+    /// are watched for as long as the server runs.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`listen_tls`](Server::listen_tls).
+    #[cfg(feature = "tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+    pub async fn listen_tls_with_shutdown<F>(self, addr: &str, tls: TlsConfig, signal: F) -> Result<()>
+    where
+        F: Future<Output = ()> + Send,
+    {
+        let listener = bind(addr).await?;
+        println!("Ruffus server listening on https://{}", addr);
+
+        self.serve_tls_with_shutdown(listener, tls, signal).await
+    }
+
+    /// Serves HTTPS requests from an already bound listener until `signal`
+    /// completes, then shuts down gracefully.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the TLS configuration is invalid or the listener
+    /// fails.
+    #[cfg(feature = "tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+    pub async fn serve_tls_with_shutdown<L, F>(self, listener: L, tls: TlsConfig, signal: F) -> Result<()>
+    where
+        L: Listener,
+        F: Future<Output = ()> + Send,
+    {
+        let acceptor = tls.acceptor()?;
+        let handshake_timeout = tls.get_handshake_timeout();
+        let handshake = move |stream| {
+            let accept = tokio::time::timeout(handshake_timeout, acceptor.accept(stream));
+            async move {
+                accept
+                    .await
+                    .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out"))?
+            }
+        };
+        let watcher = tls.watch().map(tokio::spawn);
+        let result = self.serve_listener(listener, handshake, signal).await;
+        if let Some(watcher) = watcher {
+            watcher.abort();
+        }
+        result
+    }
+
+    /// Accepts connections from `listener` until `signal` completes, then
+    /// drains the open connections.
+    ///
+    /// `handshake` turns each accepted stream into the transport HTTP is
+    /// served over.
+    async fn serve_listener<L, A, H, I, F>(self, mut listener: L, handshake: A, signal: F) -> Result<()>
+    where
+        L: Listener,
+        A: Fn(L::Io) -> H,
+        H: Future<Output = io::Result<I>> + Send + 'static,
+        I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        F: Future<Output = ()> + Send,
+    {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let mut connections = JoinSet::new();
+        let builder = Arc::new(self.builder);
+        let limit = self.max_connections.map(|max| Arc::new(Semaphore::new(max)));
+        let mut fatal = None;
+        tokio::pin!(signal);
+
+        loop {
+            tokio::select! {
+                (permit, accepted) = accept(&mut listener, limit.clone()) => {
+                    let (stream, remote) = match accepted {
+                        Ok(accepted) => accepted,
+                        // The peer went away before we got to it
+                        Err(err) if is_connection_error(&err) => continue,
+                        Err(err) if is_fatal_accept_error(&err) => {
+                            fatal = Some(err);
+                            break;
+                        }
+                        Err(err) => {
+                            // Usually out of file descriptors or memory, so
+                            // give connections a moment to close
+                            eprintln!("Error accepting connection: {}", err);
+                            drop(permit);
+                            tokio::select! {
+                                _ = tokio::time::sleep(ACCEPT_ERROR_BACKOFF) => continue,
+                                _ = &mut signal => break,
+                            }
+                        }
+                    };
+                    let connection = serve_connection(
+                        self.app.clone(),
+                        builder.clone(),
+                        handshake(stream),
+                        ConnectInfo(remote),
+                        self.limits,
+                        shutdown_rx.clone(),
+                    );
+                    connections.spawn(async move {
+                        connection.await;
+                        // Frees the slot for the next connection
+                        drop(permit);
+                    });
+                }
+                // Reap finished connections so the set doesn't grow unbounded
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                _ = &mut signal => break,
+            }
+        }
+
+        // Stop accepting, then ask every connection to finish up
+        drop(listener);
+        let _ = shutdown_tx.send(true);
+
+        let drain = async { while connections.join_next().await.is_some() {} };
+        match self.drain_timeout {
+            Some(timeout) => {
+                if tokio::time::timeout(timeout, drain).await.is_err() {
+                    connections.shutdown().await;
+                }
+            }
+            None => drain.await,
+        }
+
+        match fatal {
+            Some(err) => Err(Error::InternalServerError(format!("Failed to accept connection: {}", err))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns whether an accept error only concerns the connection being
+/// accepted.
+fn is_connection_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+    )
+}
+
+/// Returns whether an accept error means the listener can't accept any more
+/// connections.
+fn is_fatal_accept_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::InvalidInput
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::Unsupported
+    )
+}
+
+/// Waits for a free connection slot, if connections are limited, then for
+/// the next connection.
+async fn accept<L: Listener>(
+    listener: &mut L,
+    limit: Option<Arc<Semaphore>>,
+) -> (Option<OwnedSemaphorePermit>, io::Result<(L::Io, L::Addr)>) {
+    let permit = match limit {
+        Some(limit) => Some(limit.acquire_owned().await.expect("connection limit is never closed")),
+        None => None,
+    };
+    (permit, listener.accept().await)
+}
+
+/// Parses the address and binds a TCP listener to it.
+async fn bind(addr: &str) -> Result<TcpListener> {
+    let addr = addr
+        .parse::<std::net::SocketAddr>()
+        .map_err(|e| Error::InternalServerError(format!("Invalid address: {}", e)))?;
+
+    TcpListener::bind(addr)
+        .await
+        .map_err(|e| Error::InternalServerError(format!("Failed to bind: {}", e)))
+}
+
+/// Completes the handshake and serves one connection, switching to a
+/// graceful shutdown when signalled.
+async fn serve_connection<H, I, A>(
+    app: Arc<App>,
+    builder: Arc<auto::Builder<TokioExecutor>>,
+    handshake: H,
+    remote: ConnectInfo<A>,
+    limits: RequestLimits,
+    mut shutdown: watch::Receiver<bool>,
+) where
+    H: Future<Output = io::Result<I>>,
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    A: Clone + Send + Sync + 'static,
+{
+    // Connections still in their handshake are simply dropped on shutdown
+    let io = tokio::select! {
+        io = handshake => match io {
+            Ok(io) => io,
+            Err(err) => {
+                eprintln!("Error accepting connection: {}", err);
+                return;
+            }
+        },
+        _ = shutdown_requested(&mut shutdown) => return,
+    };
+
+    let service = service_fn(move |req| handle(app.clone(), remote.clone(), limits, req));
+    // Upgraded connections, such as WebSockets, continue in their own task
+    let conn = builder.serve_connection_with_upgrades(TokioIo::new(io), service);
+    tokio::pin!(conn);
+
+    let result = tokio::select! {
+        result = conn.as_mut() => result,
+        _ = shutdown_requested(&mut shutdown) => {
+            // Finishes in-flight requests and closes idle keep-alive
+            // connections, or sends GOAWAY on HTTP/2
+            conn.as_mut().graceful_shutdown();
+            conn.await
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("Error serving connection: {:?}", err);
+    }
+}
+
+/// Resolves once the server starts shutting down.
+async fn shutdown_requested(shutdown: &mut watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|stop| *stop).await;
+}
+
+/// Returns the size of the request line and headers as sent over HTTP/1.
+fn head_size<B>(req: &hyper::Request<B>) -> usize {
+    // "GET /path HTTP/1.1\r\n", then "name: value\r\n" per header and "\r\n"
+    let target = req.uri().path_and_query().map_or(1, |p| p.as_str().len());
+    let request_line = req.method().as_str().len() + target + "  HTTP/1.1\r\n".len();
+    let headers: usize = req
+        .headers()
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + ": \r\n".len())
+        .sum();
+    request_line + headers + "\r\n".len()
+}
+
+/// Runs a hyper request through the application.
+async fn handle<A: Clone + Send + Sync + 'static>(
+    app: Arc<App>,
+    remote: ConnectInfo<A>,
+    limits: RequestLimits,
+    hyper_req: hyper::Request<hyper::body::Incoming>,
+) -> std::result::Result<hyper::Response<Body>, std::convert::Infallible> {
+    if limits.max_header_size.is_some_and(|max| head_size(&hyper_req) > max) {
+        let error = Error::Custom {
+            status: StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            message: "Request headers are too large".to_string(),
+        };
+        return Ok(error.into_response().into());
+    }
+
+    // Convert hyper request to our Request type; the body streams lazily
+    let mut req = match Request::try_from(hyper_req) {
+        Ok(req) => req,
+        Err(e) => return Ok(e.into_response().into()),
+    };
+    if let Some(timeout) = limits.body_timeout {
+        req.set_body_timeout(timeout);
+    }
+    req.extensions_mut().insert(remote);
+
+    // Handle the request through our pipeline
+    let response = match app.handle_request(req).await {
+        Ok(resp) => resp,
+        Err(e) => e.into_response(),
+    };
+
+    Ok(response.into())
+}
@@ -0,0 +1,338 @@
+//! Server-Sent Events
+//!
+//! [`Sse`] turns a stream of [`Event`]s into a `text/event-stream` response
+//! that is sent to the client as events are produced. Browsers consume it
+//! with `EventSource`, which reconnects on its own and reports the id of the
+//! last event it received in the `Last-Event-ID` header, available through
+//! the [`LastEventId`] extractor.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use futures_util::StreamExt;
+//! use ruffus::sse::{Event, KeepAlive, LastEventId, Sse};
+//! use ruffus::App;
+//! use std::time::Duration;
+//!
+//! let mut app = App::new();
+//!
+//! app.get("/ticks", |LastEventId(last): LastEventId| async move {
+//!     // Resume after the last tick the client saw
+//!     let start = last.and_then(|id| id.parse::<u64>().ok()).map_or(0, |id| id + 1);
+//!     let ticks = futures_util::stream::iter(start..).then(|n| async move {
+//!         tokio::time::sleep(Duration::from_secs(1)).await;
+//!         Ok::<_, std::convert::Infallible>(Event::default().id(n.to_string()).data(format!("tick {}", n)))
+//!     });
+//!     Sse::new(ticks).keep_alive(KeepAlive::new())
+//! });
+//! ```
+
+use async_trait::async_trait;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_core::Stream;
+use serde::Serialize;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+use crate::extractors::FromRequestParts;
+use crate::response::IntoResponse;
+use crate::{Request, Response, Result};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A Server-Sent Events response
+///
+/// Wraps a stream of `Result<Event, E>`. Each event is sent as soon as the
+/// stream yields it; an error ends the response by aborting the connection.
+/// The response has `Content-Type: text/event-stream` and
+/// `Cache-Control: no-cache`.
+pub struct Sse<S> {
+    stream: S,
+    keep_alive: Option<KeepAlive>,
+}
+
+impl<S, E> Sse<S>
+where
+    S: Stream<Item = std::result::Result<Event, E>> + Send + 'static,
+    E: Into<BoxError>,
+{
+    /// Creates an SSE response from a stream of events.
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            keep_alive: None,
+        }
+    }
+
+    /// Sends a comment whenever no event was sent for a while.
+    ///
+    /// Keeps proxies and load balancers from closing idle connections.
+    /// Disabled by default.
+    pub fn keep_alive(mut self, keep_alive: KeepAlive) -> Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+}
+
+impl<S, E> IntoResponse for Sse<S>
+where
+    S: Stream<Item = std::result::Result<Event, E>> + Send + 'static,
+    E: Into<BoxError>,
+{
+    fn into_response(self) -> Response {
+        let keep_alive = self.keep_alive.map(|keep_alive| {
+            let sleep = Box::pin(tokio::time::sleep(keep_alive.interval));
+            (keep_alive, sleep)
+        });
+        let body = SseStream {
+            events: Box::pin(self.stream),
+            keep_alive,
+        };
+
+        Response::stream(body)
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+    }
+}
+
+/// Encodes events and interleaves keep-alive comments
+struct SseStream<S> {
+    events: Pin<Box<S>>,
+    keep_alive: Option<(KeepAlive, Pin<Box<Sleep>>)>,
+}
+
+impl<S, E> Stream for SseStream<S>
+where
+    S: Stream<Item = std::result::Result<Event, E>>,
+    E: Into<BoxError>,
+{
+    type Item = std::result::Result<Bytes, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        match this.events.as_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok(event))) => {
+                if let Some((keep_alive, sleep)) = &mut this.keep_alive {
+                    sleep.as_mut().reset(Instant::now() + keep_alive.interval);
+                }
+                return Poll::Ready(Some(Ok(event.finalize())));
+            }
+            Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => {}
+        }
+
+        if let Some((keep_alive, sleep)) = &mut this.keep_alive {
+            if sleep.as_mut().poll(cx).is_ready() {
+                sleep.as_mut().reset(Instant::now() + keep_alive.interval);
+                return Poll::Ready(Some(Ok(keep_alive.comment.clone())));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+/// A single Server-Sent Event
+///
+/// Built from [`Event::default`] by setting fields. Data spanning several
+/// lines is sent as one `data:` field per line, which the client joins back
+/// with newlines.
+///
+/// # Examples
+///
+/// ```
+/// use ruffus::sse::Event;
+///
+/// let event = Event::default()
+///     .event("update")
+///     .id("42")
+///     .data("first line\nsecond line");
+/// assert_eq!(
+///     event.to_string(),
+///     "event: update\nid: 42\ndata: first line\ndata: second line\n\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Event {
+    buffer: BytesMut,
+}
+
+impl Event {
+    /// Sets the event type, which selects the `EventSource` listener.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `event` contains a line break.
+    pub fn event(mut self, event: impl AsRef<str>) -> Self {
+        self.field("event", single_line("event", event.as_ref()));
+        self
+    }
+
+    /// Sets the event id, which the client sends back as `Last-Event-ID` when
+    /// it reconnects.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` contains a line break or a NUL character.
+    pub fn id(mut self, id: impl AsRef<str>) -> Self {
+        let id = single_line("id", id.as_ref());
+        assert!(!id.contains('\0'), "SSE id must not contain NUL characters");
+        self.field("id", id);
+        self
+    }
+
+    /// Sets the data of the event. Each line becomes its own `data:` field.
+    pub fn data(mut self, data: impl AsRef<str>) -> Self {
+        for line in lines(data.as_ref()) {
+            self.field("data", line);
+        }
+        self
+    }
+
+    /// Sets the data of the event to `value` serialized as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be serialized to JSON.
+    pub fn json_data<T: Serialize>(self, value: &T) -> Result<Self> {
+        let json = serde_json::to_string(value).map_err(crate::Error::JsonSerializeError)?;
+        Ok(self.data(json))
+    }
+
+    /// Tells the client how long to wait before reconnecting.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.field("retry", &retry.as_millis().to_string());
+        self
+    }
+
+    /// Adds a comment, which clients ignore.
+    pub fn comment(mut self, comment: impl AsRef<str>) -> Self {
+        for line in lines(comment.as_ref()) {
+            self.field("", line);
+        }
+        self
+    }
+
+    fn field(&mut self, name: &str, value: &str) {
+        self.buffer.put_slice(name.as_bytes());
+        self.buffer.put_slice(b": ");
+        self.buffer.put_slice(value.as_bytes());
+        self.buffer.put_u8(b'\n');
+    }
+
+    /// Returns the wire format of the event, ending with the blank line that
+    /// dispatches it.
+    fn finalize(mut self) -> Bytes {
+        self.buffer.put_u8(b'\n');
+        self.buffer.freeze()
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let event = self.clone().finalize();
+        f.write_str(&String::from_utf8_lossy(&event))
+    }
+}
+
+fn single_line<'a>(field: &str, value: &'a str) -> &'a str {
+    assert!(
+        !value.contains(['\n', '\r']),
+        "SSE {} must not contain line breaks",
+        field
+    );
+    value
+}
+
+/// Splits at `\r\n`, `\r` and `\n`, keeping a trailing empty line.
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = Some(text);
+    std::iter::from_fn(move || {
+        let text = rest?;
+        match text.find(['\r', '\n']) {
+            Some(end) => {
+                let skip = if text[end..].starts_with("\r\n") { 2 } else { 1 };
+                rest = Some(&text[end + skip..]);
+                Some(&text[..end])
+            }
+            None => {
+                rest = None;
+                Some(text)
+            }
+        }
+    })
+}
+
+/// Keep-alive comments for an [`Sse`] response
+///
+/// # Examples
+///
+/// ```
+/// use ruffus::sse::KeepAlive;
+/// use std::time::Duration;
+///
+/// let keep_alive = KeepAlive::new()
+///     .interval(Duration::from_secs(30))
+///     .text("ping");
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeepAlive {
+    interval: Duration,
+    comment: Bytes,
+}
+
+impl KeepAlive {
+    /// Creates keep-alive settings sending an empty comment every 15 seconds.
+    pub fn new() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            comment: Bytes::from_static(b":\n\n"),
+        }
+    }
+
+    /// Sets how long the stream may stay idle before a comment is sent.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the text of the comment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `text` contains a line break.
+    pub fn text(mut self, text: impl AsRef<str>) -> Self {
+        let text = single_line("keep-alive text", text.as_ref());
+        self.comment = Bytes::from(format!(": {}\n\n", text));
+        self
+    }
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Extractor for the `Last-Event-ID` header
+///
+/// `EventSource` sends the id of the last event it received when it
+/// reconnects, so the stream can resume from there. Holds `None` on the
+/// first connection, or if the header isn't valid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastEventId(pub Option<String>);
+
+#[async_trait]
+impl FromRequestParts for LastEventId {
+    async fn from_request_parts(req: &mut Request) -> Result<Self> {
+        let id = req
+            .headers()
+            .get("last-event-id")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        Ok(LastEventId(id))
+    }
+}
@@ -0,0 +1,124 @@
+//! Shared application state
+//!
+//! State registered with [`App::with_state`](crate::App::with_state) is made
+//! available to every request and can be extracted in handlers with
+//! [`State<T>`]. Mounted routers can expose a part of that state as their own
+//! substate with [`Router::substate`](crate::Router::substate), using a
+//! [`FromRef`] projection.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::{App, Response, State};
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//! use std::sync::Arc;
+//!
+//! #[derive(Clone)]
+//! struct AppState {
+//!     hits: Arc<AtomicU64>,
+//! }
+//!
+//! let mut app = App::with_state(AppState { hits: Arc::new(AtomicU64::new(0)) });
+//!
+//! app.get("/hits", |State(state): State<AppState>| async move {
+//!     let hits = state.hits.fetch_add(1, Ordering::Relaxed) + 1;
+//!     Response::text(hits.to_string())
+//! });
+//! ```
+
+use async_trait::async_trait;
+use std::marker::PhantomData;
+
+use crate::extractors::FromRequestParts;
+use crate::{Error, Middleware, Next, Request, Response, Result};
+
+/// Extractor for shared state
+///
+/// Clones the state of type `T` registered with
+/// [`App::with_state`](crate::App::with_state), or projected by
+/// [`Router::substate`](crate::Router::substate) for routes of that router.
+/// Extraction fails with a 500 error if no state of that type is available.
+///
+/// Middleware can read the same state with `req.extensions().get::<T>()`.
+pub struct State<T>(pub T);
+
+#[async_trait]
+impl<T> FromRequestParts for State<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    async fn from_request_parts(req: &mut Request) -> Result<Self> {
+        req.extensions()
+            .get::<T>()
+            .cloned()
+            .map(State)
+            .ok_or_else(|| {
+                Error::InternalServerError(format!(
+                    "No state of type `{}` was registered",
+                    std::any::type_name::<T>()
+                ))
+            })
+    }
+}
+
+/// Trait for deriving a substate from a reference to a parent state
+///
+/// Every `Clone` type can be derived from itself. Implement this trait to
+/// let a router use part of the application state as its own state.
+///
+/// # Examples
+///
+/// ```
+/// use ruffus::FromRef;
+///
+/// #[derive(Clone)]
+/// struct Database;
+///
+/// #[derive(Clone)]
+/// struct AppState {
+///     db: Database,
+///     api_key: String,
+/// }
+///
+/// impl FromRef<AppState> for Database {
+///     fn from_ref(state: &AppState) -> Self {
+///         state.db.clone()
+///     }
+/// }
+/// ```
+pub trait FromRef<T> {
+    /// Derives this value from the parent state.
+    fn from_ref(input: &T) -> Self;
+}
+
+impl<T: Clone> FromRef<T> for T {
+    fn from_ref(input: &T) -> Self {
+        input.clone()
+    }
+}
+
+/// Middleware inserting the substate `S` projected from the state `P`.
+pub(crate) struct ProjectState<P, S> {
+    _marker: PhantomData<fn(&P) -> S>,
+}
+
+impl<P, S> ProjectState<P, S> {
+    pub(crate) fn new() -> Self {
+        Self { _marker: PhantomData }
+    }
+}
+
+#[async_trait]
+impl<P, S> Middleware for ProjectState<P, S>
+where
+    P: Send + Sync + 'static,
+    S: FromRef<P> + Clone + Send + Sync + 'static,
+{
+    async fn handle(&self, mut req: Request, next: Next) -> Result<Response> {
+        if let Some(parent) = req.extensions().get::<P>() {
+            let state = S::from_ref(parent);
+            req.extensions_mut().insert(state);
+        }
+        next.run(req).await
+    }
+}
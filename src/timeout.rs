@@ -0,0 +1,88 @@
+//! Request timeouts
+//!
+//! The [`Timeout`] middleware bounds how long the rest of the pipeline may
+//! take to produce a response. When the time is up, the handler's future is
+//! dropped, cancelling it at its current `.await`, and the client gets
+//! `503 Service Unavailable`, or the status set with [`Timeout::status`].
+//!
+//! Register it with [`App::use_middleware`](crate::App::use_middleware) to
+//! bound every request, or on a [`Router`](crate::Router) to give some routes
+//! more or less time than the rest.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::timeout::Timeout;
+//! use ruffus::{App, Router};
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! let mut app = App::new();
+//! app.use_middleware(Arc::new(Timeout::new(Duration::from_secs(10))));
+//!
+//! // Reports may take longer, behind a gateway that expects a 504
+//! let mut reports = Router::new("/reports");
+//! reports.use_middleware(Arc::new(
+//!     Timeout::new(Duration::from_secs(60)).status(http::StatusCode::GATEWAY_TIMEOUT),
+//! ));
+//! app.mount("", reports);
+//! ```
+//!
+//! Middleware runs outermost first, so a timeout registered on the app also
+//! bounds the routes of a router with a longer one.
+
+use async_trait::async_trait;
+use http::StatusCode;
+use std::time::Duration;
+
+use crate::{Error, Middleware, Next, Request, Response, Result};
+
+/// Middleware failing requests whose response takes too long
+///
+/// The timeout covers the middleware registered after it and the handler,
+/// up to the point where the response is returned. Streamed bodies, such as
+/// server-sent events or files, are sent afterwards and aren't bounded by it.
+///
+/// Tasks the handler spawned keep running after a timeout, since only the
+/// handler's own future is cancelled.
+#[derive(Debug, Clone)]
+pub struct Timeout {
+    duration: Duration,
+    status: StatusCode,
+}
+
+impl Timeout {
+    /// Fails requests taking longer than `duration` with
+    /// `503 Service Unavailable`.
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            status: StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Sets the status of timed out requests, such as
+    /// `504 Gateway Timeout` for apps behind a proxy.
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Returns how long requests may take.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+#[async_trait]
+impl Middleware for Timeout {
+    async fn handle(&self, req: Request, next: Next) -> Result<Response> {
+        match tokio::time::timeout(self.duration, next.run(req)).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::Custom {
+                status: self.status,
+                message: format!("Request timed out after {:?}", self.duration),
+            }),
+        }
+    }
+}
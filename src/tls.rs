@@ -0,0 +1,294 @@
+//! TLS configuration for serving HTTPS
+//!
+//! Available with the `tls` cargo feature. A [`TlsConfig`] holds the
+//! certificate chain and private key used by
+//! [`App::listen_tls`](crate::App::listen_tls), loaded from PEM data. The
+//! server negotiates HTTP/2 or HTTP/1.1 with clients through ALPN.
+//!
+//! Certificates loaded from files can be replaced without a restart, either
+//! by calling [`TlsConfig::reload`] or by letting the server watch the files
+//! with [`TlsConfig::reload_interval`]. New connections use the new
+//! certificate; established connections are unaffected.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::{App, Request, Response, TlsConfig};
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut app = App::new();
+//!     app.get("/", |_req: Request| async { Response::text("Hello!".to_string()) });
+//!
+//!     let tls = TlsConfig::from_pem_files("cert.pem", "key.pem")
+//!         .unwrap()
+//!         .reload_interval(Duration::from_secs(60));
+//!
+//!     app.listen_tls("0.0.0.0:443", tls).await.unwrap();
+//! }
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+use crate::{Error, Result};
+
+/// Certificate, key and protocol settings for a TLS listener.
+///
+/// Cloning a `TlsConfig` is cheap, and clones share the loaded certificate,
+/// so a clone kept aside can [`reload`](TlsConfig::reload) the certificate of
+/// a running server.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    certificate: Arc<ReloadableCertificate>,
+    alpn_protocols: Vec<Vec<u8>>,
+    reload_interval: Option<Duration>,
+    handshake_timeout: Duration,
+}
+
+/// How long a client has to complete the TLS handshake by default
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl TlsConfig {
+    /// Creates a configuration from a PEM certificate chain and private key.
+    ///
+    /// The chain starts with the server certificate, followed by any
+    /// intermediates. The key may be PKCS#8, PKCS#1 (RSA) or SEC1 (EC).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the PEM data can't be parsed or the key doesn't
+    /// match the certificate.
+    pub fn from_pem(cert_chain: &[u8], key: &[u8]) -> Result<Self> {
+        let provider = Arc::new(ring::default_provider());
+        let key = load_certified_key(cert_chain, key, &provider)?;
+        Ok(Self::with_certificate(ReloadableCertificate {
+            current: RwLock::new(Arc::new(key)),
+            files: None,
+            provider,
+        }))
+    }
+
+    /// Creates a configuration from PEM files holding the certificate chain
+    /// and the private key.
+    ///
+    /// The files are read again on [`reload`](TlsConfig::reload).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either file can't be read or parsed, or the key
+    /// doesn't match the certificate.
+    pub fn from_pem_files(cert_path: impl AsRef<Path>, key_path: impl AsRef<Path>) -> Result<Self> {
+        let files = CertificateFiles {
+            cert_path: cert_path.as_ref().to_path_buf(),
+            key_path: key_path.as_ref().to_path_buf(),
+            modified: Mutex::new(None),
+        };
+        // Record the modification times the first load corresponds to
+        files.changed();
+        let provider = Arc::new(ring::default_provider());
+        let key = files.load(&provider)?;
+        Ok(Self::with_certificate(ReloadableCertificate {
+            current: RwLock::new(Arc::new(key)),
+            files: Some(files),
+            provider,
+        }))
+    }
+
+    fn with_certificate(certificate: ReloadableCertificate) -> Self {
+        Self {
+            certificate: Arc::new(certificate),
+            alpn_protocols: vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            reload_interval: None,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        }
+    }
+
+    /// Sets the protocols offered through ALPN, in order of preference.
+    ///
+    /// Defaults to `["h2", "http/1.1"]`. Pass `&["http/1.1"]` to keep TLS
+    /// clients on HTTP/1.1.
+    pub fn alpn_protocols(mut self, protocols: &[&str]) -> Self {
+        self.alpn_protocols = protocols.iter().map(|p| p.as_bytes().to_vec()).collect();
+        self
+    }
+
+    /// Sets how long a client may take to complete the TLS handshake.
+    ///
+    /// Connections that haven't finished the handshake in time are closed,
+    /// so idle sockets can't hold on to a
+    /// [`max_connections`](crate::Server::max_connections) slot. Defaults to
+    /// 10 seconds.
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Returns how long a client may take to complete the TLS handshake.
+    pub(crate) fn get_handshake_timeout(&self) -> Duration {
+        self.handshake_timeout
+    }
+
+    /// Checks the certificate and key files for changes at this interval
+    /// while the server runs, and reloads them when they change.
+    ///
+    /// A reload that fails is logged and the previous certificate stays in
+    /// use. Has no effect on configurations created with
+    /// [`from_pem`](TlsConfig::from_pem).
+    pub fn reload_interval(mut self, interval: Duration) -> Self {
+        self.reload_interval = Some(interval);
+        self
+    }
+
+    /// Reads the certificate and key files again and uses them for new
+    /// connections.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration wasn't created with
+    /// [`from_pem_files`](TlsConfig::from_pem_files), or if the files can't
+    /// be loaded. The previous certificate stays in use on error.
+    pub fn reload(&self) -> Result<()> {
+        let files = self.certificate.files.as_ref().ok_or_else(|| {
+            Error::InternalServerError("TLS configuration was not loaded from files".to_string())
+        })?;
+        let key = files.load(&self.certificate.provider)?;
+        *self.certificate.current.write().unwrap() = Arc::new(key);
+        Ok(())
+    }
+
+    /// Builds the acceptor used to complete TLS handshakes.
+    pub(crate) fn acceptor(&self) -> Result<TlsAcceptor> {
+        let mut config = ServerConfig::builder_with_provider(self.certificate.provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| Error::InternalServerError(format!("Invalid TLS configuration: {}", e)))?
+            .with_no_client_auth()
+            .with_cert_resolver(self.certificate.clone());
+        config.alpn_protocols = self.alpn_protocols.clone();
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+
+    /// Reloads the certificate files whenever they change, until dropped.
+    ///
+    /// Returns `None` if there is nothing to watch.
+    pub(crate) fn watch(&self) -> Option<impl std::future::Future<Output = ()> + Send + 'static> {
+        let interval = self.reload_interval?;
+        self.certificate.files.as_ref()?;
+        let certificate = self.certificate.clone();
+
+        Some(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                ticks.tick().await;
+                // Checking and reading the files blocks, so keep it off the
+                // runtime's workers
+                let certificate = certificate.clone();
+                let _ = tokio::task::spawn_blocking(move || certificate.reload_if_changed()).await;
+            }
+        })
+    }
+}
+
+/// The certificate currently served, swapped out on reload.
+struct ReloadableCertificate {
+    current: RwLock<Arc<CertifiedKey>>,
+    files: Option<CertificateFiles>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ReloadableCertificate {
+    /// Reloads the certificate if its files changed since the last check.
+    ///
+    /// A reload that fails is logged and the current certificate is kept.
+    fn reload_if_changed(&self) {
+        let Some(files) = &self.files else {
+            return;
+        };
+        if !files.changed() {
+            return;
+        }
+        match files.load(&self.provider) {
+            Ok(key) => *self.current.write().unwrap() = Arc::new(key),
+            Err(e) => eprintln!("Failed to reload TLS certificate: {}", e),
+        }
+    }
+}
+
+impl fmt::Debug for ReloadableCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadableCertificate")
+            .field("files", &self.files)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ResolvesServerCert for ReloadableCertificate {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+/// Paths of the PEM files a certificate was loaded from.
+#[derive(Debug)]
+struct CertificateFiles {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    /// Modification times seen by the last call to `changed`
+    modified: Mutex<Option<(SystemTime, SystemTime)>>,
+}
+
+impl CertificateFiles {
+    fn load(&self, provider: &CryptoProvider) -> Result<CertifiedKey> {
+        let cert_chain = read_file(&self.cert_path)?;
+        let key = read_file(&self.key_path)?;
+        load_certified_key(&cert_chain, &key, provider)
+    }
+
+    /// Returns true if either file was modified since the last call.
+    fn changed(&self) -> bool {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let Some(current) = modified(&self.cert_path).zip(modified(&self.key_path)) else {
+            return false;
+        };
+
+        let mut seen = self.modified.lock().unwrap();
+        let changed = seen.is_some_and(|seen| seen != current);
+        *seen = Some(current);
+        changed
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path)
+        .map_err(|e| Error::InternalServerError(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+/// Parses a PEM certificate chain and private key into a signing key.
+fn load_certified_key(cert_chain: &[u8], key: &[u8], provider: &CryptoProvider) -> Result<CertifiedKey> {
+    let cert_chain = CertificateDer::pem_slice_iter(cert_chain)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| Error::InternalServerError(format!("Invalid TLS certificate: {}", e)))?;
+    if cert_chain.is_empty() {
+        return Err(Error::InternalServerError(
+            "Invalid TLS certificate: no certificate found".to_string(),
+        ));
+    }
+
+    let key = PrivateKeyDer::from_pem_slice(key)
+        .map_err(|e| Error::InternalServerError(format!("Invalid TLS private key: {}", e)))?;
+
+    CertifiedKey::from_der(cert_chain, key, provider)
+        .map_err(|e| Error::InternalServerError(format!("Invalid TLS certificate: {}", e)))
+}
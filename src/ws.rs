@@ -0,0 +1,361 @@
+//! WebSockets
+//!
+//! Handlers accept WebSocket connections with the [`WebSocketUpgrade`]
+//! extractor, which checks the HTTP/1.1 upgrade request. Calling
+//! [`WebSocketUpgrade::on_upgrade`] answers with `101 Switching Protocols`
+//! and runs a callback with the [`WebSocket`] once hyper hands over the
+//! connection.
+//!
+//! The extractor can be combined with any other extractor, so the callback
+//! can use path parameters, state or extensions set by middleware on the
+//! upgrade request.
+//!
+//! Upgraded connections run in their own task: they don't count towards
+//! [`Server::max_connections`](crate::Server::max_connections) and graceful
+//! shutdown doesn't wait for them.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ruffus::ws::Message;
+//! use ruffus::{App, Path, WebSocketUpgrade};
+//!
+//! let mut app = App::new();
+//!
+//! app.get("/chat/:room", |Path(room): Path<String>, ws: WebSocketUpgrade| async move {
+//!     ws.on_upgrade(move |mut socket| async move {
+//!         while let Some(Ok(message)) = socket.recv().await {
+//!             if let Message::Text(text) = message {
+//!                 let reply = Message::Text(format!("[{}] {}", room, text));
+//!                 if socket.send(reply).await.is_err() {
+//!                     break;
+//!                 }
+//!             }
+//!         }
+//!     })
+//! });
+//! ```
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_util::SinkExt;
+use http::header::{self, HeaderMap, HeaderValue};
+use http::StatusCode;
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use hyper_util::rt::TokioIo;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio_tungstenite::tungstenite::{self, protocol::Role, protocol::WebSocketConfig};
+use tokio_tungstenite::WebSocketStream;
+
+use crate::extractors::FromRequestParts;
+use crate::{Error, Request, Response, Result};
+
+/// Extractor for a WebSocket upgrade request
+///
+/// Extraction fails with `405 Method Not Allowed` for methods other than
+/// GET, `426 Upgrade Required` if the request doesn't ask for a WebSocket,
+/// and `400 Bad Request` if the handshake headers are invalid. Only HTTP/1.1
+/// connections can be upgraded.
+pub struct WebSocketUpgrade {
+    key: HeaderValue,
+    on_upgrade: OnUpgrade,
+    requested_protocols: Option<HeaderValue>,
+    protocol: Option<HeaderValue>,
+    config: WebSocketConfig,
+}
+
+impl WebSocketUpgrade {
+    /// Sets the subprotocols the server supports, in order of preference.
+    ///
+    /// The first protocol the client also offered in
+    /// `Sec-WebSocket-Protocol` is selected and sent back to it.
+    pub fn protocols<I>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let requested = self
+            .requested_protocols
+            .as_ref()
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        self.protocol = protocols
+            .into_iter()
+            .find(|protocol| requested.split(',').any(|offered| offered.trim() == protocol.as_ref()))
+            .and_then(|protocol| HeaderValue::from_str(protocol.as_ref()).ok());
+        self
+    }
+
+    /// Returns the subprotocol selected by [`protocols`](Self::protocols).
+    pub fn selected_protocol(&self) -> Option<&str> {
+        self.protocol.as_ref().and_then(|value| value.to_str().ok())
+    }
+
+    /// Sets the largest message, after reassembling fragments, that the
+    /// socket accepts. Defaults to 64 MiB.
+    pub fn max_message_size(mut self, max: usize) -> Self {
+        self.config.max_message_size = Some(max);
+        self
+    }
+
+    /// Sets the largest single frame the socket accepts. Defaults to 16 MiB.
+    pub fn max_frame_size(mut self, max: usize) -> Self {
+        self.config.max_frame_size = Some(max);
+        self
+    }
+
+    /// Completes the handshake and runs `callback` with the socket.
+    ///
+    /// Returns the `101 Switching Protocols` response, which the handler must
+    /// return for the upgrade to happen.
+    pub fn on_upgrade<F, Fut>(self, callback: F) -> Response
+    where
+        F: FnOnce(WebSocket) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let protocol = self.protocol.as_ref().and_then(|value| value.to_str().ok()).map(str::to_string);
+        let config = self.config;
+        let on_upgrade = self.on_upgrade;
+        tokio::spawn(async move {
+            let upgraded = match on_upgrade.await {
+                Ok(upgraded) => upgraded,
+                Err(err) => {
+                    eprintln!("Error upgrading connection: {}", err);
+                    return;
+                }
+            };
+            let inner = WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, Some(config)).await;
+            callback(WebSocket { inner, protocol }).await;
+        });
+
+        let accept = tungstenite::handshake::derive_accept_key(self.key.as_bytes());
+        let mut response = Response::new()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header("Connection", "upgrade")
+            .header("Upgrade", "websocket")
+            .header("Sec-WebSocket-Accept", &accept);
+        if let Some(protocol) = self.protocol.as_ref().and_then(|value| value.to_str().ok()) {
+            response = response.header("Sec-WebSocket-Protocol", protocol);
+        }
+        response
+    }
+}
+
+#[async_trait]
+impl FromRequestParts for WebSocketUpgrade {
+    async fn from_request_parts(req: &mut Request) -> Result<Self> {
+        if req.method() != http::Method::GET {
+            return Err(Error::Custom {
+                status: StatusCode::METHOD_NOT_ALLOWED,
+                message: "WebSocket upgrades must use GET".to_string(),
+            });
+        }
+
+        let headers = req.headers();
+        if !header_has_token(headers, header::CONNECTION, "upgrade")
+            || !header_has_token(headers, header::UPGRADE, "websocket")
+        {
+            return Err(Error::Custom {
+                status: StatusCode::UPGRADE_REQUIRED,
+                message: "Expected a WebSocket upgrade request".to_string(),
+            });
+        }
+        if headers.get(header::SEC_WEBSOCKET_VERSION).map(HeaderValue::as_bytes) != Some(b"13") {
+            return Err(Error::BadRequest("Unsupported Sec-WebSocket-Version".to_string()));
+        }
+        let key = headers
+            .get(header::SEC_WEBSOCKET_KEY)
+            .cloned()
+            .ok_or_else(|| Error::BadRequest("Missing Sec-WebSocket-Key".to_string()))?;
+        let requested_protocols = headers.get(header::SEC_WEBSOCKET_PROTOCOL).cloned();
+
+        let on_upgrade = req
+            .extensions_mut()
+            .remove::<OnUpgrade>()
+            .ok_or_else(|| Error::InternalServerError("Connection can't be upgraded".to_string()))?;
+
+        Ok(WebSocketUpgrade {
+            key,
+            on_upgrade,
+            requested_protocols,
+            protocol: None,
+            config: WebSocketConfig::default(),
+        })
+    }
+}
+
+/// Returns whether a comma separated header lists `token`, ignoring case.
+fn header_has_token(headers: &HeaderMap, name: header::HeaderName, token: &str) -> bool {
+    headers.get_all(name).iter().any(|value| {
+        value
+            .to_str()
+            .map(|value| value.split(',').any(|part| part.trim().eq_ignore_ascii_case(token)))
+            .unwrap_or(false)
+    })
+}
+
+/// An established WebSocket connection
+///
+/// Fragmented messages are reassembled before [`recv`](Self::recv) returns
+/// them. Pings are answered automatically, and a close frame from the client
+/// is echoed back before `recv` returns `None`.
+pub struct WebSocket {
+    inner: WebSocketStream<TokioIo<Upgraded>>,
+    protocol: Option<String>,
+}
+
+impl WebSocket {
+    /// Receives the next message, or `None` once the connection is closed.
+    pub async fn recv(&mut self) -> Option<Result<Message>> {
+        futures_util::StreamExt::next(self).await
+    }
+
+    /// Sends a message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection is closed or writing to it fails.
+    pub async fn send(&mut self, message: Message) -> Result<()> {
+        self.inner.send(message.into()).await.map_err(ws_error)
+    }
+
+    /// Sends a close frame. The client answers with its own close frame,
+    /// after which [`recv`](Self::recv) returns `None`.
+    pub async fn close(&mut self, frame: Option<CloseFrame>) -> Result<()> {
+        self.send(Message::Close(frame)).await
+    }
+
+    /// Returns the subprotocol selected during the handshake.
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+}
+
+impl Stream for WebSocket {
+    type Item = Result<Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let message = match futures_core::ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+                Some(Ok(message)) => message,
+                Some(Err(err)) => return Poll::Ready(Some(Err(ws_error(err)))),
+                None => return Poll::Ready(None),
+            };
+            // Raw frames are only produced when writing
+            if let Some(message) = Message::from_tungstenite(message) {
+                return Poll::Ready(Some(Ok(message)));
+            }
+        }
+    }
+}
+
+fn ws_error(err: tungstenite::Error) -> Error {
+    match err {
+        tungstenite::Error::Protocol(_) | tungstenite::Error::Capacity(_) | tungstenite::Error::Utf8 => {
+            Error::BadRequest(format!("WebSocket error: {}", err))
+        }
+        err => Error::InternalServerError(format!("WebSocket error: {}", err)),
+    }
+}
+
+/// A WebSocket message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// A UTF-8 text message
+    Text(String),
+    /// A binary message
+    Binary(Bytes),
+    /// A ping, with at most 125 bytes of payload
+    Ping(Bytes),
+    /// A pong, with at most 125 bytes of payload
+    Pong(Bytes),
+    /// A close message, with an optional code and reason
+    Close(Option<CloseFrame>),
+}
+
+impl Message {
+    fn from_tungstenite(message: tungstenite::Message) -> Option<Self> {
+        Some(match message {
+            tungstenite::Message::Text(text) => Message::Text(text.as_str().to_string()),
+            tungstenite::Message::Binary(data) => Message::Binary(data),
+            tungstenite::Message::Ping(data) => Message::Ping(data),
+            tungstenite::Message::Pong(data) => Message::Pong(data),
+            tungstenite::Message::Close(frame) => Message::Close(frame.map(|frame| CloseFrame {
+                code: frame.code.into(),
+                reason: frame.reason.as_str().to_string(),
+            })),
+            tungstenite::Message::Frame(_) => return None,
+        })
+    }
+}
+
+impl From<Message> for tungstenite::Message {
+    fn from(message: Message) -> Self {
+        match message {
+            Message::Text(text) => tungstenite::Message::text(text),
+            Message::Binary(data) => tungstenite::Message::Binary(data),
+            Message::Ping(data) => tungstenite::Message::Ping(data),
+            Message::Pong(data) => tungstenite::Message::Pong(data),
+            Message::Close(frame) => tungstenite::Message::Close(frame.map(|frame| tungstenite::protocol::CloseFrame {
+                code: frame.code.into(),
+                reason: frame.reason.into(),
+            })),
+        }
+    }
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Message::Text(text)
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Self {
+        Message::Text(text.to_string())
+    }
+}
+
+impl From<Bytes> for Message {
+    fn from(data: Bytes) -> Self {
+        Message::Binary(data)
+    }
+}
+
+impl From<Vec<u8>> for Message {
+    fn from(data: Vec<u8>) -> Self {
+        Message::Binary(Bytes::from(data))
+    }
+}
+
+/// The code and reason of a close message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseFrame {
+    /// The close code, see [`close_code`]
+    pub code: u16,
+    /// Why the connection is being closed
+    pub reason: String,
+}
+
+/// Close codes defined by RFC 6455
+pub mod close_code {
+    /// The purpose of the connection has been fulfilled
+    pub const NORMAL: u16 = 1000;
+    /// The server is going down or the client navigated away
+    pub const AWAY: u16 = 1001;
+    /// The peer violated the protocol
+    pub const PROTOCOL: u16 = 1002;
+    /// The peer sent a kind of data that can't be accepted
+    pub const UNSUPPORTED: u16 = 1003;
+    /// A text message wasn't valid UTF-8
+    pub const INVALID: u16 = 1007;
+    /// A message violated the endpoint's policy
+    pub const POLICY: u16 = 1008;
+    /// A message was too big to process
+    pub const SIZE: u16 = 1009;
+    /// The server hit an unexpected condition
+    pub const ERROR: u16 = 1011;
+}
@@ -39,7 +39,7 @@ fn prop_handler_responses_sent_correctly(
     use tokio::runtime::Runtime;
     
     // Filter out invalid status codes and header values
-    if status_code < 100 || status_code > 599 {
+    if !(100..=599).contains(&status_code) {
         return true; // Skip invalid status codes
     }
     
@@ -69,12 +69,12 @@ fn prop_handler_responses_sent_correctly(
         let expected_header_key = header_key.clone();
         let expected_header_value = header_value.clone();
         
-        app.get("/test", move |_req| {
+        app.get("/test", move |_req: Request| {
             let body = expected_body.clone();
             let key = expected_header_key.clone();
             let val = expected_header_value.clone();
             async move {
-                Ok(Response::new()
+                Ok::<_, ruffus::Error>(Response::new()
                     .status(status)
                     .header(&key, &val)
                     .body(body))
@@ -102,7 +102,7 @@ fn prop_handler_responses_sent_correctly(
             .unwrap_or(false);
         
         // Verify the response has the correct body
-        let body_matches = response.get_body() == &bytes::Bytes::from(body_text);
+        let body_matches = response.buffered_body().unwrap() == &bytes::Bytes::from(body_text);
         
         status_matches && header_matches && body_matches
     })
@@ -125,7 +125,12 @@ fn prop_server_binds_to_specified_port(port: u16) -> bool {
     if port < 1024 || port == 0 {
         return true;
     }
-    
+
+    // Skip ports already in use, e.g. by client sockets of other tests
+    if std::net::TcpListener::bind(("127.0.0.1", port)).is_err() {
+        return true;
+    }
+
     let rt = Runtime::new().unwrap();
     
     rt.block_on(async {
@@ -180,14 +185,14 @@ fn prop_async_handlers_execute_asynchronously(delay_ms: u8) -> bool {
         let executed_clone = executed.clone();
         
         // Register an async handler that performs an async operation
-        app.get("/async", move |_req| {
+        app.get("/async", move |_req: Request| {
             let executed = executed_clone.clone();
             let delay = Duration::from_millis(delay_ms as u64);
             async move {
                 // Simulate async work
                 tokio::time::sleep(delay).await;
                 executed.store(true, Ordering::SeqCst);
-                Ok(Response::text("async done".to_string()))
+                Ok::<_, ruffus::Error>(Response::text("async done".to_string()))
             }
         });
         
@@ -230,7 +235,8 @@ fn test_async_handlers_execute_asynchronously_property() {
 // **Validates: Requirements 10.2**
 fn prop_concurrent_requests_handled_concurrently(num_requests: u8) -> bool {
     use tokio::runtime::Runtime;
-    use std::time::{Duration, Instant};
+    use tokio::sync::Barrier;
+    use std::time::Duration;
     use std::sync::Arc;
     use std::sync::atomic::{AtomicUsize, Ordering};
     
@@ -240,25 +246,25 @@ fn prop_concurrent_requests_handled_concurrently(num_requests: u8) -> bool {
     let rt = Runtime::new().unwrap();
     
     rt.block_on(async {
-        let app = Arc::new(App::new());
         let counter = Arc::new(AtomicUsize::new(0));
+        // Every handler waits here until all of them are in flight at once,
+        // which can only happen if requests are handled concurrently
+        let barrier = Arc::new(Barrier::new(num_requests as usize));
         
-        // Register a handler that simulates some work
         let counter_clone = counter.clone();
         let mut app_mut = App::new();
-        app_mut.get("/concurrent", move |_req| {
+        app_mut.get("/concurrent", move |_req: Request| {
             let counter = counter_clone.clone();
+            let barrier = barrier.clone();
             async move {
                 counter.fetch_add(1, Ordering::SeqCst);
-                // Simulate some async work
-                tokio::time::sleep(Duration::from_millis(10)).await;
-                Ok(Response::text("done".to_string()))
+                barrier.wait().await;
+                Ok::<_, ruffus::Error>(Response::text("done".to_string()))
             }
         });
         let app = Arc::new(app_mut);
         
         // Create multiple concurrent requests
-        let start = Instant::now();
         let mut handles = vec![];
         
         for _ in 0..num_requests {
@@ -275,34 +281,24 @@ fn prop_concurrent_requests_handled_concurrently(num_requests: u8) -> bool {
             handles.push(handle);
         }
         
-        // Wait for all requests to complete
-        let mut all_succeeded = true;
-        for handle in handles {
-            if let Ok(result) = handle.await {
-                if result.is_err() {
+        // Wait for all requests to complete; sequential handling would leave
+        // the first request stuck at the barrier, so give up eventually
+        let all_succeeded = tokio::time::timeout(Duration::from_secs(10), async {
+            let mut all_succeeded = true;
+            for handle in handles {
+                if !matches!(handle.await, Ok(Ok(_))) {
                     all_succeeded = false;
                 }
-            } else {
-                all_succeeded = false;
             }
-        }
-        
-        let elapsed = start.elapsed();
+            all_succeeded
+        })
+        .await
+        .unwrap_or(false);
         
         // Verify all requests were handled
         let all_handled = counter.load(Ordering::SeqCst) == num_requests as usize;
         
-        // Verify concurrent execution: if requests were truly concurrent,
-        // total time should be less than sequential execution time
-        // Sequential would be: num_requests * 10ms
-        // Concurrent should be close to: 10ms (with some overhead)
-        let concurrent_execution = if num_requests > 1 {
-            elapsed < Duration::from_millis((num_requests as u64 * 10) - 5)
-        } else {
-            true // Single request doesn't test concurrency
-        };
-        
-        all_succeeded && all_handled && concurrent_execution
+        all_succeeded && all_handled
     })
 }
 
@@ -370,7 +366,7 @@ fn prop_async_middleware_completes_before_proceeding(delay_ms: u8) -> bool {
         // Register handler that records its execution time
         let handler_ts = handler_timestamp.clone();
         let mw_completed = middleware_completed.clone();
-        app.get("/test", move |_req| {
+        app.get("/test", move |_req: Request| {
             let handler_ts = handler_ts.clone();
             let mw_completed = mw_completed.clone();
             async move {
@@ -385,7 +381,7 @@ fn prop_async_middleware_completes_before_proceeding(delay_ms: u8) -> bool {
                 let middleware_done = mw_completed.load(Ordering::SeqCst);
                 
                 if middleware_done {
-                    Ok(Response::text("success".to_string()))
+                    Ok::<_, ruffus::Error>(Response::text("success".to_string()))
                 } else {
                     Ok(Response::text("middleware not completed".to_string()))
                 }
@@ -427,3 +423,263 @@ fn test_async_middleware_completes_before_proceeding_property() {
         .tests(100)
         .quickcheck(prop_async_middleware_completes_before_proceeding as fn(u8) -> bool);
 }
+
+// Middleware that records its label each time it runs
+struct LabelMiddleware {
+    label: &'static str,
+    log: std::sync::Arc<std::sync::Mutex<Vec<&'static str>>>,
+}
+
+#[async_trait::async_trait]
+impl ruffus::Middleware for LabelMiddleware {
+    async fn handle(&self, req: Request, next: ruffus::Next) -> ruffus::Result<Response> {
+        self.log.lock().unwrap().push(self.label);
+        next.run(req).await
+    }
+}
+
+// **Feature: rust-web-framework, Property 37: Router middleware only runs for its own routes**
+// **Validates: Requirements 7.4**
+fn prop_router_middleware_runs_only_for_its_routes(public_hits: u8, admin_hits: u8) -> bool {
+    use ruffus::Router;
+    use std::sync::{Arc, Mutex};
+    use tokio::runtime::Runtime;
+
+    let public_hits = public_hits % 5;
+    let admin_hits = admin_hits % 5;
+
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let mut admin = Router::new("/admin");
+        admin.use_middleware(Arc::new(LabelMiddleware { label: "admin", log: log.clone() }));
+        admin.get("/stats", |_req: Request| async { Ok::<_, ruffus::Error>(Response::text("stats".to_string())) });
+
+        let mut app = App::new();
+        app.get("/public", |_req: Request| async { Ok::<_, ruffus::Error>(Response::text("public".to_string())) });
+        app.mount("", admin);
+
+        for _ in 0..public_hits {
+            let req = Request::new(
+                http::Method::GET,
+                "/public".parse().unwrap(),
+                http::HeaderMap::new(),
+                bytes::Bytes::new(),
+            );
+            if app.handle_request(req).await.is_err() {
+                return false;
+            }
+        }
+
+        for _ in 0..admin_hits {
+            let req = Request::new(
+                http::Method::GET,
+                "/admin/stats".parse().unwrap(),
+                http::HeaderMap::new(),
+                bytes::Bytes::new(),
+            );
+            if app.handle_request(req).await.is_err() {
+                return false;
+            }
+        }
+
+        // Only the /admin requests should have gone through the admin middleware
+        let runs = log.lock().unwrap().len();
+        runs == admin_hits as usize && app.middleware().is_empty()
+    })
+}
+
+#[test]
+fn test_router_middleware_runs_only_for_its_routes_property() {
+    QuickCheck::new()
+        .tests(100)
+        .quickcheck(prop_router_middleware_runs_only_for_its_routes as fn(u8, u8) -> bool);
+}
+
+// **Feature: rust-web-framework, Property 38: Nested router middleware composes in mount order**
+// **Validates: Requirements 4.2, 7.3, 7.4**
+fn prop_nested_router_middleware_composes_in_mount_order(nested_first: bool) -> bool {
+    use ruffus::Router;
+    use std::sync::{Arc, Mutex};
+    use tokio::runtime::Runtime;
+
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let mut users = Router::new("/users");
+        users.use_middleware(Arc::new(LabelMiddleware { label: "users", log: log.clone() }));
+        users.get("/:id", |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) });
+
+        let mut api = Router::new("/api");
+        // Middleware registered before or after mounting still wraps the nested routes
+        if nested_first {
+            api.mount("", users);
+            api.use_middleware(Arc::new(LabelMiddleware { label: "api", log: log.clone() }));
+        } else {
+            api.use_middleware(Arc::new(LabelMiddleware { label: "api", log: log.clone() }));
+            api.mount("", users);
+        }
+        api.get("/health", |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) });
+
+        let mut app = App::new();
+        app.use_middleware(Arc::new(LabelMiddleware { label: "app", log: log.clone() }));
+        app.mount("", api);
+
+        let req = Request::new(
+            http::Method::GET,
+            "/api/users/7".parse().unwrap(),
+            http::HeaderMap::new(),
+            bytes::Bytes::new(),
+        );
+        let nested_ok = app.handle_request(req).await.is_ok();
+        let nested_order = std::mem::take(&mut *log.lock().unwrap());
+
+        let req = Request::new(
+            http::Method::GET,
+            "/api/health".parse().unwrap(),
+            http::HeaderMap::new(),
+            bytes::Bytes::new(),
+        );
+        let health_ok = app.handle_request(req).await.is_ok();
+        let health_order = log.lock().unwrap().clone();
+
+        nested_ok
+            && health_ok
+            && nested_order == vec!["app", "api", "users"]
+            && health_order == vec!["app", "api"]
+    })
+}
+
+#[test]
+fn test_nested_router_middleware_composes_in_mount_order_property() {
+    QuickCheck::new()
+        .tests(10)
+        .quickcheck(prop_nested_router_middleware_composes_in_mount_order as fn(bool) -> bool);
+}
+
+#[tokio::test]
+async fn test_router_middleware_runs_for_unmatched_requests_under_its_prefix() {
+    use ruffus::Router;
+    use std::sync::{Arc, Mutex};
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let mut users = Router::new("/users");
+    users.use_middleware(Arc::new(LabelMiddleware { label: "users", log: log.clone() }));
+    users.get("/:id", |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) });
+
+    let mut api = Router::new("/api");
+    api.use_middleware(Arc::new(LabelMiddleware { label: "api", log: log.clone() }));
+    api.mount("", users);
+
+    let mut app = App::new();
+    app.use_middleware(Arc::new(LabelMiddleware { label: "app", log: log.clone() }));
+    app.mount("", api);
+
+    let cases = [
+        (http::Method::DELETE, "/api/users/7", 405, vec!["app", "api", "users"]),
+        (http::Method::GET, "/api/users/7/posts", 404, vec!["app", "api", "users"]),
+        (http::Method::GET, "/api/missing", 404, vec!["app", "api"]),
+        (http::Method::GET, "/elsewhere", 404, vec!["app"]),
+    ];
+    for (method, path, status, expected) in cases {
+        let req = Request::new(method, path.parse().unwrap(), http::HeaderMap::new(), bytes::Bytes::new());
+        let error = app.handle_request(req).await.err().unwrap();
+        assert_eq!(error.status_code().as_u16(), status, "{}", path);
+        assert_eq!(std::mem::take(&mut *log.lock().unwrap()), expected, "{}", path);
+    }
+}
+
+// **Feature: rust-web-framework, Property 46: Shared state reaches handlers, middleware and substates**
+// **Validates: Requirements 4.2, 7.4, 9.3**
+fn prop_shared_state_reaches_handlers_and_substates(counter_start: u32, token: String) -> bool {
+    use async_trait::async_trait;
+    use ruffus::{FromRef, Middleware, Next, Router, State};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use tokio::runtime::Runtime;
+
+    #[derive(Clone)]
+    struct AppState {
+        counter: Arc<AtomicU32>,
+        token: String,
+    }
+
+    #[derive(Clone)]
+    struct AdminState {
+        token: String,
+    }
+
+    impl FromRef<AppState> for AdminState {
+        fn from_ref(state: &AppState) -> Self {
+            AdminState { token: state.token.clone() }
+        }
+    }
+
+    // Middleware that reads the substate projected for its router
+    struct RequireAdminState;
+
+    #[async_trait]
+    impl Middleware for RequireAdminState {
+        async fn handle(&self, req: Request, next: Next) -> ruffus::Result<Response> {
+            if req.extensions().get::<AdminState>().is_none() {
+                return Ok(Response::new().status(http::StatusCode::FORBIDDEN));
+            }
+            next.run(req).await
+        }
+    }
+
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let mut app = App::with_state(AppState {
+            counter: Arc::new(AtomicU32::new(counter_start)),
+            token: token.clone(),
+        });
+
+        app.get("/count", |State(state): State<AppState>| async move {
+            let value = state.counter.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, ruffus::Error>(Response::text(value.to_string()))
+        });
+        app.get("/admin-outside", |State(admin): State<AdminState>| async move {
+            Ok::<_, ruffus::Error>(Response::text(admin.token))
+        });
+
+        let mut admin = Router::new("/admin");
+        admin.use_middleware(Arc::new(RequireAdminState));
+        admin.substate::<AppState, AdminState>();
+        admin.get("/token", |State(admin): State<AdminState>| async move {
+            Ok::<_, ruffus::Error>(Response::text(admin.token))
+        });
+        app.mount("", admin);
+
+        let get = |path: &str| {
+            Request::new(http::Method::GET, path.parse().unwrap(), http::HeaderMap::new(), bytes::Bytes::new())
+        };
+
+        let first = app.handle_request(get("/count")).await.unwrap();
+        let second = app.handle_request(get("/count")).await.unwrap();
+        let admin = app.handle_request(get("/admin/token")).await.unwrap();
+        let outside = app.handle_request(get("/admin-outside")).await;
+
+        // State is shared across requests
+        first.buffered_body().unwrap() == &bytes::Bytes::from(counter_start.to_string())
+            && second.buffered_body().unwrap() == &bytes::Bytes::from(counter_start.wrapping_add(1).to_string())
+            // The substate is projected before the router's own middleware
+            && admin.get_status() == http::StatusCode::OK
+            && admin.buffered_body().unwrap() == &bytes::Bytes::from(token)
+            // ...and only for that router's routes
+            && matches!(outside, Err(e) if e.status_code() == http::StatusCode::INTERNAL_SERVER_ERROR)
+    })
+}
+
+#[test]
+fn test_shared_state_reaches_handlers_and_substates_property() {
+    QuickCheck::new()
+        .tests(50)
+        .quickcheck(prop_shared_state_reaches_handlers_and_substates as fn(u32, String) -> bool);
+}
@@ -0,0 +1,206 @@
+//! Property-based tests for streaming request bodies
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::StreamExt;
+use http::{HeaderMap, Method, StatusCode};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::{App, Body, Json, Middleware, Next, Request, Response, Server};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+fn streamed(chunks: &[Vec<u8>]) -> Body {
+    let chunks: Vec<Result<Bytes, std::io::Error>> = chunks.iter().map(|c| Ok(Bytes::from(c.clone()))).collect();
+    Body::from_stream(futures_util::stream::iter(chunks))
+}
+
+fn post(path: &str, headers: HeaderMap, body: impl Into<Body>) -> Request {
+    Request::new(Method::POST, path.parse().unwrap(), headers, body)
+}
+
+// **Feature: rust-web-framework, Property 57: Streamed bodies read the same as buffered bodies**
+// **Validates: Requirements 3.5**
+fn prop_streamed_body_round_trip(chunks: Vec<Vec<u8>>) -> bool {
+    let expected: Vec<u8> = chunks.concat();
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        // Buffered, twice, through the request
+        let mut req = post("/", HeaderMap::new(), streamed(&chunks));
+        let buffered = req.body().await.unwrap();
+        let again = req.body().await.unwrap();
+
+        // As a stream of chunks
+        let mut stream = streamed(&chunks);
+        let mut from_stream = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            from_stream.extend_from_slice(&chunk.unwrap());
+        }
+
+        // Through AsyncRead, a few bytes at a time
+        let mut reader = streamed(&chunks);
+        let mut from_reader = Vec::new();
+        let mut buf = [0u8; 3];
+        loop {
+            let n = reader.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            from_reader.extend_from_slice(&buf[..n]);
+        }
+
+        buffered == expected && again == expected && from_stream == expected && from_reader == expected
+    })
+}
+
+#[test]
+fn test_streamed_body_round_trip_property() {
+    QuickCheck::new()
+        .tests(50)
+        .quickcheck(prop_streamed_body_round_trip as fn(Vec<Vec<u8>>) -> bool);
+}
+
+// **Feature: rust-web-framework, Property 58: Bodies over the limit are rejected with 413**
+// **Validates: Requirements 3.5, 7.2**
+fn prop_body_limit_enforced(limit: u8, size: u8, declare_length: bool) -> TestResult {
+    let (limit, size) = (limit as usize, size as usize);
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let mut app = App::new();
+        app.body_limit(limit);
+        app.post("/echo", |body: Bytes| async move { Ok::<_, ruffus::Error>(Response::text(body.len().to_string())) });
+
+        let payload = vec![b'a'; size];
+        let chunks: Vec<Vec<u8>> = payload.chunks(7).map(|c| c.to_vec()).collect();
+        let mut headers = HeaderMap::new();
+        if declare_length {
+            headers.insert(http::header::CONTENT_LENGTH, size.into());
+        }
+
+        match app.handle_request(post("/echo", headers, streamed(&chunks))).await {
+            Ok(response) => TestResult::from_bool(size <= limit && response.buffered_body().unwrap() == size.to_string().as_bytes()),
+            Err(e) => TestResult::from_bool(size > limit && e.status_code() == StatusCode::PAYLOAD_TOO_LARGE),
+        }
+    })
+}
+
+#[test]
+fn test_body_limit_enforced_property() {
+    QuickCheck::new()
+        .tests(100)
+        .quickcheck(prop_body_limit_enforced as fn(u8, u8, bool) -> TestResult);
+}
+
+#[tokio::test]
+async fn test_json_extractor_respects_body_limit() {
+    let mut app = App::new();
+    app.body_limit(8);
+    app.post("/json", |Json(value): Json<serde_json::Value>| async move {
+        Ok::<_, ruffus::Error>(Response::text(value.to_string()))
+    });
+
+    let ok = app.handle_request(post("/json", HeaderMap::new(), "[1,2,3]")).await.unwrap();
+    assert_eq!(ok.buffered_body().unwrap(), "[1,2,3]".as_bytes());
+
+    match app.handle_request(post("/json", HeaderMap::new(), "[1,2,3,4,5]")).await {
+        Err(e) => assert_eq!(e.status_code(), StatusCode::PAYLOAD_TOO_LARGE),
+        Ok(_) => panic!("expected 413"),
+    }
+}
+
+// Lifts the body limit for uploads
+struct LargeUploads;
+
+#[async_trait]
+impl Middleware for LargeUploads {
+    async fn handle(&self, mut req: Request, next: Next) -> ruffus::Result<Response> {
+        if req.uri().path().starts_with("/upload") {
+            req.set_body_limit(None);
+        }
+        next.run(req).await
+    }
+}
+
+#[tokio::test]
+async fn test_middleware_can_change_body_limit() {
+    let mut app = App::new();
+    app.body_limit(4);
+    app.use_middleware(Arc::new(LargeUploads));
+    app.post("/upload", |body: Bytes| async move { Ok::<_, ruffus::Error>(Response::text(body.len().to_string())) });
+    app.post("/small", |body: Bytes| async move { Ok::<_, ruffus::Error>(Response::text(body.len().to_string())) });
+
+    let res = app.handle_request(post("/upload", HeaderMap::new(), vec![0u8; 100])).await.unwrap();
+    assert_eq!(res.buffered_body().unwrap(), "100".as_bytes());
+
+    match app.handle_request(post("/small", HeaderMap::new(), vec![0u8; 100])).await {
+        Err(e) => assert_eq!(e.status_code(), StatusCode::PAYLOAD_TOO_LARGE),
+        Ok(_) => panic!("expected 413"),
+    }
+}
+
+// Starts a server on a free port that shuts down when the returned sender fires
+async fn spawn_server(app: App) -> (u16, oneshot::Sender<()>, tokio::task::JoinHandle<ruffus::Result<()>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let handle = tokio::spawn(Server::new(app).serve_with_shutdown(listener, async {
+        stopped.await.ok();
+    }));
+    (port, stop, handle)
+}
+
+async fn read_response(stream: &mut TcpStream) -> String {
+    let mut response = Vec::new();
+    tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut response))
+        .await
+        .expect("response should arrive without the body being sent")
+        .unwrap();
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn test_bodies_are_not_read_eagerly() {
+    let mut app = App::new();
+    app.body_limit(1024);
+    app.post("/bytes", |body: Bytes| async move { Ok::<_, ruffus::Error>(Response::text(body.len().to_string())) });
+    app.post("/stream", |mut body: Body| async move {
+        // Echo back the first chunk only
+        let first = body.next().await.transpose().unwrap().unwrap_or_default();
+        Ok::<_, ruffus::Error>(Response::text(String::from_utf8_lossy(&first).into_owned()))
+    });
+    let (port, stop, server) = spawn_server(app).await;
+
+    // A declared oversized body is refused before any of it arrives
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(b"POST /bytes HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1000000000\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let response = read_response(&mut stream).await;
+    assert!(response.starts_with("HTTP/1.1 413 Payload Too Large"), "{}", response);
+
+    // Unmatched routes don't wait for the body either
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(b"POST /missing HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1000000000\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let response = read_response(&mut stream).await;
+    assert!(response.starts_with("HTTP/1.1 404 Not Found"), "{}", response);
+
+    // A streaming handler sees the first chunk while the rest is outstanding
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(b"POST /stream HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n5\r\nfirst\r\n")
+        .await
+        .unwrap();
+    let response = read_response(&mut stream).await;
+    assert!(response.ends_with("first"), "{}", response);
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
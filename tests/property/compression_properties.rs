@@ -0,0 +1,366 @@
+//! Property-based tests for response compression
+
+use async_compression::tokio::bufread::{
+    BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder, ZlibDecoder, ZlibEncoder, ZstdDecoder, ZstdEncoder,
+};
+use bytes::Bytes;
+use futures_util::StreamExt;
+use http::{HeaderMap, Method, StatusCode};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::compression::{Compression, Decompression, Level};
+use ruffus::{App, Error, Json, Request, Response};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+
+const ENCODINGS: [&str; 4] = ["br", "zstd", "gzip", "deflate"];
+
+fn request(path: &str, accept_encoding: Option<&str>) -> Request {
+    let mut headers = HeaderMap::new();
+    if let Some(accept_encoding) = accept_encoding {
+        headers.insert("accept-encoding", accept_encoding.parse().unwrap());
+    }
+    Request::new(Method::GET, path.parse().unwrap(), headers, "")
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response.get_headers().get(name).map(|value| value.to_str().unwrap())
+}
+
+async fn decode(encoding: Option<&str>, data: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    match encoding {
+        Some("br") => BrotliDecoder::new(data).read_to_end(&mut decoded).await.unwrap(),
+        Some("zstd") => ZstdDecoder::new(data).read_to_end(&mut decoded).await.unwrap(),
+        Some("gzip") => GzipDecoder::new(data).read_to_end(&mut decoded).await.unwrap(),
+        Some("deflate") => ZlibDecoder::new(data).read_to_end(&mut decoded).await.unwrap(),
+        None => return data.to_vec(),
+        Some(other) => panic!("unexpected encoding {}", other),
+    };
+    decoded
+}
+
+async fn encode(encoding: &str, data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    match encoding {
+        "br" => BrotliEncoder::new(data).read_to_end(&mut encoded).await.unwrap(),
+        "zstd" => ZstdEncoder::new(data).read_to_end(&mut encoded).await.unwrap(),
+        "gzip" => GzipEncoder::new(data).read_to_end(&mut encoded).await.unwrap(),
+        "deflate" => ZlibEncoder::new(data).read_to_end(&mut encoded).await.unwrap(),
+        other => panic!("unexpected encoding {}", other),
+    };
+    encoded
+}
+
+// **Feature: rust-web-framework, Property 66: Compressed bodies decode to the original body**
+// **Validates: Requirements 4.1, 5.1**
+fn prop_compressed_bodies_decode_to_original(data: Vec<u8>, encoding: u8, streamed: bool, chunk_size: u8) -> TestResult {
+    let encoding = ENCODINGS[encoding as usize % ENCODINGS.len()];
+    let body = data.clone();
+    let mut app = App::new();
+    app.use_middleware(Arc::new(Compression::new().min_size(0).gzip(Level::Best).zstd(Level::Fastest)));
+    app.get("/data", move || {
+        let body = body.clone();
+        async move {
+            let response = if streamed {
+                let chunks: Vec<Result<Bytes, std::io::Error>> = body
+                    .chunks(chunk_size.max(1) as usize)
+                    .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+                    .collect();
+                Response::stream(futures_util::stream::iter(chunks))
+            } else {
+                Response::new().body_bytes(Bytes::from(body))
+            };
+            response.header("Content-Type", "application/octet-stream")
+        }
+    });
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        // The client prefers the chosen encoding over the others
+        let accept = format!("{}, *;q=0.5", encoding);
+        let response = app.handle_request(request("/data", Some(&accept))).await.unwrap();
+        if header(&response, "content-encoding") != Some(encoding) || header(&response, "content-length").is_some() {
+            return TestResult::failed();
+        }
+        let compressed = response.into_bytes().await.unwrap();
+        TestResult::from_bool(decode(Some(encoding), &compressed).await == data)
+    })
+}
+
+#[test]
+fn test_compressed_bodies_decode_to_original_property() {
+    QuickCheck::new()
+        .tests(100)
+        .quickcheck(prop_compressed_bodies_decode_to_original as fn(Vec<u8>, u8, bool, u8) -> TestResult);
+}
+
+fn text_app() -> App {
+    let mut app = App::new();
+    app.use_middleware(Arc::new(Compression::new()));
+    app.get("/text", || async {
+        Response::text("lorem ipsum ".repeat(200))
+            .header("ETag", "\"v1\"")
+            .header("Accept-Ranges", "bytes")
+            .header("Vary", "Origin")
+    });
+    app.get("/small", || async { "small" });
+    app.get("/image", || async {
+        Response::new()
+            .header("Content-Type", "image/png")
+            .body_bytes(Bytes::from(vec![0u8; 4096]))
+    });
+    app.get("/svg", || async {
+        Response::new()
+            .header("Content-Type", "image/svg+xml")
+            .body("<svg></svg>".repeat(200))
+    });
+    app.get("/encoded", || async {
+        Response::new()
+            .header("Content-Encoding", "gzip")
+            .body_bytes(Bytes::from(vec![0u8; 4096]))
+    });
+    app.get("/no-transform", || async {
+        Response::text("x".repeat(4096)).header("Cache-Control", "public, no-transform")
+    });
+    app.get("/partial", || async {
+        Response::text("x".repeat(4096)).status(StatusCode::PARTIAL_CONTENT)
+    });
+    app
+}
+
+#[tokio::test]
+async fn test_compression_headers() {
+    let app = text_app();
+
+    let response = app.handle_request(request("/text", Some("gzip, br"))).await.unwrap();
+    assert_eq!(header(&response, "content-encoding"), Some("br"));
+    assert_eq!(header(&response, "vary"), Some("Origin, Accept-Encoding"));
+    assert_eq!(header(&response, "etag"), Some("W/\"v1\""));
+    assert_eq!(header(&response, "accept-ranges"), None);
+    let compressed = response.into_bytes().await.unwrap();
+    assert!(compressed.len() < 200);
+    assert_eq!(decode(Some("br"), &compressed).await, "lorem ipsum ".repeat(200).as_bytes());
+
+    // Clients that accept no encoding still get the Vary header
+    for accept in [None, Some("identity"), Some("gzip;q=0, *;q=0")] {
+        let response = app.handle_request(request("/text", accept)).await.unwrap();
+        assert_eq!(header(&response, "content-encoding"), None);
+        assert_eq!(header(&response, "vary"), Some("Origin, Accept-Encoding"));
+        assert_eq!(header(&response, "etag"), Some("\"v1\""));
+    }
+}
+
+#[tokio::test]
+async fn test_responses_left_uncompressed() {
+    let app = text_app();
+
+    for path in ["/small", "/image", "/encoded", "/no-transform", "/partial"] {
+        let response = app.handle_request(request(path, Some("gzip"))).await.unwrap();
+        let encoding = header(&response, "content-encoding");
+        assert!(encoding.is_none() || path == "/encoded", "{} was compressed", path);
+        assert!(header(&response, "vary").is_none(), "{} varies", path);
+    }
+
+    let response = app.handle_request(request("/svg", Some("gzip"))).await.unwrap();
+    assert_eq!(header(&response, "content-encoding"), Some("gzip"));
+}
+
+#[tokio::test]
+async fn test_streamed_chunks_are_flushed() {
+    use tokio::sync::mpsc;
+
+    // The handler streams whatever the test sends through the channel
+    let (chunks, receiver) = mpsc::channel::<Bytes>(1);
+    let receiver = Arc::new(tokio::sync::Mutex::new(Some(receiver)));
+    let mut app = App::new();
+    app.use_middleware(Arc::new(Compression::new()));
+    app.get("/events", move || {
+        let receiver = receiver.clone();
+        async move {
+            let receiver = receiver.lock().await.take().unwrap();
+            let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+                let chunk = receiver.recv().await?;
+                Some((Ok::<_, std::io::Error>(chunk), receiver))
+            });
+            Response::stream(stream).header("Content-Type", "application/x-ndjson")
+        }
+    });
+
+    let response = app.handle_request(request("/events", Some("gzip"))).await.unwrap();
+    assert_eq!(header(&response, "content-encoding"), Some("gzip"));
+    let mut body = response.into_body();
+
+    // Each event is compressed and sent before the next one is produced
+    let mut compressed = Vec::new();
+    for event in ["{\"n\":1}\n", "{\"n\":2}\n"] {
+        chunks.send(Bytes::from(event)).await.unwrap();
+        let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await
+            .expect("the chunk was held back")
+            .unwrap()
+            .unwrap();
+        compressed.extend_from_slice(&chunk);
+    }
+    drop(chunks);
+    while let Some(chunk) = body.next().await {
+        compressed.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(decode(Some("gzip"), &compressed).await, b"{\"n\":1}\n{\"n\":2}\n");
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_server_sent_events_are_not_compressed() {
+    use ruffus::sse::{Event, KeepAlive, Sse};
+
+    let mut app = App::new();
+    app.use_middleware(Arc::new(Compression::new().min_size(0)));
+    app.get("/events", || async {
+        // One event, then silence broken only by keep-alives
+        let events = futures_util::stream::iter(vec![Ok::<_, std::convert::Infallible>(Event::default().data("hi"))])
+            .chain(futures_util::stream::pending());
+        Sse::new(events).keep_alive(KeepAlive::new().interval(Duration::from_secs(10)).text("ping"))
+    });
+
+    let response = app.handle_request(request("/events", Some("gzip, br"))).await.unwrap();
+    assert_eq!(header(&response, "content-encoding"), None);
+    assert!(header(&response, "vary").is_none());
+    let mut body = response.into_body();
+
+    let event = tokio::time::timeout(Duration::from_secs(15), body.next())
+        .await
+        .expect("the event was held back");
+    assert_eq!(event.unwrap().unwrap(), "data: hi\n\n");
+    let ping = tokio::time::timeout(Duration::from_secs(15), body.next())
+        .await
+        .expect("the keep-alive was held back");
+    assert_eq!(ping.unwrap().unwrap(), ": ping\n\n");
+}
+
+fn upload(path: &str, content_encoding: &str, body: Vec<u8>) -> Request {
+    let mut headers = HeaderMap::new();
+    headers.insert("content-encoding", content_encoding.parse().unwrap());
+    headers.insert("content-length", body.len().to_string().parse().unwrap());
+    headers.insert("content-type", "application/json".parse().unwrap());
+    Request::new(Method::POST, path.parse().unwrap(), headers, body)
+}
+
+// Echoes the request body and the headers left for the handler
+fn echo_app(decompression: Decompression) -> App {
+    let mut app = App::new();
+    app.use_middleware(Arc::new(decompression));
+    app.post("/echo", |mut req: Request| async move {
+        let encoding = req.headers().get("content-encoding").is_some();
+        let length = req.headers().get("content-length").is_some();
+        let body = req.body().await?;
+        Ok::<_, Error>(Response::new()
+            .header("X-Had-Encoding", &encoding.to_string())
+            .header("X-Had-Length", &length.to_string())
+            .body_bytes(body))
+    });
+    app
+}
+
+fn status(result: ruffus::Result<Response>) -> StatusCode {
+    match result {
+        Ok(response) => response.get_status(),
+        Err(error) => error.status_code(),
+    }
+}
+
+// **Feature: rust-web-framework, Property 67: Compressed request bodies are decoded before handlers read them**
+// **Validates: Requirements 3.5, 4.1**
+fn prop_request_bodies_are_decoded(data: Vec<u8>, first: u8, second: Option<u8>) -> TestResult {
+    let mut encodings = vec![ENCODINGS[first as usize % ENCODINGS.len()]];
+    encodings.extend(second.map(|second| ENCODINGS[second as usize % ENCODINGS.len()]));
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        // Encodings are listed in the order they were applied
+        let mut body = data.clone();
+        for encoding in &encodings {
+            body = encode(encoding, &body).await;
+        }
+        let request = upload("/echo", &encodings.join(", "), body);
+        let response = echo_app(Decompression::new()).handle_request(request).await.unwrap();
+        TestResult::from_bool(
+            header(&response, "x-had-encoding") == Some("false")
+                && header(&response, "x-had-length") == Some("false")
+                && response.buffered_body().unwrap() == &data,
+        )
+    })
+}
+
+#[test]
+fn test_request_bodies_are_decoded_property() {
+    QuickCheck::new()
+        .tests(100)
+        .quickcheck(prop_request_bodies_are_decoded as fn(Vec<u8>, u8, Option<u8>) -> TestResult);
+}
+
+#[tokio::test]
+async fn test_decompressed_json() {
+    let mut app = App::new();
+    app.use_middleware(Arc::new(Decompression::new()));
+    app.post("/events", |Json(events): Json<Vec<String>>| async move { events.join(",") });
+
+    let body = encode("gzip", br#"["a","b"]"#).await;
+    let response = app.handle_request(upload("/events", "gzip", body)).await.unwrap();
+    assert_eq!(response.buffered_body().unwrap(), "a,b");
+
+    // Identity and absent encodings are left as they are
+    let response = app
+        .handle_request(upload("/events", "identity", br#"["c"]"#.to_vec()))
+        .await
+        .unwrap();
+    assert_eq!(response.buffered_body().unwrap(), "c");
+}
+
+#[tokio::test]
+async fn test_unsupported_and_corrupt_request_bodies() {
+    let app = echo_app(Decompression::new());
+
+    for encoding in ["compress", "gzip, rot13"] {
+        let result = app.handle_request(upload("/echo", encoding, b"data".to_vec())).await;
+        assert!(
+            matches!(&result, Err(Error::Custom { status, .. }) if *status == StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            "{} was accepted",
+            encoding
+        );
+    }
+
+    let corrupt = app.handle_request(upload("/echo", "gzip", b"not gzip at all".to_vec())).await;
+    assert_eq!(status(corrupt), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_decompressed_size_is_limited() {
+    // A megabyte of zeros compresses to about a kilobyte
+    let bomb = encode("gzip", &vec![0u8; 1024 * 1024]).await;
+    assert!(bomb.len() < 8 * 1024);
+
+    let app = echo_app(Decompression::new().max_size(64 * 1024));
+    let result = app.handle_request(upload("/echo", "gzip", bomb.clone())).await;
+    assert_eq!(status(result), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // The limit also applies to handlers that stream the body
+    let mut app = App::new();
+    app.use_middleware(Arc::new(Decompression::new()));
+    app.body_limit(64 * 1024);
+    app.post("/stream", |mut req: Request| async move {
+        let mut body = req.take_body();
+        let mut read = 0;
+        while let Some(chunk) = body.next().await {
+            match chunk {
+                Ok(chunk) => read += chunk.len(),
+                Err(e) => return Ok::<_, Error>(Response::text(format!("failed after {} bytes: {}", read, e))),
+            }
+        }
+        Ok(Response::text(format!("read {} bytes", read)))
+    });
+    let response = app.handle_request(upload("/stream", "gzip", bomb)).await.unwrap();
+    let text = String::from_utf8(response.buffered_body().unwrap().to_vec()).unwrap();
+    assert!(text.starts_with("failed after"), "{}", text);
+    assert!(text.ends_with("body is larger than 65536 bytes"), "{}", text);
+}
+
@@ -0,0 +1,119 @@
+//! Property-based tests for the ConcurrencyLimit middleware
+
+use http::{HeaderMap, Method, StatusCode};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::concurrency_limit::ConcurrencyLimit;
+use ruffus::{App, Error, Request, Response, Router};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+fn request(path: &str) -> Request {
+    Request::new(Method::GET, path.parse().unwrap(), HeaderMap::new(), "")
+}
+
+// Handlers wait for the gate to open, so requests stay in flight until then
+fn gated_app(limit: ConcurrencyLimit, gate: Arc<Semaphore>) -> App {
+    let mut app = App::new();
+    app.use_middleware(Arc::new(limit));
+    app.get("/work", move || {
+        let gate = gate.clone();
+        async move {
+            gate.acquire().await.unwrap().forget();
+            "done"
+        }
+    });
+    app
+}
+
+async fn settle() {
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+}
+
+// **Feature: rust-web-framework, Property 70: Requests over the concurrency limit are shed**
+// **Validates: Requirements 4.1, 4.4**
+fn prop_requests_over_the_concurrency_limit_are_shed(max: u8, requests: u8) -> TestResult {
+    let max = (max % 8) as usize + 1;
+    let requests = (requests % 16) as usize;
+    let gate = Arc::new(Semaphore::new(0));
+    let limit = ConcurrencyLimit::new(max);
+    let app = Arc::new(gated_app(limit.clone(), gate.clone()));
+
+    let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    rt.block_on(async {
+        let tasks: Vec<_> = (0..requests)
+            .map(|_| {
+                let app = app.clone();
+                tokio::spawn(async move { app.handle_request(request("/work")).await })
+            })
+            .collect();
+        settle().await;
+        let in_flight = limit.in_flight();
+
+        gate.add_permits(requests);
+        let mut handled = 0;
+        let mut shed = 0;
+        for task in tasks {
+            match task.await.unwrap() {
+                Ok(response) if response.get_status() == StatusCode::OK => handled += 1,
+                Err(error) if error.status_code() == StatusCode::SERVICE_UNAVAILABLE => shed += 1,
+                _ => return TestResult::failed(),
+            }
+        }
+
+        let expected = requests.min(max);
+        TestResult::from_bool(
+            in_flight == expected && handled == expected && shed == requests - expected && limit.in_flight() == 0,
+        )
+    })
+}
+
+#[test]
+fn test_requests_over_the_concurrency_limit_are_shed_property() {
+    QuickCheck::new()
+        .tests(100)
+        .quickcheck(prop_requests_over_the_concurrency_limit_are_shed as fn(u8, u8) -> TestResult);
+}
+
+#[tokio::test]
+async fn test_retry_after_and_router_scope() {
+    let gate = Arc::new(Semaphore::new(0));
+    let mut app = gated_app(ConcurrencyLimit::new(1).retry_after(Duration::from_millis(1500)), gate.clone());
+
+    // Each router has its own cap, behind the app's
+    let mut reports = Router::new("/reports");
+    reports.use_middleware(Arc::new(ConcurrencyLimit::new(1)));
+    reports.get("/", || async { "report" });
+    app.mount("", reports);
+    let app = Arc::new(app);
+
+    let busy = tokio::spawn({
+        let app = app.clone();
+        async move { app.handle_request(request("/work")).await }
+    });
+    settle().await;
+
+    let response: Response = app.handle_request(request("/work")).await.unwrap();
+    assert_eq!(response.get_status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.get_headers().get("retry-after").unwrap(), "2");
+    let body: serde_json::Value = serde_json::from_slice(response.buffered_body().unwrap()).unwrap();
+    assert_eq!(body["error"]["status"], 503);
+
+    gate.add_permits(1);
+    assert_eq!(busy.await.unwrap().unwrap().get_status(), StatusCode::OK);
+    assert_eq!(app.handle_request(request("/reports")).await.unwrap().get_status(), StatusCode::OK);
+
+    // Errors release the request's slot too
+    let limit = ConcurrencyLimit::new(1);
+    let mut app = App::new();
+    app.use_middleware(Arc::new(limit.clone()));
+    app.get("/fail", || async { Err::<Response, _>(Error::BadRequest("no".to_string())) });
+    for _ in 0..3 {
+        let error = app.handle_request(request("/fail")).await.err().unwrap();
+        assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+    }
+    assert_eq!(limit.in_flight(), 0);
+    assert_eq!(limit.max(), 1);
+}
@@ -0,0 +1,211 @@
+//! Property-based tests for the CORS middleware
+
+use async_trait::async_trait;
+use http::{HeaderMap, Method, StatusCode};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::cors::Cors;
+use ruffus::{App, Error, Middleware, Next, Request, Response};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn request(method: Method, path: &str, headers: &[(&str, &str)]) -> Request {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        map.append(http::HeaderName::from_bytes(name.as_bytes()).unwrap(), value.parse().unwrap());
+    }
+    Request::new(method, path.parse().unwrap(), map, "")
+}
+
+fn cors_app(cors: Cors) -> App {
+    let mut app = App::new();
+    app.use_middleware(Arc::new(cors));
+    app.get("/users", || async { "users" });
+    app.post("/users", || async { Err::<Response, _>(Error::BadRequest("missing name".to_string())) });
+    app
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response.get_headers().get(name).map(|value| value.to_str().unwrap())
+}
+
+// **Feature: rust-web-framework, Property 65: Only allowed origins get CORS headers**
+// **Validates: Requirements 4.1, 5.4**
+fn prop_only_allowed_origins_get_cors_headers(host: String, allowed: Vec<String>, preflight: bool) -> TestResult {
+    let valid = |s: &String| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric());
+    if !valid(&host) || !allowed.iter().all(valid) {
+        return TestResult::discard();
+    }
+    let origin = format!("https://{}.test", host);
+    let is_allowed = allowed.contains(&host);
+    let cors = Cors::new().allow_origins(allowed.iter().map(|host| format!("https://{}.test", host)));
+    let app = cors_app(cors);
+
+    let req = if preflight {
+        request(Method::OPTIONS, "/users", &[("origin", &origin), ("access-control-request-method", "GET")])
+    } else {
+        request(Method::GET, "/users", &[("origin", &origin)])
+    };
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let response = app.handle_request(req).await.unwrap();
+        let allow_origin = header(&response, "access-control-allow-origin");
+        let varies = header(&response, "vary").is_some_and(|vary| vary.contains("Origin"));
+        let ok = if is_allowed {
+            allow_origin == Some(origin.as_str())
+        } else {
+            allow_origin.is_none()
+        };
+        TestResult::from_bool(ok && varies)
+    })
+}
+
+#[test]
+fn test_only_allowed_origins_get_cors_headers_property() {
+    QuickCheck::new()
+        .tests(100)
+        .quickcheck(prop_only_allowed_origins_get_cors_headers as fn(String, Vec<String>, bool) -> TestResult);
+}
+
+#[tokio::test]
+async fn test_preflights_are_answered_before_routing() {
+    let cors = Cors::new()
+        .allow_origin_regex(r"^https://[a-z]+\.example\.com$")
+        .allow_methods([Method::GET, Method::POST])
+        .max_age(Duration::from_secs(600));
+    let app = cors_app(cors);
+
+    // No route handles OPTIONS, and /missing has no route at all
+    for path in ["/users", "/missing"] {
+        let preflight = request(
+            Method::OPTIONS,
+            path,
+            &[
+                ("origin", "https://app.example.com"),
+                ("access-control-request-method", "POST"),
+                ("access-control-request-headers", "content-type, x-token"),
+            ],
+        );
+        let response = app.handle_request(preflight).await.unwrap();
+        assert_eq!(response.get_status(), StatusCode::NO_CONTENT);
+        assert_eq!(header(&response, "access-control-allow-origin"), Some("https://app.example.com"));
+        assert_eq!(header(&response, "access-control-allow-methods"), Some("GET, POST"));
+        assert_eq!(header(&response, "access-control-allow-headers"), Some("content-type, x-token"));
+        assert_eq!(header(&response, "access-control-max-age"), Some("600"));
+        assert!(header(&response, "vary").unwrap().contains("Origin"));
+    }
+
+    // Plain OPTIONS requests aren't preflights
+    let options = request(Method::OPTIONS, "/users", &[("origin", "https://app.example.com")]);
+    let response = app.handle_request(options).await.unwrap();
+    assert_eq!(response.get_status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(header(&response, "access-control-allow-origin"), Some("https://app.example.com"));
+
+    // Origins the pattern rejects get no CORS headers
+    let preflight = request(
+        Method::OPTIONS,
+        "/users",
+        &[("origin", "https://evil.com"), ("access-control-request-method", "GET")],
+    );
+    let response = app.handle_request(preflight).await.unwrap();
+    assert!(header(&response, "access-control-allow-origin").is_none());
+}
+
+#[tokio::test]
+async fn test_credentials_expose_headers_and_errors() {
+    let cors = Cors::new()
+        .allow_origin_fn(|origin| origin.ends_with(".internal"))
+        .allow_headers(["content-type"])
+        .allow_credentials(true)
+        .expose_headers(["x-request-id", "x-total"]);
+    let app = cors_app(cors);
+
+    let preflight = request(
+        Method::OPTIONS,
+        "/users",
+        &[
+            ("origin", "http://admin.internal"),
+            ("access-control-request-method", "GET"),
+            ("access-control-request-headers", "x-other"),
+        ],
+    );
+    let response = app.handle_request(preflight).await.unwrap();
+    assert_eq!(header(&response, "access-control-allow-headers"), Some("content-type"));
+    assert_eq!(header(&response, "access-control-allow-credentials"), Some("true"));
+
+    let response = app
+        .handle_request(request(Method::GET, "/users", &[("origin", "http://admin.internal")]))
+        .await
+        .unwrap();
+    assert_eq!(header(&response, "access-control-allow-origin"), Some("http://admin.internal"));
+    assert_eq!(header(&response, "access-control-allow-credentials"), Some("true"));
+    assert_eq!(header(&response, "access-control-expose-headers"), Some("x-request-id, x-total"));
+
+    // Errors carry the headers too, so scripts can read them
+    let response = app
+        .handle_request(request(Method::POST, "/users", &[("origin", "http://admin.internal")]))
+        .await
+        .unwrap();
+    assert_eq!(response.get_status(), StatusCode::BAD_REQUEST);
+    assert_eq!(header(&response, "access-control-allow-origin"), Some("http://admin.internal"));
+}
+
+#[tokio::test]
+async fn test_any_origin() {
+    let app = cors_app(Cors::new().allow_any_origin());
+    let response = app
+        .handle_request(request(Method::GET, "/users", &[("origin", "https://anywhere.test")]))
+        .await
+        .unwrap();
+    assert_eq!(header(&response, "access-control-allow-origin"), Some("*"));
+    assert!(header(&response, "vary").is_none());
+
+    // Credentials require the origin to be repeated
+    let app = cors_app(Cors::new().allow_origin_fn(|_| true).allow_credentials(true));
+    let response = app
+        .handle_request(request(Method::GET, "/users", &[("origin", "https://anywhere.test")]))
+        .await
+        .unwrap();
+    assert_eq!(header(&response, "access-control-allow-origin"), Some("https://anywhere.test"));
+    assert_eq!(header(&response, "vary"), Some("Origin"));
+}
+
+#[test]
+#[should_panic(expected = "can't allow credentials for any origin")]
+fn test_any_origin_rejects_credentials() {
+    let _ = Cors::new().allow_any_origin().allow_credentials(true);
+}
+
+#[test]
+#[should_panic(expected = "can't allow credentials for any origin")]
+fn test_credentials_reject_any_origin() {
+    let _ = Cors::new().allow_credentials(true).allow_any_origin();
+}
+
+// Records the errors it sees from the rest of the pipeline
+struct ErrorRecorder(Arc<Mutex<Vec<u16>>>);
+
+#[async_trait]
+impl Middleware for ErrorRecorder {
+    async fn handle(&self, req: Request, next: Next) -> ruffus::Result<Response> {
+        let result = next.run(req).await;
+        if let Err(error) = &result {
+            self.0.lock().unwrap().push(error.status_code().as_u16());
+        }
+        result
+    }
+}
+
+#[tokio::test]
+async fn test_global_middleware_sees_unmatched_requests() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut app = App::new();
+    app.use_middleware(Arc::new(ErrorRecorder(seen.clone())));
+    app.get("/users", || async { "users" });
+
+    let missing = app.handle_request(request(Method::GET, "/missing", &[])).await;
+    assert!(matches!(missing, Err(Error::RouteNotFound)));
+    let wrong_method = app.handle_request(request(Method::DELETE, "/users", &[])).await;
+    assert!(matches!(wrong_method, Err(Error::MethodNotAllowed(_))));
+    assert_eq!(*seen.lock().unwrap(), vec![404, 405]);
+}
@@ -275,6 +275,7 @@ fn prop_extractors_handle_type_conversion_errors(invalid_num: String) -> TestRes
         return TestResult::discard();
     }
     
+    #[allow(dead_code)]
     #[derive(Debug, Deserialize)]
     struct QueryParams {
         value: u32,
@@ -307,3 +308,66 @@ fn prop_extractors_handle_type_conversion_errors(invalid_num: String) -> TestRes
         Ok(_) => TestResult::failed(), // Should not succeed with invalid data
     }
 }
+
+// Feature: rust-web-framework, Property 44: Handlers receive extractor arguments
+// Validates: Requirements 9.2, 9.3
+#[quickcheck]
+fn prop_handlers_receive_extractor_arguments(id: u32, page: u32, name: String, valid_id: bool) -> TestResult {
+    use ruffus::{App, Response};
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Body {
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Pagination {
+        page: u32,
+    }
+
+    let mut app = App::new();
+    app.post(
+        "/users/:id",
+        |Path(id): Path<u32>, Query(q): Query<Pagination>, mut req: Request| async move {
+            let body: Body = req.json().await?;
+            Ok::<_, ruffus::Error>(Response::text(format!("{}/{}/{}", id, q.page, body.name)))
+        },
+    );
+    app.put("/users/:id", |Path(id): Path<u32>, Json(body): Json<Body>| async move {
+        Ok::<_, ruffus::Error>(Response::text(format!("{}/{}", id, body.name)))
+    });
+
+    let segment = if valid_id { id.to_string() } else { format!("user{}", id) };
+    let body = Bytes::from(serde_json::to_vec(&Body { name: name.clone() }).unwrap());
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let post = Request::new(
+            Method::POST,
+            format!("/users/{}?page={}", segment, page).parse().unwrap(),
+            HeaderMap::new(),
+            body.clone(),
+        );
+        let put = Request::new(
+            Method::PUT,
+            format!("/users/{}", segment).parse().unwrap(),
+            HeaderMap::new(),
+            body,
+        );
+
+        let post = app.handle_request(post).await;
+        let put = app.handle_request(put).await;
+
+        if !valid_id {
+            // Extractor failures surface as errors with a 400 status
+            let rejected = |r: &ruffus::Result<ruffus::Response>| {
+                matches!(r, Err(e) if e.status_code() == http::StatusCode::BAD_REQUEST)
+            };
+            return TestResult::from_bool(rejected(&post) && rejected(&put));
+        }
+
+        let post_ok = post.map(|r| r.buffered_body().unwrap() == &Bytes::from(format!("{}/{}/{}", id, page, name)));
+        let put_ok = put.map(|r| r.buffered_body().unwrap() == &Bytes::from(format!("{}/{}", id, name)));
+        TestResult::from_bool(post_ok.unwrap_or(false) && put_ok.unwrap_or(false))
+    })
+}
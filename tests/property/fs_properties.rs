@@ -0,0 +1,303 @@
+//! Property-based tests for static file serving
+
+use http::{HeaderMap, Method, StatusCode};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::fs::{ServeDir, ServeFile};
+use ruffus::{App, Error, Request, Response, Router};
+use std::path::Path;
+
+fn get(path: &str, headers: &[(&str, &str)]) -> Request {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        map.insert(http::HeaderName::from_bytes(name.as_bytes()).unwrap(), value.parse().unwrap());
+    }
+    Request::new(Method::GET, path.parse().unwrap(), map, "")
+}
+
+fn status(result: &ruffus::Result<Response>) -> StatusCode {
+    match result {
+        Ok(response) => response.get_status(),
+        Err(error) => error.status_code(),
+    }
+}
+
+// A site with a nested directory, and a secret next to it
+fn site() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let public = dir.path().join("public");
+    std::fs::create_dir_all(public.join("docs")).unwrap();
+    std::fs::write(public.join("index.html"), "<h1>home</h1>").unwrap();
+    std::fs::write(public.join("site.css"), "body {}").unwrap();
+    std::fs::write(public.join("docs/index.html"), "<h1>docs</h1>").unwrap();
+    std::fs::write(dir.path().join("secret.txt"), "hunter2").unwrap();
+    dir
+}
+
+fn static_app(root: &Path) -> App {
+    let mut app = App::new();
+    app.static_files("/assets", root.join("public"));
+    app
+}
+
+// **Feature: rust-web-framework, Property 62: Served files round-trip and never leave the root**
+// **Validates: Requirements 2.3, 4.1**
+fn prop_served_files_round_trip(name: String, contents: Vec<u8>, escape: Vec<u8>) -> TestResult {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return TestResult::discard();
+    }
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let dir = site();
+        std::fs::write(dir.path().join("public").join(&name), &contents).unwrap();
+        let app = static_app(dir.path());
+
+        let response = app.handle_request(get(&format!("/assets/{}", name), &[])).await.unwrap();
+        let length_ok = response.get_headers().get("content-length").unwrap() == &contents.len().to_string();
+        let body_ok = response.into_bytes().await.unwrap() == contents;
+
+        // However many `..` segments, in whatever encoding, the secret stays hidden
+        let dots: Vec<&str> = escape
+            .iter()
+            .map(|b| ["..", "%2e%2e", ".%2E", "..%2f..", "%2E%2E%2F", "."][*b as usize % 6])
+            .collect();
+        let path = format!("/assets/{}/secret.txt", dots.join("/"));
+        let result = app.handle_request(get(&path, &[])).await;
+        let hidden = match result {
+            Ok(response) => response.into_bytes().await.unwrap() != "hunter2",
+            Err(error) => error.status_code() == StatusCode::NOT_FOUND,
+        };
+
+        TestResult::from_bool(length_ok && body_ok && hidden)
+    })
+}
+
+#[test]
+fn test_served_files_round_trip_property() {
+    QuickCheck::new()
+        .tests(50)
+        .quickcheck(prop_served_files_round_trip as fn(String, Vec<u8>, Vec<u8>) -> TestResult);
+}
+
+#[tokio::test]
+async fn test_content_type_and_directories() {
+    let dir = site();
+    let app = static_app(dir.path());
+
+    let css = app.handle_request(get("/assets/site.css", &[])).await.unwrap();
+    assert_eq!(css.get_headers().get("content-type").unwrap(), "text/css");
+
+    let index = app.handle_request(get("/assets/docs/", &[])).await.unwrap();
+    assert_eq!(index.get_headers().get("content-type").unwrap(), "text/html");
+    assert_eq!(index.into_bytes().await.unwrap(), "<h1>docs</h1>");
+
+    // Directories are redirected to their path with a trailing slash
+    let redirect = app.handle_request(get("/assets/docs?lang=en", &[])).await.unwrap();
+    assert_eq!(redirect.get_status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(redirect.get_headers().get("location").unwrap(), "/assets/docs/?lang=en");
+    let root = app.handle_request(get("/assets", &[])).await.unwrap();
+    assert_eq!(root.get_headers().get("location").unwrap(), "/assets/");
+    // Extra leading slashes can't turn the redirect into one to another host
+    let redirect = app.handle_request(get("//assets/docs", &[])).await.unwrap();
+    assert_eq!(redirect.get_status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(redirect.get_headers().get("location").unwrap(), "/assets/docs/");
+    let root = app.handle_request(get("/assets/", &[])).await.unwrap();
+    assert_eq!(root.into_bytes().await.unwrap(), "<h1>home</h1>");
+
+    let missing = app.handle_request(get("/assets/missing.js", &[])).await;
+    assert!(matches!(missing, Err(Error::Custom { status: StatusCode::NOT_FOUND, .. })));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_symlinks_out_of_the_root_are_not_followed() {
+    let dir = site();
+    std::os::unix::fs::symlink(dir.path().join("secret.txt"), dir.path().join("public/link.txt")).unwrap();
+    let app = static_app(dir.path());
+
+    let result = app.handle_request(get("/assets/link.txt", &[])).await;
+    assert_eq!(status(&result), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_conditional_requests() {
+    let dir = site();
+    let app = static_app(dir.path());
+
+    let first = app.handle_request(get("/assets/site.css", &[])).await.unwrap();
+    let etag = first.get_headers().get("etag").unwrap().to_str().unwrap().to_string();
+    let modified = first.get_headers().get("last-modified").unwrap().to_str().unwrap().to_string();
+
+    let cached = app.handle_request(get("/assets/site.css", &[("if-none-match", &etag)])).await.unwrap();
+    assert_eq!(cached.get_status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(cached.get_headers().get("etag").unwrap(), etag.as_str());
+    assert!(cached.into_bytes().await.unwrap().is_empty());
+
+    let weak = format!("\"other\", W/{}", etag);
+    let cached = app.handle_request(get("/assets/site.css", &[("if-none-match", &weak)])).await;
+    assert_eq!(status(&cached), StatusCode::NOT_MODIFIED);
+
+    let cached = app.handle_request(get("/assets/site.css", &[("if-modified-since", &modified)])).await;
+    assert_eq!(status(&cached), StatusCode::NOT_MODIFIED);
+
+    // If-None-Match wins over If-Modified-Since
+    let changed = app
+        .handle_request(get("/assets/site.css", &[("if-none-match", "\"other\""), ("if-modified-since", &modified)]))
+        .await;
+    assert_eq!(status(&changed), StatusCode::OK);
+
+    let stale = app
+        .handle_request(get("/assets/site.css", &[("if-modified-since", "Thu, 01 Jan 1970 00:00:00 GMT")]))
+        .await;
+    assert_eq!(status(&stale), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_precompressed_files() {
+    let dir = site();
+    let public = dir.path().join("public");
+    std::fs::write(public.join("app.js"), "plain").unwrap();
+    std::fs::write(public.join("app.js.gz"), "gzipped").unwrap();
+    std::fs::write(public.join("app.js.br"), "brotli").unwrap();
+    std::fs::write(public.join("only-gzip.js.gz"), "gzipped").unwrap();
+
+    let mut router = Router::new("");
+    router.get("/*path", ServeDir::new(&public).precompressed_gzip().precompressed_br());
+    let mut app = App::new();
+    app.mount("/static", router);
+
+    let request = |path: &str, accept: &str| get(path, &[("accept-encoding", accept)]);
+
+    let br = app.handle_request(request("/static/app.js", "gzip, br")).await.unwrap();
+    assert_eq!(br.get_headers().get("content-encoding").unwrap(), "br");
+    assert_eq!(br.get_headers().get("content-type").unwrap(), "text/javascript");
+    assert_eq!(br.get_headers().get("vary").unwrap(), "Accept-Encoding");
+    assert_eq!(br.into_bytes().await.unwrap(), "brotli");
+
+    let gzip = app.handle_request(request("/static/app.js", "gzip, br;q=0")).await.unwrap();
+    assert_eq!(gzip.get_headers().get("content-encoding").unwrap(), "gzip");
+    assert_eq!(gzip.into_bytes().await.unwrap(), "gzipped");
+
+    let plain = app.handle_request(request("/static/app.js", "identity")).await.unwrap();
+    assert!(plain.get_headers().get("content-encoding").is_none());
+    assert_eq!(plain.into_bytes().await.unwrap(), "plain");
+
+    // A compressed sibling alone doesn't make the file exist
+    let missing = app.handle_request(request("/static/only-gzip.js", "identity")).await;
+    assert_eq!(status(&missing), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_spa_fallback_and_single_files() {
+    let dir = site();
+    let public = dir.path().join("public");
+
+    let mut router = Router::new("");
+    router.get("/*path", ServeDir::new(&public).fallback(public.join("index.html")));
+    let mut app = App::new();
+    app.mount("/app", router);
+    app.get("/style", ServeFile::new(public.join("site.css")));
+
+    let route = app.handle_request(get("/app/users/42", &[])).await.unwrap();
+    assert_eq!(route.get_status(), StatusCode::OK);
+    assert_eq!(route.into_bytes().await.unwrap(), "<h1>home</h1>");
+
+    // Rejected paths don't fall back
+    let escape = app.handle_request(get("/app/../secret.txt", &[])).await;
+    assert_eq!(status(&escape), StatusCode::NOT_FOUND);
+
+    let style = app.handle_request(get("/style", &[])).await.unwrap();
+    assert_eq!(style.get_headers().get("content-type").unwrap(), "text/css");
+    assert_eq!(style.into_bytes().await.unwrap(), "body {}");
+}
+
+#[tokio::test]
+async fn test_range_requests() {
+    let dir = site();
+    std::fs::write(dir.path().join("public/video.mp4"), b"0123456789").unwrap();
+    let app = static_app(dir.path());
+    let range = |range: &str| get("/assets/video.mp4", &[("range", range)]);
+
+    let full = app.handle_request(get("/assets/video.mp4", &[])).await.unwrap();
+    assert_eq!(full.get_headers().get("accept-ranges").unwrap(), "bytes");
+    let etag = full.get_headers().get("etag").unwrap().to_str().unwrap().to_string();
+
+    let partial = app.handle_request(range("bytes=2-4")).await.unwrap();
+    assert_eq!(partial.get_status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(partial.get_headers().get("content-range").unwrap(), "bytes 2-4/10");
+    assert_eq!(partial.get_headers().get("content-length").unwrap(), "3");
+    assert_eq!(partial.get_headers().get("content-type").unwrap(), "video/mp4");
+    assert_eq!(partial.into_bytes().await.unwrap(), "234");
+
+    let suffix = app.handle_request(range("bytes=-3")).await.unwrap();
+    assert_eq!(suffix.into_bytes().await.unwrap(), "789");
+
+    let unsatisfiable = app.handle_request(range("bytes=10-")).await.unwrap();
+    assert_eq!(unsatisfiable.get_status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(unsatisfiable.get_headers().get("content-range").unwrap(), "bytes */10");
+
+    // If-Range only serves the range while the file is unchanged
+    let current = app
+        .handle_request(get("/assets/video.mp4", &[("range", "bytes=0-0"), ("if-range", &etag)]))
+        .await
+        .unwrap();
+    assert_eq!(current.get_status(), StatusCode::PARTIAL_CONTENT);
+    let changed = app
+        .handle_request(get("/assets/video.mp4", &[("range", "bytes=0-0"), ("if-range", "\"old\"")]))
+        .await
+        .unwrap();
+    assert_eq!(changed.get_status(), StatusCode::OK);
+    assert_eq!(changed.into_bytes().await.unwrap(), "0123456789");
+}
+
+#[tokio::test]
+async fn test_multiple_ranges_over_a_connection() {
+    use ruffus::Server;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let dir = site();
+    std::fs::write(dir.path().join("public/data.txt"), b"0123456789").unwrap();
+    let app = static_app(dir.path());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(Server::new(app).serve_with_shutdown(listener, async {
+        stopped.await.ok();
+    }));
+
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(b"GET /assets/data.txt HTTP/1.1\r\nHost: localhost\r\nRange: bytes=0-1, 8-\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 206 Partial Content"), "{}", head);
+    let head = head.to_lowercase();
+    let boundary = head
+        .lines()
+        .find_map(|line| line.strip_prefix("content-type: multipart/byteranges; boundary="))
+        .unwrap();
+    let length: usize = head
+        .lines()
+        .find_map(|line| line.strip_prefix("content-length: "))
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(body.len(), length);
+    assert_eq!(
+        body,
+        format!(
+            "\r\n--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/10\r\n\r\n01\
+             \r\n--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 8-9/10\r\n\r\n89\
+             \r\n--{b}--\r\n",
+            b = boundary
+        )
+    );
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
@@ -0,0 +1,240 @@
+//! Property-based tests for serving from listeners
+
+use async_trait::async_trait;
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::{App, ConnectInfo, Listener, Request, Response};
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+
+// Sends a request with `Connection: close` and reads the whole response
+async fn round_trip<S>(stream: &mut S, path: &str) -> String
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+// **Feature: rust-web-framework, Property 54: Apps serve from pre-bound listeners**
+// **Validates: Requirements 1.1, 1.5**
+fn prop_serve_from_port_zero_listener(word: String) -> TestResult {
+    if word.is_empty() || !word.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return TestResult::discard();
+    }
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut app = App::new();
+        app.get("/echo/:word", |ConnectInfo(remote): ConnectInfo<SocketAddr>, req: Request| async move {
+            Ok::<_, ruffus::Error>(Response::text(format!("{} from {}", req.param("word").unwrap(), remote)))
+        });
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(app.serve_with_shutdown(listener, async {
+            stopped.await.ok();
+        }));
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let local = stream.local_addr().unwrap();
+        let response = round_trip(&mut stream, &format!("/echo/{}", word)).await;
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+
+        TestResult::from_bool(
+            port != 0
+                && response.starts_with("HTTP/1.1 200 OK")
+                && response.ends_with(&format!("{} from {}", word, local)),
+        )
+    })
+}
+
+#[test]
+fn test_serve_from_port_zero_listener_property() {
+    QuickCheck::new()
+        .tests(10)
+        .quickcheck(prop_serve_from_port_zero_listener as fn(String) -> TestResult);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_serve_from_unix_socket() {
+    use tokio::net::unix::SocketAddr as UnixAddr;
+    use tokio::net::{UnixListener, UnixStream};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("app.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let mut app = App::new();
+    app.get("/peer", |ConnectInfo(peer): ConnectInfo<UnixAddr>| async move {
+        Ok::<_, ruffus::Error>(Response::text(format!("unnamed: {}", peer.is_unnamed())))
+    });
+
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(app.serve_with_shutdown(listener, async {
+        stopped.await.ok();
+    }));
+
+    let mut stream = UnixStream::connect(&path).await.unwrap();
+    let response = round_trip(&mut stream, "/peer").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("unnamed: true"));
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+// A listener handing out in-memory connections, each tagged with an id
+struct ChannelListener {
+    connections: mpsc::Receiver<(DuplexStream, u32)>,
+}
+
+#[async_trait]
+impl Listener for ChannelListener {
+    type Io = DuplexStream;
+    type Addr = u32;
+
+    async fn accept(&mut self) -> std::io::Result<(Self::Io, Self::Addr)> {
+        match self.connections.recv().await {
+            Some(connection) => Ok(connection),
+            // Park once all senders are gone; the server stops on its signal
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(0)
+    }
+}
+
+#[tokio::test]
+async fn test_serve_from_custom_listener() {
+    let (connect, connections) = mpsc::channel(4);
+
+    let mut app = App::new();
+    app.get("/whoami", |ConnectInfo(id): ConnectInfo<u32>| async move {
+        Ok::<_, ruffus::Error>(Response::text(format!("connection {}", id)))
+    });
+
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(app.serve_with_shutdown(ChannelListener { connections }, async {
+        stopped.await.ok();
+    }));
+
+    for id in [7, 8] {
+        let (mut client, server_side) = tokio::io::duplex(4096);
+        connect.send((server_side, id)).await.unwrap();
+        let response = round_trip(&mut client, "/whoami").await;
+        assert!(response.ends_with(&format!("connection {}", id)));
+    }
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_connect_info_requires_listener() {
+    let mut app = App::new();
+    app.get("/ip", |ConnectInfo(addr): ConnectInfo<SocketAddr>| async move {
+        Ok::<_, ruffus::Error>(Response::text(addr.to_string()))
+    });
+
+    let req = Request::new(
+        http::Method::GET,
+        "/ip".parse().unwrap(),
+        http::HeaderMap::new(),
+        bytes::Bytes::new(),
+    );
+    match app.handle_request(req).await {
+        Err(ruffus::Error::InternalServerError(msg)) => assert!(msg.contains("SocketAddr")),
+        Err(e) => panic!("expected a 500 error, got {}", e),
+        Ok(_) => panic!("expected a 500 error"),
+    }
+}
+
+// A listener handing out whatever `accept` results it is sent
+struct FallibleListener {
+    results: mpsc::Receiver<std::io::Result<(DuplexStream, u32)>>,
+}
+
+#[async_trait]
+impl Listener for FallibleListener {
+    type Io = DuplexStream;
+    type Addr = u32;
+
+    async fn accept(&mut self) -> std::io::Result<(Self::Io, Self::Addr)> {
+        match self.results.recv().await {
+            Some(result) => result,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(0)
+    }
+}
+
+#[tokio::test]
+async fn test_transient_accept_errors_keep_serving() {
+    use std::io::{Error, ErrorKind};
+
+    let (results, rx) = mpsc::channel(8);
+
+    let mut app = App::new();
+    app.get("/whoami", |ConnectInfo(id): ConnectInfo<u32>| async move {
+        Ok::<_, ruffus::Error>(Response::text(format!("connection {}", id)))
+    });
+
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(app.serve_with_shutdown(FallibleListener { results: rx }, async {
+        stopped.await.ok();
+    }));
+
+    results.send(Err(Error::from(ErrorKind::ConnectionAborted))).await.unwrap();
+    // What running out of file descriptors looks like
+    results.send(Err(Error::other("Too many open files"))).await.unwrap();
+
+    let (mut client, server_side) = tokio::io::duplex(4096);
+    results.send(Ok((server_side, 9))).await.unwrap();
+    let response = round_trip(&mut client, "/whoami").await;
+    assert!(response.ends_with("connection 9"));
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_fatal_accept_error_drains_connections() {
+    let (results, rx) = mpsc::channel(8);
+
+    let mut app = App::new();
+    app.get("/slow", |_req: Request| async {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        Ok::<_, ruffus::Error>(Response::text("done".to_string()))
+    });
+
+    let server = tokio::spawn(app.serve(FallibleListener { results: rx }));
+
+    let (mut client, server_side) = tokio::io::duplex(4096);
+    results.send(Ok((server_side, 1))).await.unwrap();
+    let request = tokio::spawn(async move { round_trip(&mut client, "/slow").await });
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+    results
+        .send(Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)))
+        .await
+        .unwrap();
+
+    // The in-flight request still completes before the server gives up
+    assert!(request.await.unwrap().ends_with("done"));
+    assert!(server.await.unwrap().is_err());
+}
@@ -0,0 +1,230 @@
+//! Property-based tests for multipart/form-data bodies
+
+use bytes::Bytes;
+use http::{HeaderMap, Method, StatusCode};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::multipart::FieldData;
+use ruffus::{App, Body, Json, Multipart, Request};
+
+const BOUNDARY: &str = "X-RUFFUS-BOUNDARY";
+
+// A field as sent by a client: name, file name, content type and data
+type Part = (String, Option<String>, Option<String>, Vec<u8>);
+
+fn encode(parts: &[Part]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, file_name, content_type, data) in parts {
+        body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+        let mut disposition = format!("Content-Disposition: form-data; name=\"{}\"", name);
+        if let Some(file_name) = file_name {
+            disposition.push_str(&format!("; filename=\"{}\"", file_name));
+        }
+        body.extend_from_slice(disposition.as_bytes());
+        body.extend_from_slice(b"\r\n");
+        if let Some(content_type) = content_type {
+            body.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+        }
+        body.extend_from_slice(b"\r\n");
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+    body
+}
+
+fn post(path: &str, content_type: &str, body: impl Into<Body>, content_length: Option<usize>) -> Request {
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", content_type.parse().unwrap());
+    if let Some(length) = content_length {
+        headers.insert("content-length", length.to_string().parse().unwrap());
+    }
+    Request::new(Method::POST, path.parse().unwrap(), headers, body)
+}
+
+fn upload(body: impl Into<Body>, content_length: Option<usize>) -> Request {
+    let content_type = format!("multipart/form-data; boundary={}", BOUNDARY);
+    post("/upload", &content_type, body, content_length)
+}
+
+// Echoes the fields it receives
+fn echo_app() -> App {
+    let mut app = App::new();
+    app.post("/upload", |mut multipart: Multipart| async move {
+        let mut parts: Vec<Part> = Vec::new();
+        while let Some(field) = multipart.next_field().await? {
+            let name = field.name().unwrap_or_default().to_string();
+            let file_name = field.file_name().map(str::to_string);
+            let content_type = field.content_type().map(str::to_string);
+            parts.push((name, file_name, content_type, field.bytes().await?.to_vec()));
+        }
+        Ok::<_, ruffus::Error>(Json(parts))
+    });
+    app
+}
+
+// **Feature: rust-web-framework, Property 64: Multipart fields round-trip whatever the chunking**
+// **Validates: Requirements 3.5**
+fn prop_multipart_fields_round_trip(fields: Vec<(String, bool, Vec<u8>)>, chunk_size: u8) -> TestResult {
+    if fields.iter().any(|(name, _, _)| !name.chars().all(|c| c.is_ascii_alphanumeric())) {
+        return TestResult::discard();
+    }
+    let parts: Vec<Part> = fields
+        .into_iter()
+        .map(|(name, is_file, data)| {
+            let file_name = is_file.then(|| format!("{}.bin", name));
+            let content_type = is_file.then(|| "application/octet-stream".to_string());
+            (name, file_name, content_type, data)
+        })
+        .collect();
+
+    // The body arrives in chunks that split boundaries and headers
+    let encoded = encode(&parts);
+    let chunks: Vec<Result<Bytes, std::io::Error>> = encoded
+        .chunks(chunk_size.max(1) as usize)
+        .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+        .collect();
+    let body = Body::from_stream(futures_util::stream::iter(chunks));
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let response = echo_app().handle_request(upload(body, None)).await.unwrap();
+        let received: Vec<Part> = serde_json::from_slice(response.buffered_body().unwrap()).unwrap();
+        TestResult::from_bool(received == parts)
+    })
+}
+
+#[test]
+fn test_multipart_fields_round_trip_property() {
+    QuickCheck::new()
+        .tests(100)
+        .quickcheck(prop_multipart_fields_round_trip as fn(Vec<(String, bool, Vec<u8>)>, u8) -> TestResult);
+}
+
+#[tokio::test]
+async fn test_large_fields_are_spooled_to_disk() {
+    let dir = tempfile::tempdir().unwrap();
+    let spool_dir = dir.path().to_path_buf();
+    let saved = dir.path().join("saved.bin");
+    let saved_in_handler = saved.clone();
+
+    let mut app = App::new();
+    app.post("/upload", move |multipart: Multipart| {
+        let spool_dir = spool_dir.clone();
+        let saved = saved_in_handler.clone();
+        async move {
+            let mut multipart = multipart.spool_threshold(8).spool_dir(&spool_dir);
+            let mut kinds = Vec::new();
+            while let Some(field) = multipart.next_field().await? {
+                match field.data().await? {
+                    FieldData::Memory(bytes) => kinds.push(format!("memory:{}", bytes.len())),
+                    FieldData::File(file) => {
+                        assert!(file.path().starts_with(&spool_dir));
+                        kinds.push(format!("file:{}", file.len()));
+                        file.persist(&saved).await?;
+                    }
+                }
+            }
+            Ok::<_, ruffus::Error>(Json(kinds))
+        }
+    });
+
+    let body = encode(&[
+        ("title".to_string(), None, None, b"short".to_vec()),
+        ("video".to_string(), Some("a.mp4".to_string()), None, b"0123456789abcdef".to_vec()),
+    ]);
+    let response = app.handle_request(upload(body, None)).await.unwrap();
+    assert_eq!(response.buffered_body().unwrap(), "[\"memory:5\",\"file:16\"]");
+    assert_eq!(std::fs::read(&saved).unwrap(), b"0123456789abcdef");
+
+    // Only the persisted file is left in the spool directory
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn test_spooled_files_are_removed_when_dropped() {
+    let dir = tempfile::tempdir().unwrap();
+    let spool_dir = dir.path().to_path_buf();
+
+    let mut app = App::new();
+    app.post("/upload", move |multipart: Multipart| {
+        let spool_dir = spool_dir.clone();
+        async move {
+            let mut multipart = multipart.spool_threshold(0).spool_dir(&spool_dir);
+            let field = multipart.next_field().await?.unwrap();
+            let FieldData::File(file) = field.data().await? else {
+                panic!("expected a spooled file");
+            };
+            let contents = tokio::fs::read_to_string(file.path()).await.unwrap();
+            Ok::<_, ruffus::Error>(contents)
+        }
+    });
+
+    let body = encode(&[("doc".to_string(), Some("a.txt".to_string()), None, b"hello".to_vec())]);
+    let response = app.handle_request(upload(body, None)).await.unwrap();
+    assert_eq!(response.buffered_body().unwrap(), "hello");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn test_multipart_limits() {
+    let mut app = App::new();
+    app.body_limit(64);
+    app.post("/fields", |multipart: Multipart| async move {
+        let mut multipart = multipart.total_limit(1024).field_limit(4);
+        while let Some(field) = multipart.next_field().await? {
+            field.bytes().await?;
+        }
+        Ok::<_, ruffus::Error>("ok")
+    });
+    app.post("/upload", |mut multipart: Multipart| async move {
+        while let Some(field) = multipart.next_field().await? {
+            field.bytes().await?;
+        }
+        Ok::<_, ruffus::Error>("ok")
+    });
+    let content_type = format!("multipart/form-data; boundary={}", BOUNDARY);
+    let request = |path: &str, body: Vec<u8>, content_length: Option<usize>| post(path, &content_type, body, content_length);
+    let status = |result: ruffus::Result<ruffus::Response>| match result {
+        Ok(response) => response.get_status(),
+        Err(error) => error.status_code(),
+    };
+
+    let small = encode(&[("a".to_string(), None, None, b"1234".to_vec())]);
+    let large = encode(&[("a".to_string(), None, None, b"12345".to_vec())]);
+    assert_eq!(status(app.handle_request(request("/fields", small, None)).await), StatusCode::OK);
+    assert_eq!(
+        status(app.handle_request(request("/fields", large, None)).await),
+        StatusCode::PAYLOAD_TOO_LARGE
+    );
+
+    // The total defaults to the app's body limit
+    let big = encode(&[("a".to_string(), None, None, vec![b'x'; 100])]);
+    let len = big.len();
+    assert_eq!(
+        status(app.handle_request(request("/upload", big.clone(), None)).await),
+        StatusCode::PAYLOAD_TOO_LARGE
+    );
+    assert_eq!(
+        status(app.handle_request(request("/upload", big, Some(len))).await),
+        StatusCode::PAYLOAD_TOO_LARGE
+    );
+}
+
+#[tokio::test]
+async fn test_malformed_multipart_bodies() {
+    let app = echo_app();
+
+    let bad_request = |result: ruffus::Result<ruffus::Response>| matches!(result, Err(ruffus::Error::BadRequest(_)));
+
+    let no_boundary = post("/upload", "multipart/form-data", encode(&[]), None);
+    assert!(bad_request(app.handle_request(no_boundary).await));
+    let not_multipart = post("/upload", "application/json", "{}", None);
+    assert!(bad_request(app.handle_request(not_multipart).await));
+
+    let body = encode(&[("a".to_string(), None, None, b"1".to_vec())]);
+    let wrong_boundary = String::from_utf8(body.clone()).unwrap().replace(BOUNDARY, "OTHER");
+    assert!(bad_request(app.handle_request(upload(wrong_boundary, None)).await));
+
+    let truncated = body[..body.len() - 10].to_vec();
+    assert!(bad_request(app.handle_request(upload(truncated, None)).await));
+}
@@ -0,0 +1,305 @@
+//! Property-based tests for rate limiting
+
+use async_trait::async_trait;
+use http::{HeaderMap, Method, StatusCode};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::rate_limit::{Algorithm, Decision, MemoryStore, Quota, RateLimit, RateLimitStore};
+use ruffus::{App, ConnectInfo, Error, Request, Response};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn paused_runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .unwrap()
+}
+
+fn request(path: &str, headers: &[(&str, &str)]) -> Request {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        map.insert(
+            http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            value.parse().unwrap(),
+        );
+    }
+    Request::new(Method::GET, path.parse().unwrap(), map, "")
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response
+        .get_headers()
+        .get(name)
+        .map(|value| value.to_str().unwrap())
+}
+
+// **Feature: rust-web-framework, Property 68: Rate limits never allow more than the quota permits**
+// **Validates: Requirements 4.1, 4.4**
+fn prop_rate_limits_respect_quota(gaps: Vec<u16>, limit: u8, sliding: bool) -> TestResult {
+    let limit = (limit % 10) as u32 + 1;
+    let quota = Quota::new(limit, Duration::from_secs(1));
+    let algorithm = if sliding {
+        Algorithm::SlidingWindow
+    } else {
+        Algorithm::TokenBucket
+    };
+
+    let rt = paused_runtime();
+    rt.block_on(async {
+        let store = MemoryStore::new();
+        let start = tokio::time::Instant::now();
+        let mut allowed = Vec::new();
+        for (i, gap) in gaps.iter().enumerate() {
+            tokio::time::advance(Duration::from_millis(*gap as u64 % 500)).await;
+            let decision = store.hit("client", algorithm, quota).await.unwrap();
+            if i < limit as usize
+                && gaps[..=i].iter().all(|gap| gap % 500 == 0)
+                && !decision.allowed
+            {
+                // A burst of up to the limit is always allowed
+                return TestResult::failed();
+            }
+            if decision.allowed == decision.retry_after.is_some() {
+                return TestResult::failed();
+            }
+            if decision.allowed {
+                allowed.push(start.elapsed().as_secs_f64());
+            }
+        }
+
+        let ok = if sliding {
+            // The windows start with the first request and last a second
+            let first = allowed.first().copied().unwrap_or_default();
+            let mut per_window = std::collections::HashMap::<u64, u32>::new();
+            for t in &allowed {
+                *per_window.entry((t - first).floor() as u64).or_default() += 1;
+            }
+            per_window.values().all(|&count| count <= limit)
+        } else {
+            // A full bucket, plus what refills during the interval
+            (0..allowed.len()).all(|i| {
+                (i..allowed.len()).all(|j| {
+                    let refilled = (allowed[j] - allowed[i]) * limit as f64;
+                    (j - i + 1) as f64 <= limit as f64 + refilled + 1e-6
+                })
+            })
+        };
+        TestResult::from_bool(ok)
+    })
+}
+
+#[test]
+fn test_rate_limits_respect_quota_property() {
+    QuickCheck::new()
+        .tests(100)
+        .quickcheck(prop_rate_limits_respect_quota as fn(Vec<u16>, u8, bool) -> TestResult);
+}
+
+fn limited_app(limit: RateLimit) -> App {
+    let mut app = App::new();
+    app.use_middleware(Arc::new(limit));
+    app.get("/tenants/:tenant", || async { "ok" });
+    app
+}
+
+#[test]
+fn test_token_bucket_by_header() {
+    paused_runtime().block_on(async {
+        let app = limited_app(RateLimit::token_bucket(Quota::per_minute(2)).by_header("x-api-key"));
+        let alice = || request("/tenants/a", &[("x-api-key", "alice")]);
+
+        let response = app.handle_request(alice()).await.unwrap();
+        assert_eq!(header(&response, "ratelimit-limit"), Some("2"));
+        assert_eq!(header(&response, "ratelimit-remaining"), Some("1"));
+        assert_eq!(header(&response, "ratelimit-reset"), Some("30"));
+        assert_eq!(header(&response, "ratelimit-policy"), Some("2;w=60"));
+        app.handle_request(alice()).await.unwrap();
+
+        let response = app.handle_request(alice()).await.unwrap();
+        assert_eq!(response.get_status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(header(&response, "retry-after"), Some("30"));
+        assert_eq!(header(&response, "ratelimit-remaining"), Some("0"));
+        let body: serde_json::Value =
+            serde_json::from_slice(response.buffered_body().unwrap()).unwrap();
+        assert_eq!(body["error"]["status"], 429);
+
+        // Other keys have their own bucket, and requests without one aren't limited
+        let bob = app
+            .handle_request(request("/tenants/a", &[("x-api-key", "bob")]))
+            .await
+            .unwrap();
+        assert_eq!(bob.get_status(), StatusCode::OK);
+        for _ in 0..5 {
+            let anonymous = app
+                .handle_request(request("/tenants/a", &[]))
+                .await
+                .unwrap();
+            assert_eq!(anonymous.get_status(), StatusCode::OK);
+            assert_eq!(header(&anonymous, "ratelimit-limit"), None);
+        }
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        let response = app.handle_request(alice()).await.unwrap();
+        assert_eq!(response.get_status(), StatusCode::OK);
+        assert_eq!(header(&response, "ratelimit-remaining"), Some("0"));
+    });
+}
+
+#[test]
+fn test_sliding_window_by_ip_and_param() {
+    paused_runtime().block_on(async {
+        let from = |ip: &str| {
+            let mut req = request("/tenants/a", &[]);
+            let addr: SocketAddr = format!("{}:4000", ip).parse().unwrap();
+            req.extensions_mut().insert(ConnectInfo(addr));
+            req
+        };
+        let app = limited_app(RateLimit::sliding_window(Quota::per_second(3)));
+        for _ in 0..3 {
+            assert_eq!(
+                app.handle_request(from("10.0.0.1"))
+                    .await
+                    .unwrap()
+                    .get_status(),
+                StatusCode::OK
+            );
+        }
+        let response = app.handle_request(from("10.0.0.1")).await.unwrap();
+        assert_eq!(response.get_status(), StatusCode::TOO_MANY_REQUESTS);
+        // A third of the next window has to pass before the count drops
+        assert_eq!(header(&response, "retry-after"), Some("2"));
+        assert_eq!(
+            app.handle_request(from("10.0.0.2"))
+                .await
+                .unwrap()
+                .get_status(),
+            StatusCode::OK
+        );
+
+        // Halfway through the next window, half of the previous one still counts
+        tokio::time::advance(Duration::from_millis(1500)).await;
+        assert_eq!(
+            app.handle_request(from("10.0.0.1"))
+                .await
+                .unwrap()
+                .get_status(),
+            StatusCode::OK
+        );
+        let response = app.handle_request(from("10.0.0.1")).await.unwrap();
+        assert_eq!(response.get_status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Requests from unknown addresses share a bucket instead of going unlimited
+        let app = limited_app(RateLimit::sliding_window(Quota::per_second(2)));
+        for _ in 0..2 {
+            assert_eq!(
+                app.handle_request(request("/tenants/a", &[]))
+                    .await
+                    .unwrap()
+                    .get_status(),
+                StatusCode::OK
+            );
+        }
+        let response = app
+            .handle_request(request("/tenants/b", &[]))
+            .await
+            .unwrap();
+        assert_eq!(response.get_status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            app.handle_request(from("10.0.0.1"))
+                .await
+                .unwrap()
+                .get_status(),
+            StatusCode::OK
+        );
+
+        let app = limited_app(RateLimit::sliding_window(Quota::per_second(1)).by_param("tenant"));
+        assert_eq!(
+            app.handle_request(request("/tenants/a", &[]))
+                .await
+                .unwrap()
+                .get_status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            app.handle_request(request("/tenants/b", &[]))
+                .await
+                .unwrap()
+                .get_status(),
+            StatusCode::OK
+        );
+        let response = app
+            .handle_request(request("/tenants/a", &[]))
+            .await
+            .unwrap();
+        assert_eq!(response.get_status(), StatusCode::TOO_MANY_REQUESTS);
+    });
+}
+
+// A store answering from a script, recording the keys it is asked about
+struct ScriptedStore {
+    keys: Arc<Mutex<Vec<String>>>,
+    fail: bool,
+}
+
+#[async_trait]
+impl RateLimitStore for ScriptedStore {
+    async fn hit(
+        &self,
+        key: &str,
+        _algorithm: Algorithm,
+        _quota: Quota,
+    ) -> ruffus::Result<Decision> {
+        self.keys.lock().unwrap().push(key.to_string());
+        if self.fail {
+            return Err(Error::InternalServerError("store unavailable".to_string()));
+        }
+        Ok(Decision {
+            allowed: false,
+            remaining: 0,
+            reset: Duration::from_millis(2500),
+            retry_after: Some(Duration::from_millis(1200)),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_custom_keys_and_stores() {
+    let keys = Arc::new(Mutex::new(Vec::new()));
+    let store = ScriptedStore {
+        keys: keys.clone(),
+        fail: false,
+    };
+    let limit = RateLimit::token_bucket(Quota::per_hour(1000))
+        .by_key(|req| req.uri().query().map(|query| format!("query:{}", query)))
+        .store(store);
+    let app = limited_app(limit);
+
+    let response = app
+        .handle_request(request("/tenants/a?user=7", &[]))
+        .await
+        .unwrap();
+    assert_eq!(response.get_status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&response, "retry-after"), Some("2"));
+    assert_eq!(header(&response, "ratelimit-reset"), Some("3"));
+    assert_eq!(header(&response, "ratelimit-policy"), Some("1000;w=3600"));
+    assert_eq!(
+        app.handle_request(request("/tenants/a", &[]))
+            .await
+            .unwrap()
+            .get_status(),
+        StatusCode::OK
+    );
+    assert_eq!(*keys.lock().unwrap(), vec!["query:user=7"]);
+
+    // Store failures are errors, not silently allowed requests
+    let failing = ScriptedStore { keys, fail: true };
+    let app = limited_app(
+        RateLimit::token_bucket(Quota::per_hour(1))
+            .by_key(|_| Some("all".to_string()))
+            .store(failing),
+    );
+    let result = app.handle_request(request("/tenants/a", &[])).await;
+    assert!(matches!(result, Err(Error::InternalServerError(_))));
+}
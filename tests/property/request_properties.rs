@@ -252,6 +252,7 @@ fn prop_json_deserialization_round_trip(name: String, age: u8, active: bool) ->
 fn prop_invalid_json_returns_400_error(invalid_json: String) -> TestResult {
     use serde::Deserialize;
     
+    #[allow(dead_code)]
     #[derive(Debug, Deserialize)]
     struct TestData {
         name: String,
@@ -3,7 +3,7 @@
 use quickcheck::TestResult;
 use quickcheck_macros::quickcheck;
 use ruffus::Response;
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 
 // Feature: rust-web-framework, Property 17: JSON responses include correct Content-Type
 // Validates: Requirements 5.2
@@ -97,8 +97,6 @@ fn prop_custom_headers_are_included(header_name: String, header_value: String) -
 // Validates: Requirements 5.5
 #[test]
 fn prop_serialization_failures_return_500() {
-    use std::fmt;
-
     // Create a type that always fails to serialize
     struct FailingSerialize;
 
@@ -181,7 +179,7 @@ fn prop_response_builder_methods_work_correctly(
     let header_matches = headers.get(&header_name).is_some()
         && headers.get(&header_name).unwrap().to_str().unwrap() == header_value;
     
-    let body_matches = response.get_body().as_ref() == body_text.as_bytes();
+    let body_matches = response.buffered_body().unwrap().as_ref() == body_text.as_bytes();
 
     TestResult::from_bool(status_matches && header_matches && body_matches)
 }
@@ -196,7 +194,7 @@ fn prop_convenience_methods_work() {
         html_response.get_headers().get("content-type").unwrap().to_str().unwrap(),
         "text/html; charset=utf-8"
     );
-    assert_eq!(html_response.get_body().as_ref(), b"<h1>Hello</h1>");
+    assert_eq!(html_response.buffered_body().unwrap().as_ref(), b"<h1>Hello</h1>");
 
     // Test not_found()
     let not_found = Response::not_found();
@@ -205,7 +203,7 @@ fn prop_convenience_methods_work() {
     // Test bad_request()
     let bad_req = Response::bad_request("Invalid input".to_string());
     assert_eq!(bad_req.get_status(), http::StatusCode::BAD_REQUEST);
-    assert_eq!(bad_req.get_body().as_ref(), b"Invalid input");
+    assert_eq!(bad_req.buffered_body().unwrap().as_ref(), b"Invalid input");
 
     // Test internal_error()
     let error = Response::internal_error("Server error".to_string());
@@ -222,7 +220,7 @@ fn prop_convenience_methods_work() {
     // Test no_content()
     let no_content = Response::no_content();
     assert_eq!(no_content.get_status(), http::StatusCode::NO_CONTENT);
-    assert!(no_content.get_body().is_empty());
+    assert!(no_content.buffered_body().unwrap().is_empty());
 
     // Test method chaining with convenience methods
     let chained = Response::html("<p>Test</p>".to_string())
@@ -234,3 +232,316 @@ fn prop_convenience_methods_work() {
         "value"
     );
 }
+
+// Feature: rust-web-framework, Property 45: Handler return values convert into responses
+// Validates: Requirements 5.1, 5.2, 5.3, 5.4
+#[quickcheck]
+fn prop_handler_return_values_convert_into_responses(status_code: u16, text: String, number: i64) -> TestResult {
+    use http::{HeaderMap, HeaderValue, StatusCode};
+    use ruffus::{App, Error, Json, Request};
+
+    let status = match StatusCode::from_u16(200 + status_code % 300) {
+        Ok(status) => status,
+        Err(_) => return TestResult::discard(),
+    };
+
+    let mut app = App::new();
+    let body = text.clone();
+    app.get("/string", move || {
+        let body = body.clone();
+        async move { body }
+    });
+    app.get("/str", || async { "static" });
+    app.get("/unit", || async {});
+    app.get("/json", move || async move { Json(serde_json::json!({ "n": number })) });
+    app.get("/status", move || async move { (status, Json(number)) });
+    app.get("/headers", move || async move {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-number", HeaderValue::from(number));
+        (status, headers, "with headers")
+    });
+    app.get("/ok", || async { Ok::<_, Error>("fine") });
+    app.get("/err", || async { Err::<&str, _>(Error::Custom { status: StatusCode::CONFLICT, message: "taken".to_string() }) });
+    app.get("/error", || async { Error::BadRequest("nope".to_string()) });
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let get = |path: &str| {
+            Request::new(http::Method::GET, path.parse().unwrap(), HeaderMap::new(), bytes::Bytes::new())
+        };
+
+        let string = app.handle_request(get("/string")).await.unwrap();
+        let str_ = app.handle_request(get("/str")).await.unwrap();
+        let unit = app.handle_request(get("/unit")).await.unwrap();
+        let json = app.handle_request(get("/json")).await.unwrap();
+        let with_status = app.handle_request(get("/status")).await.unwrap();
+        let with_headers = app.handle_request(get("/headers")).await.unwrap();
+        let ok = app.handle_request(get("/ok")).await.unwrap();
+        let err = app.handle_request(get("/err")).await;
+        let error = app.handle_request(get("/error")).await;
+
+        let json_value: serde_json::Value = serde_json::from_slice(json.buffered_body().unwrap()).unwrap();
+
+        TestResult::from_bool(
+            string.buffered_body().unwrap() == &bytes::Bytes::from(text)
+                && str_.buffered_body().unwrap() == &bytes::Bytes::from("static")
+                && unit.get_status() == StatusCode::OK && unit.buffered_body().unwrap().is_empty()
+                && json.get_headers().get("content-type").unwrap() == "application/json"
+                && json_value["n"] == number
+                && with_status.get_status() == status
+                && with_status.buffered_body().unwrap() == &bytes::Bytes::from(number.to_string())
+                && with_headers.get_status() == status
+                && with_headers.get_headers().get("x-number").unwrap() == &number.to_string()
+                && ok.buffered_body().unwrap() == &bytes::Bytes::from("fine")
+                // Errors stay errors so middleware can see them
+                && matches!(err, Err(e) if e.status_code() == StatusCode::CONFLICT)
+                && matches!(error, Err(Error::BadRequest(_))),
+        )
+    })
+}
+
+#[tokio::test]
+async fn test_handlers_return_custom_error_types() {
+    use http::{HeaderMap, StatusCode};
+    use ruffus::{App, IntoResponse, Request};
+
+    // An application error that renders itself
+    enum AppError {
+        NotFound(u64),
+    }
+
+    impl IntoResponse for AppError {
+        fn into_response(self) -> Response {
+            match self {
+                AppError::NotFound(id) => (StatusCode::NOT_FOUND, format!("no user {}", id)).into_response(),
+            }
+        }
+    }
+
+    async fn find_user(id: u64) -> Result<String, AppError> {
+        match id {
+            1 => Ok("ada".to_string()),
+            id => Err(AppError::NotFound(id)),
+        }
+    }
+
+    let mut app = App::new();
+    app.get("/users/:id", |req: Request| async move {
+        let id = req.param("id").unwrap().parse().unwrap();
+        find_user(id).await
+    });
+
+    let get = |path: &str| Request::new(http::Method::GET, path.parse().unwrap(), HeaderMap::new(), bytes::Bytes::new());
+
+    let found = app.handle_request(get("/users/1")).await.unwrap();
+    assert_eq!(found.get_status(), StatusCode::OK);
+    assert_eq!(found.buffered_body().unwrap(), "ada");
+
+    let missing = app.handle_request(get("/users/7")).await.unwrap();
+    assert_eq!(missing.get_status(), StatusCode::NOT_FOUND);
+    assert_eq!(missing.buffered_body().unwrap(), "no user 7");
+}
+
+// Feature: rust-web-framework, Property 59: Streamed response bodies arrive intact
+// Validates: Requirements 5.1
+#[quickcheck]
+fn prop_streamed_responses_arrive_intact(chunks: Vec<Vec<u8>>) -> bool {
+    use bytes::Bytes;
+
+    let expected = chunks.concat();
+    let stream = futures_util::stream::iter(
+        chunks.into_iter().map(|chunk| Ok::<_, std::io::Error>(Bytes::from(chunk))),
+    );
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let streamed = Response::stream(stream);
+        let read = Response::reader(std::io::Cursor::new(expected.clone()));
+
+        streamed.buffered_body().is_none()
+            && streamed.into_bytes().await.unwrap() == expected
+            && read.into_bytes().await.unwrap() == expected
+    })
+}
+
+#[tokio::test]
+async fn test_streamed_response_is_sent_chunked() {
+    use bytes::Bytes;
+    use ruffus::{App, Server};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::{mpsc, oneshot};
+
+    // The handler streams whatever the test sends through the channel
+    let (chunks, receiver) = mpsc::channel::<Bytes>(1);
+    let receiver = std::sync::Arc::new(tokio::sync::Mutex::new(Some(receiver)));
+    let mut app = App::new();
+    app.get("/export", move || {
+        let receiver = receiver.clone();
+        async move {
+            let receiver = receiver.lock().await.take().unwrap();
+            let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+                let chunk = receiver.recv().await?;
+                Some((Ok::<_, std::io::Error>(chunk), receiver))
+            });
+            Response::stream(stream).header("Content-Type", "text/csv")
+        }
+    });
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(Server::new(app).serve_with_shutdown(listener, async {
+        stopped.await.ok();
+    }));
+
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(b"GET /export HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+
+    // The first row reaches the client before the stream ends
+    chunks.send(Bytes::from("id,name\n")).await.unwrap();
+    let mut head = Vec::new();
+    while !String::from_utf8_lossy(&head).contains("id,name") {
+        let mut buf = [0u8; 1024];
+        let n = stream.read(&mut buf).await.unwrap();
+        assert!(n > 0, "connection closed early");
+        head.extend_from_slice(&buf[..n]);
+    }
+    let head = String::from_utf8_lossy(&head).to_lowercase();
+    assert!(head.contains("transfer-encoding: chunked"), "{}", head);
+    assert!(!head.contains("content-length"), "{}", head);
+
+    chunks.send(Bytes::from("1,alice\n")).await.unwrap();
+    drop(chunks);
+    let mut rest = String::new();
+    stream.read_to_string(&mut rest).await.unwrap();
+    assert!(rest.contains("1,alice"), "{}", rest);
+    assert!(rest.ends_with("0\r\n\r\n"), "{}", rest);
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+// Reads the parts of a `multipart/byteranges` body as (Content-Range, data)
+fn byteranges_parts(content_type: &str, body: &[u8]) -> Option<Vec<(String, Vec<u8>)>> {
+    let boundary = content_type.strip_prefix("multipart/byteranges; boundary=")?;
+    let delimiter = format!("\r\n--{}", boundary);
+    let mut rest = body.strip_prefix(delimiter.as_bytes())?;
+    let mut parts = Vec::new();
+    while let Some(part) = rest.strip_prefix(b"\r\n") {
+        let head_end = part.windows(4).position(|w| w == b"\r\n\r\n")?;
+        let head = std::str::from_utf8(&part[..head_end]).ok()?;
+        let content_range = head
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Range: "))?
+            .to_string();
+        let data = &part[head_end + 4..];
+        let data_end = data.windows(delimiter.len()).position(|w| w == delimiter.as_bytes())?;
+        parts.push((content_range, data[..data_end].to_vec()));
+        rest = &data[data_end + delimiter.len()..];
+    }
+    (rest == b"--\r\n").then_some(parts)
+}
+
+// Feature: rust-web-framework, Property 63: Ranged responses carry exactly the requested bytes
+// Validates: Requirements 5.1, 5.4
+#[quickcheck]
+fn prop_ranged_responses_carry_requested_bytes(data: Vec<u8>, specs: Vec<(u8, u8)>) -> TestResult {
+    use http::{HeaderMap, StatusCode};
+
+    if specs.is_empty() {
+        return TestResult::discard();
+    }
+    let specs: Vec<(usize, usize)> = specs
+        .into_iter()
+        .map(|(a, b)| (a.min(b) as usize, a.max(b) as usize))
+        .collect();
+    let header = specs
+        .iter()
+        .map(|(first, last)| format!("{}-{}", first, last))
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut request_headers = HeaderMap::new();
+    request_headers.insert("range", format!("bytes={}", header).parse().unwrap());
+
+    // Overlapping and adjacent ranges are merged, in ascending order
+    let len = data.len();
+    let mut ranges: Vec<(usize, usize)> = specs
+        .iter()
+        .filter(|(first, _)| *first < len)
+        .map(|&(first, last)| (first, (last + 1).min(len)))
+        .collect();
+    ranges.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    let expected: Vec<(String, Vec<u8>)> = merged
+        .into_iter()
+        .map(|(start, end)| (format!("bytes {}-{}/{}", start, end - 1, len), data[start..end].to_vec()))
+        .collect();
+
+    let response = Response::new()
+        .header("Content-Type", "application/octet-stream")
+        .body_bytes(data.clone().into())
+        .ranged(&request_headers);
+    let headers = response.get_headers();
+    let advertised = headers.get("accept-ranges").unwrap() == "bytes";
+    let content_range = headers.get("content-range").map(|v| v.to_str().unwrap().to_string());
+    let content_type = headers.get("content-type").map(|v| v.to_str().unwrap().to_string());
+    let body = response.buffered_body().unwrap().to_vec();
+
+    let ok = match (response.get_status(), expected.as_slice()) {
+        (StatusCode::RANGE_NOT_SATISFIABLE, []) => {
+            content_range == Some(format!("bytes */{}", len)) && body.is_empty()
+        }
+        (StatusCode::PARTIAL_CONTENT, [(range, bytes)]) => content_range.as_ref() == Some(range) && &body == bytes,
+        (StatusCode::PARTIAL_CONTENT, _) => {
+            byteranges_parts(&content_type.unwrap_or_default(), &body) == Some(expected.clone())
+        }
+        // Too many ranges are answered with the whole body
+        (StatusCode::OK, _) => expected.len() > 64 && body == data,
+        _ => false,
+    };
+    TestResult::from_bool(advertised && ok)
+}
+
+#[test]
+fn test_ranged_responses_honor_if_range() {
+    use http::{HeaderMap, StatusCode};
+
+    let response = || {
+        Response::text("Hello, World!".to_string())
+            .header("ETag", "\"v1\"")
+            .header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+    };
+    let request = |if_range: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert("range", "bytes=7-".parse().unwrap());
+        headers.insert("if-range", if_range.parse().unwrap());
+        headers
+    };
+
+    let current = response().ranged(&request("\"v1\""));
+    assert_eq!(current.get_status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(current.buffered_body().unwrap(), "World!");
+    let current = response().ranged(&request("Wed, 21 Oct 2015 07:28:00 GMT"));
+    assert_eq!(current.get_status(), StatusCode::PARTIAL_CONTENT);
+
+    // A changed or weak validator gets the whole body
+    for stale in ["\"v0\"", "W/\"v1\"", "Thu, 22 Oct 2015 07:28:00 GMT"] {
+        let full = response().ranged(&request(stale));
+        assert_eq!(full.get_status(), StatusCode::OK);
+        assert_eq!(full.buffered_body().unwrap(), "Hello, World!");
+    }
+
+    // Malformed or foreign ranges are ignored
+    let mut headers = HeaderMap::new();
+    headers.insert("range", "lines=1-2".parse().unwrap());
+    assert_eq!(response().ranged(&headers).get_status(), StatusCode::OK);
+}
@@ -1,7 +1,7 @@
 //! Property-based tests for routing system
 
 use quickcheck::{Arbitrary, Gen, QuickCheck};
-use ruffus::{Method, Response, Router};
+use ruffus::{Method, Request, Response, Router};
 
 // Helper to generate valid path segments
 #[derive(Clone, Debug)]
@@ -23,6 +23,7 @@ impl Arbitrary for ValidPathSegment {
 // Helper to generate valid path patterns
 #[derive(Clone, Debug)]
 struct ValidPath {
+    #[allow(dead_code)]
     segments: Vec<String>,
     pattern: String,
 }
@@ -68,11 +69,11 @@ fn prop_route_registration_preserved(method: TestMethod, path: ValidPath) -> boo
     
     // Register a route
     match method {
-        Method::GET => router.get(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::POST => router.post(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PUT => router.put(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::DELETE => router.delete(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PATCH => router.patch(&path.pattern, |_req| async { Ok(Response::new()) }),
+        Method::GET => router.get(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::POST => router.post(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PUT => router.put(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::DELETE => router.delete(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PATCH => router.patch(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
         _ => return true, // Skip unsupported methods
     };
     
@@ -107,11 +108,10 @@ fn prop_matching_requests_invoke_handlers(method: TestMethod, path: ValidPath) -
     rt.block_on(async {
         let mut router = Router::new("");
         let method = method.0;
-        let mut handler_called = false;
         
-        // Create a handler that sets a flag when called
+        // Create a handler for the route
         let handler = |_req: ruffus::Request| async {
-            Ok(ruffus::Response::text("handler called".to_string()))
+            Ok::<_, ruffus::Error>(ruffus::Response::text("handler called".to_string()))
         };
         
         // Register the route
@@ -175,11 +175,11 @@ fn prop_http_method_matching_exclusive(method1: TestMethod, method2: TestMethod,
     
     // Register a route with method1
     match method1 {
-        Method::GET => router.get(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::POST => router.post(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PUT => router.put(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::DELETE => router.delete(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PATCH => router.patch(&path.pattern, |_req| async { Ok(Response::new()) }),
+        Method::GET => router.get(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::POST => router.post(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PUT => router.put(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::DELETE => router.delete(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PATCH => router.patch(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
         _ => return true, // Skip unsupported methods
     };
     
@@ -224,11 +224,11 @@ fn prop_non_existent_routes_return_404(
     
     // Register a route with the first path
     match method {
-        Method::GET => router.get(&registered_path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::POST => router.post(&registered_path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PUT => router.put(&registered_path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::DELETE => router.delete(&registered_path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PATCH => router.patch(&registered_path.pattern, |_req| async { Ok(Response::new()) }),
+        Method::GET => router.get(&registered_path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::POST => router.post(&registered_path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PUT => router.put(&registered_path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::DELETE => router.delete(&registered_path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PATCH => router.patch(&registered_path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
         _ => return true, // Skip unsupported methods
     };
     
@@ -272,11 +272,11 @@ fn prop_wrong_method_returns_405(
     
     // Register a route with registered_method
     match registered_method {
-        Method::GET => router.get(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::POST => router.post(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PUT => router.put(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::DELETE => router.delete(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PATCH => router.patch(&path.pattern, |_req| async { Ok(Response::new()) }),
+        Method::GET => router.get(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::POST => router.post(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PUT => router.put(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::DELETE => router.delete(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PATCH => router.patch(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
         _ => return true, // Skip unsupported methods
     };
     
@@ -332,11 +332,11 @@ fn prop_router_prefix_prepends(prefix: ValidPrefix, path: ValidPath, method: Tes
     
     // Register a route
     match method {
-        Method::GET => router.get(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::POST => router.post(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PUT => router.put(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::DELETE => router.delete(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PATCH => router.patch(&path.pattern, |_req| async { Ok(Response::new()) }),
+        Method::GET => router.get(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::POST => router.post(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PUT => router.put(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::DELETE => router.delete(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PATCH => router.patch(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
         _ => return true, // Skip unsupported methods
     };
     
@@ -381,11 +381,11 @@ fn prop_mounted_router_routes_registered(
     
     // Register a route on the router
     match method {
-        Method::GET => router.get(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::POST => router.post(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PUT => router.put(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::DELETE => router.delete(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PATCH => router.patch(&path.pattern, |_req| async { Ok(Response::new()) }),
+        Method::GET => router.get(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::POST => router.post(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PUT => router.put(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::DELETE => router.delete(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PATCH => router.patch(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
         _ => return true, // Skip unsupported methods
     };
     
@@ -432,11 +432,11 @@ fn prop_nested_router_prefixes_combine(
     // Create the innermost router with prefix3
     let mut inner_router = Router::new(&prefix3.0);
     match method {
-        Method::GET => inner_router.get(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::POST => inner_router.post(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PUT => inner_router.put(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::DELETE => inner_router.delete(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PATCH => inner_router.patch(&path.pattern, |_req| async { Ok(Response::new()) }),
+        Method::GET => inner_router.get(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::POST => inner_router.post(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PUT => inner_router.put(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::DELETE => inner_router.delete(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PATCH => inner_router.patch(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
         _ => return true, // Skip unsupported methods
     };
     
@@ -483,7 +483,6 @@ fn prop_router_middleware_scopes(
 ) -> bool {
     use ruffus::middleware::{Middleware, Next};
     use async_trait::async_trait;
-    use std::sync::Arc;
     
     // Ensure the two routers have different prefixes
     if router1_prefix.0 == router2_prefix.0 {
@@ -506,22 +505,22 @@ fn prop_router_middleware_scopes(
     let mut router1 = Router::new(&router1_prefix.0);
     router1.use_middleware(std::sync::Arc::new(TestMiddleware));
     match method {
-        Method::GET => router1.get(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::POST => router1.post(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PUT => router1.put(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::DELETE => router1.delete(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PATCH => router1.patch(&path.pattern, |_req| async { Ok(Response::new()) }),
+        Method::GET => router1.get(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::POST => router1.post(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PUT => router1.put(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::DELETE => router1.delete(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PATCH => router1.patch(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
         _ => return true, // Skip unsupported methods
     };
     
     // Create router2 without middleware
     let mut router2 = Router::new(&router2_prefix.0);
     match method {
-        Method::GET => router2.get(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::POST => router2.post(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PUT => router2.put(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::DELETE => router2.delete(&path.pattern, |_req| async { Ok(Response::new()) }),
-        Method::PATCH => router2.patch(&path.pattern, |_req| async { Ok(Response::new()) }),
+        Method::GET => router2.get(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::POST => router2.post(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PUT => router2.put(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::DELETE => router2.delete(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PATCH => router2.patch(&path.pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
         _ => return true, // Skip unsupported methods
     };
     
@@ -544,3 +543,406 @@ mod test_router_middleware_scoping {
             .quickcheck(prop_router_middleware_scopes as fn(ValidPrefix, ValidPrefix, ValidPath, TestMethod) -> bool);
     }
 }
+
+// Helper to generate small route tables that overlap heavily
+#[derive(Clone, Debug)]
+struct RouteTable {
+    routes: Vec<(Method, String)>,
+    path: String,
+}
+
+impl Arbitrary for RouteTable {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let pattern_segments = ["a", "b", ":x", ":y", ":n<u8>", ":ab<[ab]>", "a.:x", ":x.:y", "v:n<u8>"];
+        let path_segments = ["a", "b", "c", "7", "300", "a.b", "x.y.z", "v7"];
+
+        let num_routes = (u8::arbitrary(g) % 6) + 1;
+        let routes = (0..num_routes)
+            .map(|_| {
+                let len = u8::arbitrary(g) % 4;
+                let mut segments: Vec<&str> = (0..len)
+                    .map(|_| *g.choose(&pattern_segments).unwrap())
+                    .collect();
+                // Wildcards and optional segments may only appear at the end of a pattern
+                match u8::arbitrary(g) % 6 {
+                    0 => segments.push("*rest"),
+                    1 => segments.push(":opt?"),
+                    2 => segments.extend([":opt?", ":more?"]),
+                    _ => {}
+                }
+                (TestMethod::arbitrary(g).0, format!("/{}", segments.join("/")))
+            })
+            .collect();
+
+        let len = u8::arbitrary(g) % 4;
+        let segments: Vec<&str> = (0..len)
+            .map(|_| *g.choose(&path_segments).unwrap())
+            .collect();
+
+        RouteTable {
+            routes,
+            path: format!("/{}", segments.join("/")),
+        }
+    }
+}
+
+fn register(router: &mut Router, method: Method, pattern: &str) {
+    match method {
+        Method::GET => router.get(pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::POST => router.post(pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PUT => router.put(pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::DELETE => router.delete(pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        Method::PATCH => router.patch(pattern, |_req: Request| async { Ok::<_, ruffus::Error>(Response::new()) }),
+        _ => router,
+    };
+}
+
+/// **Feature: rust-web-framework, Property 39: Route tree agrees with pattern matching**
+/// **Validates: Requirements 1.3, 6.3, 6.4**
+///
+/// For any set of routes and any path, the route tree should find exactly the
+/// routes whose patterns match the path, and report their methods as allowed.
+fn prop_route_tree_agrees_with_patterns(table: RouteTable, method: TestMethod) -> bool {
+    let mut router = Router::new("");
+    for (route_method, pattern) in &table.routes {
+        register(&mut router, *route_method, pattern);
+    }
+
+    let matching: Vec<&ruffus::Route> = router
+        .routes()
+        .iter()
+        .filter(|route| route.pattern().matches(&table.path).is_some())
+        .collect();
+
+    let path_exists_agrees = router.path_exists(&table.path) != matching.is_empty();
+
+    let allowed = router.allowed_methods(&table.path);
+    let allowed_agrees = matching.iter().all(|route| allowed.contains(route.method()))
+        && allowed.iter().all(|m| matching.iter().any(|route| route.method() == m));
+
+    let expects_route = matching.iter().any(|route| *route.method() == method.0);
+    let found_agrees = match router.find_route(&method.0, &table.path) {
+        Some((route, params)) => {
+            expects_route
+                && *route.method() == method.0
+                && route.pattern().matches(&table.path) == Some(params)
+        }
+        None => !expects_route,
+    };
+
+    path_exists_agrees && allowed_agrees && found_agrees
+}
+
+#[cfg(test)]
+mod test_route_tree {
+    use super::*;
+
+    #[test]
+    fn run_route_tree_agrees_with_patterns_property() {
+        QuickCheck::new()
+            .tests(500)
+            .quickcheck(prop_route_tree_agrees_with_patterns as fn(RouteTable, TestMethod) -> bool);
+    }
+}
+
+/// **Feature: rust-web-framework, Property 40: Static segments take priority over dynamic ones**
+/// **Validates: Requirements 1.3**
+///
+/// For any static route that overlaps a dynamic route, requests for the static
+/// path should reach the static route regardless of registration order.
+fn prop_static_segments_take_priority(literal: ValidPathSegment, other: ValidPathSegment, static_first: bool) -> bool {
+    if literal.0 == other.0 {
+        return true; // Discard this test case
+    }
+
+    let static_pattern = format!("/users/{}", literal.0);
+    let mut router = Router::new("");
+    if static_first {
+        register(&mut router, Method::GET, &static_pattern);
+        register(&mut router, Method::GET, "/users/:id");
+    } else {
+        register(&mut router, Method::GET, "/users/:id");
+        register(&mut router, Method::GET, &static_pattern);
+    }
+
+    let static_hit = router
+        .find_route(&Method::GET, &static_pattern)
+        .map(|(route, params)| route.pattern().raw() == static_pattern && params.is_empty())
+        .unwrap_or(false);
+
+    let dynamic_hit = router
+        .find_route(&Method::GET, &format!("/users/{}", other.0))
+        .map(|(route, params)| route.pattern().raw() == "/users/:id" && params.get("id") == Some(&other.0))
+        .unwrap_or(false);
+
+    static_hit && dynamic_hit
+}
+
+#[cfg(test)]
+mod test_static_priority {
+    use super::*;
+
+    #[test]
+    fn run_static_segments_take_priority_property() {
+        QuickCheck::new()
+            .tests(100)
+            .quickcheck(prop_static_segments_take_priority as fn(ValidPathSegment, ValidPathSegment, bool) -> bool);
+    }
+}
+
+/// **Feature: rust-web-framework, Property 41: Wildcards capture the rest of the path**
+/// **Validates: Requirements 1.3, 2.1**
+///
+/// For any path below a wildcard route, the wildcard should capture the
+/// remaining segments joined by slashes, and more specific routes should win.
+fn prop_wildcards_capture_rest_of_path(tail: Vec<ValidPathSegment>, mounted: bool) -> bool {
+    let tail: Vec<String> = tail.into_iter().take(5).map(|s| s.0).collect();
+
+    let mut router = Router::new("");
+    if mounted {
+        let mut files = Router::new("");
+        register(&mut files, Method::GET, "/");
+        router.mount("/files/*path", files);
+    } else {
+        register(&mut router, Method::GET, "/files/*path");
+    }
+    register(&mut router, Method::GET, "/files/:name/meta");
+    register(&mut router, Method::GET, "/files/readme");
+
+    let path = format!("/files/{}", tail.join("/"));
+    let (route, params) = match router.find_route(&Method::GET, &path) {
+        Some(found) => found,
+        None => return false,
+    };
+
+    let expected = if tail.len() == 1 && tail[0] == "readme" {
+        "/files/readme"
+    } else if tail.len() == 2 && tail[1] == "meta" {
+        "/files/:name/meta"
+    } else {
+        ""
+    };
+
+    if !expected.is_empty() {
+        return route.pattern().raw() == expected;
+    }
+
+    params.get("path") == Some(&tail.join("/")) && route.pattern().matches(&path) == Some(params)
+}
+
+#[cfg(test)]
+mod test_wildcards {
+    use super::*;
+
+    #[test]
+    fn run_wildcards_capture_rest_of_path_property() {
+        QuickCheck::new()
+            .tests(200)
+            .quickcheck(prop_wildcards_capture_rest_of_path as fn(Vec<ValidPathSegment>, bool) -> bool);
+    }
+
+    #[test]
+    #[should_panic(expected = "wildcard must be the last segment")]
+    fn wildcard_must_be_last() {
+        ruffus::PathPattern::parse("/files/*path/meta");
+    }
+
+    #[test]
+    #[should_panic(expected = "can't mount a router at wildcard prefix `/assets/*file`: `/foo` is below the router's root")]
+    fn wildcard_mount_rejects_routes_below_root() {
+        let mut assets = Router::new("");
+        register(&mut assets, Method::GET, "/");
+        register(&mut assets, Method::GET, "/foo");
+
+        let mut router = Router::new("");
+        router.mount("/assets/*file", assets);
+    }
+
+    #[test]
+    #[should_panic(expected = "`/v1` is below the router's root")]
+    fn wildcard_mount_rejects_prefixed_routers() {
+        let mut api = Router::new("/v1");
+        register(&mut api, Method::GET, "/");
+
+        let mut router = Router::new("");
+        router.mount("/api/*rest", api);
+    }
+}
+
+/// **Feature: rust-web-framework, Property 42: Constrained parameters only match valid values**
+/// **Validates: Requirements 1.3, 2.1**
+///
+/// For any segment, a constrained route should match exactly the values its
+/// constraint accepts, and other values should fall through to a plain
+/// parameter registered on the same path.
+fn prop_constrained_params_fall_through(id: u64, name: ValidPathSegment, typed_first: bool) -> bool {
+    let mut router = Router::new("");
+    if typed_first {
+        register(&mut router, Method::GET, "/users/:id<u64>");
+        register(&mut router, Method::GET, "/users/:name");
+    } else {
+        register(&mut router, Method::GET, "/users/:name");
+        register(&mut router, Method::GET, "/users/:id<u64>");
+    }
+    register(&mut router, Method::GET, "/posts/:slug<[a-z0-9-]+>");
+    register(&mut router, Method::GET, "/orders/:uuid<uuid>");
+
+    let id_hit = router
+        .find_route(&Method::GET, &format!("/users/{}", id))
+        .map(|(route, params)| route.pattern().raw() == "/users/:id<u64>" && params.get("id") == Some(&id.to_string()))
+        .unwrap_or(false);
+
+    let name_is_numeric = name.0.parse::<u64>().is_ok();
+    let name_hit = router
+        .find_route(&Method::GET, &format!("/users/{}", name.0))
+        .map(|(route, params)| {
+            if name_is_numeric {
+                params.get("id") == Some(&name.0)
+            } else {
+                route.pattern().raw() == "/users/:name" && params.get("name") == Some(&name.0)
+            }
+        })
+        .unwrap_or(false);
+
+    let slug_is_valid = name.0.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    let slug_agrees = router.path_exists(&format!("/posts/{}", name.0)) == slug_is_valid;
+
+    let uuid_agrees = router.path_exists("/orders/67e55044-10b1-426f-9247-bb680e5fe0c8")
+        && !router.path_exists(&format!("/orders/{}", id));
+
+    id_hit && name_hit && slug_agrees && uuid_agrees
+}
+
+#[cfg(test)]
+mod test_constrained_params {
+    use super::*;
+
+    #[test]
+    fn run_constrained_params_fall_through_property() {
+        QuickCheck::new()
+            .tests(200)
+            .quickcheck(prop_constrained_params_fall_through as fn(u64, ValidPathSegment, bool) -> bool);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid route parameter constraint")]
+    fn invalid_constraint_panics() {
+        ruffus::PathPattern::parse("/users/:id<[a-z>");
+    }
+}
+
+/// **Feature: rust-web-framework, Property 43: Mixed and optional segments**
+/// **Validates: Requirements 1.3, 2.1**
+///
+/// For any file name and extension, a mixed segment should split the name at
+/// its last literal separator, `-` should separate two parameters, and
+/// optional trailing parameters should match with or without a value.
+fn prop_mixed_and_optional_segments(name: Vec<ValidPathSegment>, ext: ValidPathSegment, page: Option<ValidPathSegment>) -> bool {
+    if name.is_empty() {
+        return true; // Discard this test case
+    }
+    let name: Vec<String> = name.into_iter().take(3).map(|s| s.0).collect();
+    let name = name.join(".");
+
+    let mut router = Router::new("");
+    register(&mut router, Method::GET, "/report.:format");
+    register(&mut router, Method::GET, "/files/:name.:ext");
+    register(&mut router, Method::GET, "/posts/:page?");
+    register(&mut router, Method::GET, "/flights/:from-:to");
+
+    let report_hit = router
+        .find_route(&Method::GET, &format!("/report.{}", ext.0))
+        .map(|(route, params)| route.pattern().raw() == "/report.:format" && params.get("format") == Some(&ext.0))
+        .unwrap_or(false);
+
+    let file_hit = router
+        .find_route(&Method::GET, &format!("/files/{}.{}", name, ext.0))
+        .map(|(_, params)| params.get("name") == Some(&name) && params.get("ext") == Some(&ext.0))
+        .unwrap_or(false);
+
+    let to = ext.0.replace('-', "_");
+    let flight_hit = router
+        .find_route(&Method::GET, &format!("/flights/{}-{}", name, to))
+        .map(|(_, params)| params.get("from") == Some(&name) && params.get("to") == Some(&to))
+        .unwrap_or(false);
+
+    // Without a separator there is nothing to split
+    let no_ext_miss = !router.path_exists(&format!("/files/{}", ext.0));
+
+    let path = match &page {
+        Some(page) => format!("/posts/{}", page.0),
+        None => "/posts".to_string(),
+    };
+    let page_hit = router
+        .find_route(&Method::GET, &path)
+        .map(|(_, params)| params.get("page") == page.as_ref().map(|p| &p.0))
+        .unwrap_or(false);
+
+    let too_deep_miss = !router.path_exists(&format!("{}/{}/extra", path, ext.0));
+
+    report_hit && file_hit && flight_hit && no_ext_miss && page_hit && too_deep_miss
+}
+
+#[cfg(test)]
+mod test_mixed_and_optional_segments {
+    use super::*;
+
+    #[test]
+    fn run_mixed_and_optional_segments_property() {
+        QuickCheck::new()
+            .tests(200)
+            .quickcheck(prop_mixed_and_optional_segments as fn(Vec<ValidPathSegment>, ValidPathSegment, Option<ValidPathSegment>) -> bool);
+    }
+
+    #[test]
+    fn optional_segments_in_mount_prefix() {
+        let mut posts = Router::new("");
+        register(&mut posts, Method::GET, "/");
+
+        let mut router = Router::new("");
+        router.mount("/posts/:page?", posts);
+
+        assert!(router.path_exists("/posts"));
+        let (_, params) = router.find_route(&Method::GET, "/posts/3").unwrap();
+        assert_eq!(params.get("page"), Some(&"3".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "optional segments must come last")]
+    fn optional_must_be_last() {
+        ruffus::PathPattern::parse("/posts/:page?/comments");
+    }
+
+    #[test]
+    #[should_panic(expected = "parameters must be separated by literal text")]
+    fn adjacent_params_panic() {
+        ruffus::PathPattern::parse("/files/:name:ext");
+    }
+
+    #[test]
+    fn colons_before_digits_are_literal() {
+        let mut router = Router::new("");
+        register(&mut router, Method::GET, "/time/12:30");
+        register(&mut router, Method::GET, "/books/isbn:978-:id");
+
+        let (route, params) = router.find_route(&Method::GET, "/time/12:30").unwrap();
+        assert_eq!(route.pattern().raw(), "/time/12:30");
+        assert!(params.is_empty());
+        assert!(!router.path_exists("/time/12:45"));
+
+        let (_, params) = router.find_route(&Method::GET, "/books/isbn:978-7").unwrap();
+        assert_eq!(params.get("id"), Some(&"7".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "parameter without a name")]
+    fn unnamed_param_panics() {
+        ruffus::PathPattern::parse("/a/:");
+    }
+
+    #[test]
+    #[should_panic(expected = "parameter without a name")]
+    fn unnamed_constrained_param_panics() {
+        ruffus::PathPattern::parse("/a/:<u64>");
+    }
+}
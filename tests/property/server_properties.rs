@@ -0,0 +1,442 @@
+//! Property-based tests for the HTTP server
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::{StatusCode, Version};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::{App, Request, Response, Server};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+async fn connect(port: u16) -> TcpStream {
+    TcpStream::connect(("127.0.0.1", port)).await.unwrap()
+}
+
+// App with a handler that sleeps for the number of milliseconds in the path
+fn sleepy_app() -> App {
+    let mut app = App::new();
+    app.get("/sleep/:ms<u64>", |req: Request| async move {
+        let ms: u64 = req.param("ms").unwrap().parse().unwrap();
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        Ok::<_, ruffus::Error>(Response::text(format!("slept {}", ms)))
+    });
+    app
+}
+
+// Sends a GET request over a new HTTP/2 prior-knowledge connection
+async fn h2_get(port: u16, path: &str) -> (StatusCode, Version, String) {
+    let stream = connect(port).await;
+    let (mut sender, conn) = hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+        .await
+        .unwrap();
+    tokio::spawn(conn);
+
+    let req = hyper::Request::get(format!("http://127.0.0.1:{}{}", port, path))
+        .body(Empty::<Bytes>::new())
+        .unwrap();
+    let res = sender.send_request(req).await.unwrap();
+    let (status, version) = (res.status(), res.version());
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    (status, version, String::from_utf8(body.to_vec()).unwrap())
+}
+
+// Starts a server on a free port that shuts down when the returned sender fires
+async fn spawn_server(server: Server) -> (u16, oneshot::Sender<()>, tokio::task::JoinHandle<ruffus::Result<()>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let handle = tokio::spawn(server.serve_with_shutdown(listener, async {
+        stopped.await.ok();
+    }));
+    (port, stop, handle)
+}
+
+// **Feature: rust-web-framework, Property 47: In-flight requests finish during graceful shutdown**
+// **Validates: Requirements 1.5, 10.2**
+fn prop_in_flight_requests_finish_on_shutdown(delay_ms: u8) -> bool {
+    let delay_ms = 50 + delay_ms as u64;
+    let rt = tokio::runtime::Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let (port, stop, server) = spawn_server(Server::new(sleepy_app())).await;
+
+        let mut stream = connect(port).await;
+        let request = format!("GET /sleep/{} HTTP/1.1\r\nHost: localhost\r\n\r\n", delay_ms);
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        // Signal shutdown while the handler is still running
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        stop.send(()).unwrap();
+
+        // The response arrives in full and the connection is then closed
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let completed = response.starts_with("HTTP/1.1 200 OK")
+            && response.ends_with(&format!("slept {}", delay_ms));
+
+        let stopped = tokio::time::timeout(Duration::from_secs(5), server).await.is_ok();
+        let refused = TcpStream::connect(("127.0.0.1", port)).await.is_err();
+
+        completed && stopped && refused
+    })
+}
+
+#[test]
+fn test_in_flight_requests_finish_on_shutdown_property() {
+    QuickCheck::new()
+        .tests(10)
+        .quickcheck(prop_in_flight_requests_finish_on_shutdown as fn(u8) -> bool);
+}
+
+// **Feature: rust-web-framework, Property 48: Idle keep-alive connections close on shutdown**
+// **Validates: Requirements 1.5**
+#[tokio::test]
+async fn test_idle_keep_alive_connections_close_on_shutdown() {
+    let (port, stop, server) = spawn_server(Server::new(sleepy_app())).await;
+
+    let mut stream = connect(port).await;
+    stream
+        .write_all(b"GET /sleep/0 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+
+    // Read the first response, leaving the connection idle but open
+    let mut buf = vec![0; 1024];
+    let n = stream.read(&mut buf).await.unwrap();
+    assert!(String::from_utf8_lossy(&buf[..n]).ends_with("slept 0"));
+
+    stop.send(()).unwrap();
+
+    let started = Instant::now();
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server should not wait for idle connections")
+        .unwrap()
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    // The server closed the idle connection
+    assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+}
+
+// **Feature: rust-web-framework, Property 49: Drain timeout bounds graceful shutdown**
+// **Validates: Requirements 1.5**
+#[tokio::test]
+async fn test_drain_timeout_bounds_shutdown() {
+    let server = Server::new(sleepy_app()).drain_timeout(Duration::from_millis(100));
+    let (port, stop, server) = spawn_server(server).await;
+
+    let mut stream = connect(port).await;
+    stream
+        .write_all(b"GET /sleep/10000 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let started = Instant::now();
+    stop.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("drain timeout should stop the server")
+        .unwrap()
+        .unwrap();
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_secs(2));
+
+    // The unfinished request was cut off without a response
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    assert!(response.is_empty());
+}
+
+// **Feature: rust-web-framework, Property 50: HTTP/1.1 and HTTP/2 share a port**
+// **Validates: Requirements 1.2, 1.3**
+fn prop_http1_and_http2_on_same_port(word: String) -> TestResult {
+    if word.is_empty() || !word.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return TestResult::discard();
+    }
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let (port, stop, server) = spawn_server(Server::new(sleepy_app())).await;
+        let path = format!("/sleep/0?word={}", word);
+
+        let mut stream = connect(port).await;
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut http1 = String::new();
+        stream.read_to_string(&mut http1).await.unwrap();
+
+        let (status, version, body) = h2_get(port, &path).await;
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+
+        TestResult::from_bool(
+            http1.starts_with("HTTP/1.1 200 OK")
+                && http1.ends_with("slept 0")
+                && status == StatusCode::OK
+                && version == Version::HTTP_2
+                && body == "slept 0",
+        )
+    })
+}
+
+#[test]
+fn test_http1_and_http2_on_same_port_property() {
+    QuickCheck::new()
+        .tests(10)
+        .quickcheck(prop_http1_and_http2_on_same_port as fn(String) -> TestResult);
+}
+
+#[tokio::test]
+async fn test_http2_settings_apply() {
+    let server = Server::new(sleepy_app())
+        .http2_max_concurrent_streams(1)
+        .http2_initial_stream_window_size(128 * 1024)
+        .http2_initial_connection_window_size(256 * 1024)
+        .http2_keep_alive_interval(Duration::from_millis(50))
+        .http2_keep_alive_timeout(Duration::from_secs(1));
+    let (port, stop, server) = spawn_server(server).await;
+
+    let stream = connect(port).await;
+    let (sender, conn) = hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+        .await
+        .unwrap();
+    tokio::spawn(conn);
+
+    // A first request makes sure the client has seen the server settings
+    let req = hyper::Request::get(format!("http://127.0.0.1:{}/sleep/0", port))
+        .body(Empty::<Bytes>::new())
+        .unwrap();
+    assert_eq!(sender.clone().send_request(req).await.unwrap().status(), StatusCode::OK);
+
+    // With a single stream allowed, concurrent requests on one connection
+    // are sent one after the other
+    let started = Instant::now();
+    let requests = (0..2).map(|_| {
+        let mut sender = sender.clone();
+        let req = hyper::Request::get(format!("http://127.0.0.1:{}/sleep/100", port))
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        tokio::spawn(async move { sender.send_request(req).await.unwrap().status() })
+    });
+    for request in requests.collect::<Vec<_>>() {
+        assert_eq!(request.await.unwrap(), StatusCode::OK);
+    }
+    assert!(started.elapsed() >= Duration::from_millis(200));
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+// **Feature: rust-web-framework, Property 51: HTTP/2 streams finish during graceful shutdown**
+// **Validates: Requirements 1.5**
+#[tokio::test]
+async fn test_http2_streams_finish_on_shutdown() {
+    let (port, stop, server) = spawn_server(Server::new(sleepy_app())).await;
+
+    let request = tokio::spawn(async move { h2_get(port, "/sleep/150").await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stop.send(()).unwrap();
+
+    let (status, version, body) = request.await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(version, Version::HTTP_2);
+    assert_eq!(body, "slept 150");
+
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server should stop once the stream is done")
+        .unwrap()
+        .unwrap();
+}
+
+// Reads from the stream until the server closes it or `limit` passes
+async fn read_until_close(stream: &mut TcpStream, limit: Duration) -> Option<String> {
+    let mut response = Vec::new();
+    tokio::time::timeout(limit, stream.read_to_end(&mut response))
+        .await
+        .ok()
+        .map(|_| String::from_utf8_lossy(&response).into_owned())
+}
+
+#[tokio::test]
+async fn test_header_read_timeout_closes_slow_connections() {
+    let server = Server::new(sleepy_app()).header_read_timeout(Duration::from_millis(100));
+    let (port, stop, server) = spawn_server(server).await;
+
+    // A slowloris client that never finishes its headers
+    let mut stream = connect(port).await;
+    stream.write_all(b"GET /sleep/0 HTTP/1.1\r\nHost: loc").await.unwrap();
+
+    let started = Instant::now();
+    let response = read_until_close(&mut stream, Duration::from_secs(5)).await;
+    assert!(response.is_some(), "connection should be closed");
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_request_body_timeout() {
+    let mut app = App::new();
+    app.post("/upload", |mut req: Request| async move {
+        Ok::<_, ruffus::Error>(Response::text(format!("{} bytes", req.body().await?.len())))
+    });
+    let server = Server::new(app).request_body_timeout(Duration::from_millis(100));
+    let (port, stop, server) = spawn_server(server).await;
+
+    // Promise ten bytes and send two
+    let mut stream = connect(port).await;
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nab")
+        .await
+        .unwrap();
+    let response = read_until_close(&mut stream, Duration::from_secs(5)).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 408 Request Timeout"), "{}", response);
+
+    // A complete body is unaffected
+    let mut stream = connect(port).await;
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\nConnection: close\r\n\r\nab")
+        .await
+        .unwrap();
+    let response = read_until_close(&mut stream, Duration::from_secs(5)).await.unwrap();
+    assert!(response.ends_with("2 bytes"), "{}", response);
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_keep_alive_can_be_disabled() {
+    for keep_alive in [true, false] {
+        let (port, stop, server) = spawn_server(Server::new(sleepy_app()).keep_alive(keep_alive)).await;
+
+        let mut stream = connect(port).await;
+        stream
+            .write_all(b"GET /sleep/0 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let response = read_until_close(&mut stream, Duration::from_millis(300)).await;
+
+        // Without keep-alive the server closes the connection after responding
+        match response {
+            Some(response) => {
+                assert!(!keep_alive);
+                assert!(response.contains("connection: close"));
+                assert!(response.ends_with("slept 0"));
+            }
+            None => assert!(keep_alive),
+        }
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
+
+// **Feature: rust-web-framework, Property 55: Header limits reject oversized requests**
+// **Validates: Requirements 9.2**
+fn prop_header_limits(extra_headers: u8, header_len: u16) -> bool {
+    let extra_headers = extra_headers as usize % 20;
+    let header_len = header_len as usize % 20_000;
+    let rt = tokio::runtime::Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let server = Server::new(sleepy_app()).max_headers(10).max_header_size(10_000);
+        let (port, stop, server) = spawn_server(server).await;
+
+        let mut request = String::from("GET /sleep/0 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n");
+        for i in 0..extra_headers {
+            request.push_str(&format!("X-Extra-{}: {}\r\n", i, i));
+        }
+        request.push_str(&format!("X-Long: {}\r\n\r\n", "a".repeat(header_len)));
+
+        let mut stream = connect(port).await;
+        // The server may close the connection before reading everything
+        let _ = stream.write_all(request.as_bytes()).await;
+        let response = read_until_close(&mut stream, Duration::from_secs(5)).await.unwrap_or_default();
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+
+        // Host, Connection and X-Long plus the extra headers
+        let too_many = extra_headers + 3 > 10;
+        let too_large = request.len() > 10_000;
+        if too_many || too_large {
+            response.starts_with("HTTP/1.1 431 Request Header Fields Too Large")
+        } else {
+            response.starts_with("HTTP/1.1 200 OK")
+        }
+    })
+}
+
+#[test]
+fn test_header_limits_property() {
+    // Heads of exactly 10 000 and 10 001 bytes
+    assert!(prop_header_limits(0, 9929));
+    assert!(prop_header_limits(0, 9930));
+
+    QuickCheck::new()
+        .tests(20)
+        .quickcheck(prop_header_limits as fn(u8, u16) -> bool);
+}
+
+#[tokio::test]
+async fn test_half_close() {
+    for half_close in [true, false] {
+        let (port, stop, server) = spawn_server(Server::new(sleepy_app()).half_close(half_close)).await;
+
+        // Send the request, then shut down the write side
+        let mut stream = connect(port).await;
+        stream
+            .write_all(b"GET /sleep/50 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        stream.shutdown().await.unwrap();
+
+        let response = read_until_close(&mut stream, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(response.ends_with("slept 50"), half_close, "{}", response);
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
+
+// **Feature: rust-web-framework, Property 56: Connection limit applies backpressure at accept**
+// **Validates: Requirements 10.2**
+#[tokio::test]
+async fn test_max_connections() {
+    let (port, stop, server) = spawn_server(Server::new(sleepy_app()).max_connections(1)).await;
+
+    // The first connection stays open after its response
+    let mut first = connect(port).await;
+    first
+        .write_all(b"GET /sleep/0 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut buf = vec![0; 1024];
+    let n = first.read(&mut buf).await.unwrap();
+    assert!(String::from_utf8_lossy(&buf[..n]).ends_with("slept 0"));
+
+    // The second one waits in the backlog
+    let mut second = connect(port).await;
+    second
+        .write_all(b"GET /sleep/1 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    assert!(read_until_close(&mut second, Duration::from_millis(200)).await.is_none());
+
+    // Closing the first connection lets the second through
+    drop(first);
+    let response = read_until_close(&mut second, Duration::from_secs(5)).await.unwrap();
+    assert!(response.ends_with("slept 1"), "{}", response);
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
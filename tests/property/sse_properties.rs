@@ -0,0 +1,118 @@
+//! Property-based tests for Server-Sent Events
+
+use bytes::Bytes;
+use futures_util::StreamExt;
+use http::{HeaderMap, Method};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::sse::{Event, KeepAlive, LastEventId, Sse};
+use ruffus::{App, IntoResponse, Request, Response};
+use std::convert::Infallible;
+use std::time::Duration;
+
+// Reads the `data` of one event the way an `EventSource` does
+fn parse_data(event: &str) -> Option<String> {
+    let body = event.strip_suffix("\n\n")?;
+    let lines: Vec<&str> = body
+        .split('\n')
+        .filter_map(|line| line.strip_prefix("data: "))
+        .collect();
+    Some(lines.join("\n"))
+}
+
+// **Feature: rust-web-framework, Property 60: SSE data survives multi-line framing**
+// **Validates: Requirements 5.1**
+fn prop_sse_data_framing(data: String, id: String, event: String) -> TestResult {
+    if [&id, &event].iter().any(|s| s.contains(['\n', '\r', '\0'])) {
+        return TestResult::discard();
+    }
+
+    let encoded = Event::default().event(&event).id(&id).data(&data).to_string();
+
+    // Every field is on its own line and the event ends with a blank line
+    let fields_ok = encoded.starts_with(&format!("event: {}\nid: {}\n", event, id))
+        && encoded.matches("\n\n").count() == 1
+        && !encoded.contains('\r');
+    let expected = data.replace("\r\n", "\n").replace('\r', "\n");
+    TestResult::from_bool(fields_ok && parse_data(&encoded) == Some(expected))
+}
+
+#[test]
+fn test_sse_data_framing_property() {
+    QuickCheck::new()
+        .tests(200)
+        .quickcheck(prop_sse_data_framing as fn(String, String, String) -> TestResult);
+}
+
+#[test]
+fn test_sse_fields() {
+    let event = Event::default()
+        .comment("hello")
+        .retry(Duration::from_secs(3))
+        .data("");
+    assert_eq!(event.to_string(), ": hello\nretry: 3000\ndata: \n\n");
+
+    let json = Event::default().json_data(&serde_json::json!({ "n": 1 })).unwrap();
+    assert_eq!(json.to_string(), "data: {\"n\":1}\n\n");
+}
+
+#[test]
+#[should_panic(expected = "line breaks")]
+fn test_sse_id_rejects_line_breaks() {
+    let _ = Event::default().id("1\n2");
+}
+
+#[tokio::test]
+async fn test_sse_response_streams_events() {
+    let events = futures_util::stream::iter(vec![
+        Ok::<_, Infallible>(Event::default().id("1").data("one")),
+        Ok(Event::default().id("2").data("two")),
+    ]);
+    let response = Sse::new(events).into_response();
+
+    let headers = response.get_headers();
+    assert_eq!(headers.get("content-type").unwrap(), "text/event-stream");
+    assert_eq!(headers.get("cache-control").unwrap(), "no-cache");
+    assert_eq!(
+        response.into_bytes().await.unwrap(),
+        Bytes::from("id: 1\ndata: one\n\nid: 2\ndata: two\n\n")
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_sse_keep_alive_comments() {
+    // One event, then silence
+    let events = futures_util::stream::iter(vec![Ok::<_, Infallible>(Event::default().data("hi"))])
+        .chain(futures_util::stream::pending());
+    let response = Sse::new(events)
+        .keep_alive(KeepAlive::new().interval(Duration::from_secs(10)).text("ping"))
+        .into_response();
+    let mut body = response.into_body();
+
+    let started = tokio::time::Instant::now();
+    assert_eq!(body.next().await.unwrap().unwrap(), "data: hi\n\n");
+    assert_eq!(body.next().await.unwrap().unwrap(), ": ping\n\n");
+    assert_eq!(started.elapsed(), Duration::from_secs(10));
+    assert_eq!(body.next().await.unwrap().unwrap(), ": ping\n\n");
+    assert_eq!(started.elapsed(), Duration::from_secs(20));
+}
+
+#[tokio::test]
+async fn test_last_event_id_extractor() {
+    let mut app = App::new();
+    app.get("/events", |LastEventId(id): LastEventId| async move {
+        Response::text(id.unwrap_or_else(|| "none".to_string()))
+    });
+
+    let request = |last: Option<&str>| {
+        let mut headers = HeaderMap::new();
+        if let Some(last) = last {
+            headers.insert("last-event-id", last.parse().unwrap());
+        }
+        Request::new(Method::GET, "/events".parse().unwrap(), headers, Bytes::new())
+    };
+
+    let first = app.handle_request(request(None)).await.unwrap();
+    assert_eq!(first.buffered_body().unwrap(), "none");
+    let resumed = app.handle_request(request(Some("41"))).await.unwrap();
+    assert_eq!(resumed.buffered_body().unwrap(), "41");
+}
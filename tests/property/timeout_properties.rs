@@ -0,0 +1,105 @@
+//! Property-based tests for the Timeout middleware
+
+use http::{HeaderMap, Method, StatusCode};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::timeout::Timeout;
+use ruffus::{App, Error, Request, Router};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn paused_runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .unwrap()
+}
+
+fn request(path: &str) -> Request {
+    Request::new(Method::GET, path.parse().unwrap(), HeaderMap::new(), "")
+}
+
+fn sleepy_app(timeout: Timeout, done: Arc<AtomicBool>) -> App {
+    let mut app = App::new();
+    app.use_middleware(Arc::new(timeout));
+    app.get("/sleep/:ms", move |req: Request| {
+        let done = done.clone();
+        async move {
+            let ms: u64 = req.param("ms").unwrap().parse().unwrap();
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            done.store(true, Ordering::SeqCst);
+            "awake"
+        }
+    });
+    app
+}
+
+// **Feature: rust-web-framework, Property 69: Requests outliving their timeout are cancelled**
+// **Validates: Requirements 4.1, 4.4**
+fn prop_requests_outliving_their_timeout_are_cancelled(handler_ms: u16, timeout_ms: u16) -> TestResult {
+    if handler_ms == timeout_ms || timeout_ms == 0 {
+        return TestResult::discard();
+    }
+    let done = Arc::new(AtomicBool::new(false));
+    let app = sleepy_app(Timeout::new(Duration::from_millis(timeout_ms as u64)), done.clone());
+
+    paused_runtime().block_on(async {
+        let result = app.handle_request(request(&format!("/sleep/{}", handler_ms))).await;
+        let ok = match result {
+            Ok(response) => handler_ms < timeout_ms && response.get_status() == StatusCode::OK,
+            Err(Error::Custom { status, .. }) => handler_ms > timeout_ms && status == StatusCode::SERVICE_UNAVAILABLE,
+            Err(_) => false,
+        };
+
+        // A cancelled handler never resumes
+        tokio::time::sleep(Duration::from_millis(handler_ms as u64 + 1)).await;
+        TestResult::from_bool(ok && done.load(Ordering::SeqCst) == (handler_ms < timeout_ms))
+    })
+}
+
+#[test]
+fn test_requests_outliving_their_timeout_are_cancelled_property() {
+    QuickCheck::new()
+        .tests(100)
+        .quickcheck(prop_requests_outliving_their_timeout_are_cancelled as fn(u16, u16) -> TestResult);
+}
+
+#[test]
+fn test_timeout_status_and_router_scope() {
+    paused_runtime().block_on(async {
+        let done = Arc::new(AtomicBool::new(false));
+        let timeout = Timeout::new(Duration::from_secs(1)).status(StatusCode::GATEWAY_TIMEOUT);
+        let mut app = sleepy_app(timeout, done);
+
+        // A longer timeout on a router doesn't lift the app's
+        let mut slow = Router::new("/slow");
+        slow.use_middleware(Arc::new(Timeout::new(Duration::from_secs(5))));
+        slow.get("/:ms", |req: Request| async move {
+            let ms: u64 = req.param("ms").unwrap().parse().unwrap();
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            "done"
+        });
+        app.mount("", slow);
+
+        let error = app.handle_request(request("/sleep/1500")).await.err().unwrap();
+        assert_eq!(error.status_code(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(error.to_string(), "504 Gateway Timeout: Request timed out after 1s");
+        assert!(app.handle_request(request("/sleep/500")).await.is_ok());
+
+        let error = app.handle_request(request("/slow/3000")).await.err().unwrap();
+        assert_eq!(error.status_code(), StatusCode::GATEWAY_TIMEOUT);
+
+        let mut app = App::new();
+        app.use_middleware(Arc::new(Timeout::new(Duration::from_secs(1))));
+        let mut slow = Router::new("/slow");
+        slow.use_middleware(Arc::new(Timeout::new(Duration::from_millis(100))));
+        slow.get("/", || async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            "done"
+        });
+        app.mount("", slow);
+        let error = app.handle_request(request("/slow")).await.err().unwrap();
+        assert_eq!(error.to_string(), "503 Service Unavailable: Request timed out after 100ms");
+    });
+}
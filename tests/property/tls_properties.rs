@@ -0,0 +1,263 @@
+//! Property-based tests for TLS termination
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::Version;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::{App, Request, Response, Server, TlsConfig};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+// A self-signed certificate for localhost, as PEM certificate and key
+fn self_signed() -> (String, String) {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    (cert.cert.pem(), cert.key_pair.serialize_pem())
+}
+
+fn echo_app() -> App {
+    let mut app = App::new();
+    app.get("/echo/:word", |req: Request| async move {
+        Ok::<_, ruffus::Error>(Response::text(req.param("word").unwrap().to_string()))
+    });
+    app
+}
+
+// Starts an HTTPS server on a free port that shuts down when the returned sender fires
+async fn spawn_tls_server(tls: TlsConfig) -> (u16, oneshot::Sender<()>, tokio::task::JoinHandle<ruffus::Result<()>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let handle = tokio::spawn(Server::new(echo_app()).serve_tls_with_shutdown(listener, tls, async {
+        stopped.await.ok();
+    }));
+    (port, stop, handle)
+}
+
+struct HttpsResponse {
+    alpn: Option<Vec<u8>>,
+    version: Version,
+    body: String,
+    certificate: CertificateDer<'static>,
+}
+
+// Sends a GET request over a new TLS connection trusting the given certificates
+async fn https_get(port: u16, path: &str, trusted: &[&str], alpn: &[&[u8]]) -> HttpsResponse {
+    let mut roots = RootCertStore::empty();
+    for pem in trusted {
+        roots.add(cert_der(pem)).unwrap();
+    }
+    let mut config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+
+    let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .unwrap();
+
+    let (_, session) = stream.get_ref();
+    let negotiated = session.alpn_protocol().map(|p| p.to_vec());
+    let certificate = session.peer_certificates().unwrap()[0].clone().into_owned();
+
+    let req = hyper::Request::get(format!("https://localhost:{}{}", port, path))
+        .body(Empty::<Bytes>::new())
+        .unwrap();
+    let res = if negotiated.as_deref() == Some(b"h2") {
+        let (mut sender, conn) = hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+            .await
+            .unwrap();
+        tokio::spawn(conn);
+        sender.send_request(req).await.unwrap()
+    } else {
+        let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(conn);
+        sender.send_request(req).await.unwrap()
+    };
+
+    let version = res.version();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    HttpsResponse {
+        alpn: negotiated,
+        version,
+        body: String::from_utf8(body.to_vec()).unwrap(),
+        certificate,
+    }
+}
+
+fn cert_der(pem: &str) -> CertificateDer<'static> {
+    CertificateDer::from_pem_slice(pem.as_bytes()).unwrap()
+}
+
+// **Feature: rust-web-framework, Property 52: TLS connections negotiate HTTP/2 or HTTP/1.1**
+// **Validates: Requirements 1.2, 1.3**
+fn prop_tls_negotiates_protocol(word: String, prefer_h2: bool) -> TestResult {
+    if word.is_empty() || !word.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return TestResult::discard();
+    }
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let (cert, key) = self_signed();
+        let tls = TlsConfig::from_pem(cert.as_bytes(), key.as_bytes()).unwrap();
+        let (port, stop, server) = spawn_tls_server(tls).await;
+
+        let alpn: &[&[u8]] = if prefer_h2 { &[b"h2", b"http/1.1"] } else { &[b"http/1.1"] };
+        let res = https_get(port, &format!("/echo/{}", word), &[&cert], alpn).await;
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+
+        let (expected_alpn, expected_version): (&[u8], _) = if prefer_h2 {
+            (b"h2", Version::HTTP_2)
+        } else {
+            (b"http/1.1", Version::HTTP_11)
+        };
+        TestResult::from_bool(
+            res.alpn.as_deref() == Some(expected_alpn)
+                && res.version == expected_version
+                && res.body == word,
+        )
+    })
+}
+
+#[test]
+fn test_tls_negotiates_protocol_property() {
+    QuickCheck::new()
+        .tests(10)
+        .quickcheck(prop_tls_negotiates_protocol as fn(String, bool) -> TestResult);
+}
+
+#[tokio::test]
+async fn test_alpn_protocols_can_be_restricted() {
+    let (cert, key) = self_signed();
+    let tls = TlsConfig::from_pem(cert.as_bytes(), key.as_bytes())
+        .unwrap()
+        .alpn_protocols(&["http/1.1"]);
+    let (port, stop, server) = spawn_tls_server(tls).await;
+
+    let res = https_get(port, "/echo/hi", &[&cert], &[b"h2", b"http/1.1"]).await;
+    assert_eq!(res.alpn.as_deref(), Some(&b"http/1.1"[..]));
+    assert_eq!(res.version, Version::HTTP_11);
+    assert_eq!(res.body, "hi");
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_stalled_handshakes_time_out() {
+    use tokio::io::AsyncReadExt;
+
+    let (cert, key) = self_signed();
+    let tls = TlsConfig::from_pem(cert.as_bytes(), key.as_bytes())
+        .unwrap()
+        .handshake_timeout(Duration::from_millis(100));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(Server::new(echo_app()).max_connections(1).serve_tls_with_shutdown(
+        listener,
+        tls,
+        async {
+            stopped.await.ok();
+        },
+    ));
+
+    // A client that never starts the handshake is disconnected
+    let mut idle = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let mut buf = [0; 16];
+    let closed = tokio::time::timeout(Duration::from_secs(5), idle.read(&mut buf)).await;
+    assert!(matches!(closed, Ok(Ok(0)) | Ok(Err(_))), "the stalled handshake was kept open");
+
+    // And its connection slot is free again
+    let res = tokio::time::timeout(
+        Duration::from_secs(5),
+        https_get(port, "/echo/hi", &[&cert], &[b"http/1.1"]),
+    )
+    .await
+    .expect("the connection slot was not released");
+    assert_eq!(res.body, "hi");
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+// **Feature: rust-web-framework, Property 53: Certificates reload without a restart**
+// **Validates: Requirements 1.1**
+#[tokio::test]
+async fn test_certificate_hot_reload() {
+    let dir = tempfile::tempdir().unwrap();
+    let cert_path = dir.path().join("cert.pem");
+    let key_path = dir.path().join("key.pem");
+
+    let (first_cert, first_key) = self_signed();
+    std::fs::write(&cert_path, &first_cert).unwrap();
+    std::fs::write(&key_path, &first_key).unwrap();
+
+    let tls = TlsConfig::from_pem_files(&cert_path, &key_path)
+        .unwrap()
+        .reload_interval(Duration::from_millis(20));
+    let handle = tls.clone();
+    let (port, stop, server) = spawn_tls_server(tls).await;
+
+    let res = https_get(port, "/echo/a", &[&first_cert], &[b"http/1.1"]).await;
+    assert_eq!(res.certificate, cert_der(&first_cert));
+
+    // The watcher picks up new files
+    let (second_cert, second_key) = self_signed();
+    std::fs::write(&key_path, &second_key).unwrap();
+    std::fs::write(&cert_path, &second_cert).unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let res = https_get(port, "/echo/b", &[&first_cert, &second_cert], &[b"http/1.1"]).await;
+    assert_eq!(res.certificate, cert_der(&second_cert));
+    assert_eq!(res.body, "b");
+
+    // A clone of the configuration can reload explicitly
+    let (third_cert, third_key) = self_signed();
+    std::fs::write(&key_path, &third_key).unwrap();
+    std::fs::write(&cert_path, &third_cert).unwrap();
+    handle.reload().unwrap();
+
+    let res = https_get(port, "/echo/c", &[&third_cert], &[b"http/1.1"]).await;
+    assert_eq!(res.certificate, cert_der(&third_cert));
+
+    // A broken certificate file keeps the current certificate
+    std::fs::write(&cert_path, "not a certificate").unwrap();
+    assert!(handle.reload().is_err());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let res = https_get(port, "/echo/d", &[&third_cert], &[b"http/1.1"]).await;
+    assert_eq!(res.certificate, cert_der(&third_cert));
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[test]
+fn test_invalid_tls_configuration() {
+    let (cert, key) = self_signed();
+    let (_, other_key) = self_signed();
+
+    assert!(TlsConfig::from_pem(b"", key.as_bytes()).is_err());
+    assert!(TlsConfig::from_pem(cert.as_bytes(), b"not a key").is_err());
+    // The key must belong to the certificate
+    assert!(TlsConfig::from_pem(cert.as_bytes(), other_key.as_bytes()).is_err());
+    assert!(TlsConfig::from_pem_files("/nonexistent/cert.pem", "/nonexistent/key.pem").is_err());
+
+    // Only configurations loaded from files can be reloaded
+    let tls = TlsConfig::from_pem(cert.as_bytes(), key.as_bytes()).unwrap();
+    assert!(tls.reload().is_err());
+}
@@ -0,0 +1,233 @@
+//! Property-based tests for WebSockets
+
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use quickcheck::{QuickCheck, TestResult};
+use ruffus::ws::{close_code, CloseFrame, Message};
+use ruffus::{App, Middleware, Next, Path, Request, Response, Server, State, WebSocketUpgrade};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite;
+
+// Marks requests as coming from a user, like an authentication middleware
+#[derive(Clone)]
+struct User(String);
+
+struct Authenticate;
+
+#[async_trait]
+impl Middleware for Authenticate {
+    async fn handle(&self, mut req: Request, next: Next) -> ruffus::Result<Response> {
+        req.extensions_mut().insert(User("alice".to_string()));
+        next.run(req).await
+    }
+}
+
+// Reports the close codes clients send
+type Closed = mpsc::UnboundedSender<u16>;
+
+fn ws_app(closed: Closed) -> App {
+    let mut app = App::with_state(closed);
+    app.use_middleware(Arc::new(Authenticate));
+
+    // Echoes messages back, prefixed with the room and the user
+    app.get("/rooms/:room", |Path(room): Path<String>, State(closed): State<Closed>, ws: WebSocketUpgrade, req: Request| async move {
+        let User(user) = req.extensions().get::<User>().unwrap().clone();
+        ws.protocols(["chat"]).on_upgrade(move |mut socket| async move {
+            let protocol = socket.protocol().unwrap_or("none").to_string();
+            while let Some(Ok(message)) = socket.recv().await {
+                let reply = match message {
+                    Message::Text(text) => Message::Text(format!("{}/{}/{}: {}", room, user, protocol, text)),
+                    Message::Binary(data) => Message::Binary(data),
+                    Message::Close(frame) => {
+                        let _ = closed.send(frame.map_or(0, |frame| frame.code));
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if socket.send(reply).await.is_err() {
+                    break;
+                }
+            }
+        })
+    });
+
+    app.get("/bye", |ws: WebSocketUpgrade| async move {
+        ws.on_upgrade(|mut socket| async move {
+            let frame = CloseFrame {
+                code: close_code::POLICY,
+                reason: "go away".to_string(),
+            };
+            socket.close(Some(frame)).await.unwrap();
+            while socket.recv().await.is_some() {}
+        })
+    });
+    app
+}
+
+async fn spawn_server(app: App) -> (u16, oneshot::Sender<()>, tokio::task::JoinHandle<ruffus::Result<()>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let handle = tokio::spawn(Server::new(app).serve_with_shutdown(listener, async {
+        stopped.await.ok();
+    }));
+    (port, stop, handle)
+}
+
+type Client = tokio_tungstenite::WebSocketStream<TcpStream>;
+
+async fn ws_connect(port: u16, path: &str, protocol: Option<&str>) -> (Client, http::Response<Option<Vec<u8>>>) {
+    use tungstenite::client::IntoClientRequest;
+
+    let mut request = format!("ws://127.0.0.1:{}{}", port, path).into_client_request().unwrap();
+    if let Some(protocol) = protocol {
+        request.headers_mut().insert("sec-websocket-protocol", protocol.parse().unwrap());
+    }
+    let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    tokio_tungstenite::client_async(request, stream).await.unwrap()
+}
+
+// **Feature: rust-web-framework, Property 61: WebSocket messages round-trip**
+// **Validates: Requirements 2.3, 4.1**
+fn prop_websocket_messages_round_trip(room: String, texts: Vec<String>, data: Vec<u8>) -> TestResult {
+    if room.is_empty() || !room.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return TestResult::discard();
+    }
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let (closed, _) = mpsc::unbounded_channel();
+        let (port, stop, server) = spawn_server(ws_app(closed)).await;
+        let (mut client, response) = ws_connect(port, &format!("/rooms/{}", room), Some("other, chat")).await;
+        let mut ok = response.headers().get("sec-websocket-protocol").unwrap() == "chat";
+
+        for text in &texts {
+            client.send(tungstenite::Message::text(text.clone())).await.unwrap();
+            let reply = client.next().await.unwrap().unwrap();
+            ok &= reply.to_text().unwrap() == format!("{}/alice/chat: {}", room, text);
+        }
+        client.send(tungstenite::Message::binary(data.clone())).await.unwrap();
+        ok &= client.next().await.unwrap().unwrap().into_data() == data;
+
+        client.close(None).await.unwrap();
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+        TestResult::from_bool(ok)
+    })
+}
+
+#[test]
+fn test_websocket_messages_round_trip_property() {
+    QuickCheck::new()
+        .tests(10)
+        .quickcheck(prop_websocket_messages_round_trip as fn(String, Vec<String>, Vec<u8>) -> TestResult);
+}
+
+// Writes a masked client frame
+async fn write_frame(stream: &mut TcpStream, fin: bool, opcode: u8, payload: &[u8]) {
+    let mask = [1u8, 2, 3, 4];
+    let mut frame = vec![if fin { 0x80 } else { 0 } | opcode, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    stream.write_all(&frame).await.unwrap();
+}
+
+// Reads an unmasked server frame with a short payload
+async fn read_frame(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut head = [0u8; 2];
+    stream.read_exact(&mut head).await.unwrap();
+    let mut payload = vec![0u8; (head[1] & 0x7f) as usize];
+    stream.read_exact(&mut payload).await.unwrap();
+    (head[0] & 0x0f, payload)
+}
+
+#[tokio::test]
+async fn test_fragmented_messages_and_close_codes() {
+    let (closed, mut close_codes) = mpsc::unbounded_channel();
+    let (port, stop, server) = spawn_server(ws_app(closed)).await;
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(
+            b"GET /rooms/lobby HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+              Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    let head = String::from_utf8(head).unwrap();
+    assert!(head.starts_with("HTTP/1.1 101 Switching Protocols"), "{}", head);
+    // The accept key from the example in RFC 6455
+    assert!(head.contains("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="), "{}", head);
+
+    // A text message split over a text frame and two continuation frames
+    write_frame(&mut stream, false, 0x1, b"hel").await;
+    write_frame(&mut stream, false, 0x0, b"lo ").await;
+    write_frame(&mut stream, true, 0x0, b"world").await;
+    let (opcode, payload) = read_frame(&mut stream).await;
+    assert_eq!(opcode, 0x1);
+    assert_eq!(payload, b"lobby/alice/none: hello world");
+
+    // The close frame is echoed and the handler sees its code
+    write_frame(&mut stream, true, 0x8, &[0x03, 0xe9]).await;
+    let (opcode, payload) = read_frame(&mut stream).await;
+    assert_eq!((opcode, payload.as_slice()), (0x8, &[0x03, 0xe9][..]));
+    assert_eq!(close_codes.recv().await, Some(close_code::AWAY));
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_server_close_frame() {
+    let (port, stop, server) = spawn_server(ws_app(mpsc::unbounded_channel().0)).await;
+
+    let (mut client, _) = ws_connect(port, "/bye", None).await;
+    match client.next().await.unwrap().unwrap() {
+        tungstenite::Message::Close(Some(frame)) => {
+            assert_eq!(u16::from(frame.code), close_code::POLICY);
+            assert_eq!(frame.reason.as_str(), "go away");
+        }
+        other => panic!("expected a close frame, got {:?}", other),
+    }
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_plain_requests_are_not_upgraded() {
+    let (port, stop, server) = spawn_server(ws_app(mpsc::unbounded_channel().0)).await;
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(b"GET /bye HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 426 Upgrade Required"), "{}", response);
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(
+            b"GET /bye HTTP/1.1\r\nHost: localhost\r\nConnection: upgrade\r\nUpgrade: websocket\r\n\
+              Sec-WebSocket-Version: 8\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut status_line = Vec::new();
+    while !status_line.ends_with(b"\r\n") {
+        status_line.push(stream.read_u8().await.unwrap());
+    }
+    assert_eq!(status_line, b"HTTP/1.1 400 Bad Request\r\n");
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
@@ -20,3 +20,46 @@ mod middleware_properties;
 
 #[path = "property/extractor_properties.rs"]
 mod extractor_properties;
+
+#[path = "property/server_properties.rs"]
+mod server_properties;
+
+#[cfg(feature = "tls")]
+#[path = "property/tls_properties.rs"]
+mod tls_properties;
+
+#[path = "property/listener_properties.rs"]
+mod listener_properties;
+
+#[path = "property/body_properties.rs"]
+mod body_properties;
+
+#[path = "property/sse_properties.rs"]
+mod sse_properties;
+
+#[cfg(feature = "ws")]
+#[path = "property/ws_properties.rs"]
+mod ws_properties;
+
+#[path = "property/fs_properties.rs"]
+mod fs_properties;
+
+#[cfg(feature = "multipart")]
+#[path = "property/multipart_properties.rs"]
+mod multipart_properties;
+
+#[path = "property/cors_properties.rs"]
+mod cors_properties;
+
+#[cfg(feature = "compression")]
+#[path = "property/compression_properties.rs"]
+mod compression_properties;
+
+#[path = "property/rate_limit_properties.rs"]
+mod rate_limit_properties;
+
+#[path = "property/timeout_properties.rs"]
+mod timeout_properties;
+
+#[path = "property/concurrency_limit_properties.rs"]
+mod concurrency_limit_properties;